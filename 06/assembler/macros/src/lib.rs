@@ -0,0 +1,285 @@
+//! Procedural macros that run the Hack assembler at compile time, so that small machine code
+//! programs can be embedded in Rust code, such as test suites for a Hack CPU, and kept as
+//! readable assembly.
+//!
+//! The assembler is used without its command line features, through the in-memory 'assemble'
+//! function of the 'assembler' crate.
+
+use proc_macro::{Delimiter, Group, Ident, Literal, Punct, Spacing, Span, TokenStream, TokenTree};
+use assembler::SymbolTable;
+
+/// Assembles a Hack assembly program at compile time, expanding to an array expression of type
+/// '[u16; N]' holding its machine instructions.
+///
+/// Instructions are written as they would be in a source file, without spaces inside them, and
+/// are separated by spaces or new lines.  A directive, such as '.global NAME', takes the symbols
+/// and numbers that follow it as its operands.  Comments are written as Rust comments, which are the
+/// same as assembly comments.  The predefined symbols are available, and variables are allocated
+/// from RAM address 16.
+///
+/// An error in the program is reported as a compile error at the token that caused it.
+///
+/// # Examples
+///
+/// '''
+/// use assembler_macros::hack_asm;
+///
+/// // RAM[0] = 2 + 3
+/// const ADD: [u16; 6] = hack_asm!{ @2 D=A @3 D=D+A @0 M=D };
+///
+/// assert_eq!(ADD[1], 0b1110_1100_0001_0000);
+/// '''
+#[proc_macro]
+pub fn hack_asm(input: TokenStream) -> TokenStream {
+    match assemble(input) {
+        Ok(words) => array(&words),
+        Err((message, span)) => compile_error(&message, span),
+    }
+}
+
+/// A piece of source text taken from a single token, along with the span of that token.
+///
+#[derive(Debug)]
+struct Piece<S> {
+    text: String,
+    span: S,
+}
+
+/// Assembles the tokens passed to the macro, returning the machine instructions, or an error
+/// message with the span of the token it should be reported at.
+///
+fn assemble(input: TokenStream) -> Result<Vec<u16>, (String, Span)> {
+    let instructions = instructions(input)?;
+
+    match assembler::assemble(&source(&instructions), SymbolTable::new()) {
+        Ok(assembly) => Ok(assembly.words),
+        Err(e) => {
+            let span = locate(&instructions, e.span()).unwrap_or_else(Span::call_site);
+            let message = match e.help() {
+                Some(help) => format!("{}\nhelp: {}", e.message(), help),
+                None => e.message(),
+            };
+
+            Err((message, span))
+        },
+    }
+}
+
+/// Divides the tokens into instructions.  Tokens that touch, with no space between them, belong
+/// to the same word, and each word is an instruction unless it is an operand of a directive.  A
+/// label in parentheses is taken as a single piece.
+///
+fn instructions(input: TokenStream) -> Result<Vec<Vec<Piece<Span>>>, (String, Span)> {
+    let mut words: Vec<Vec<Piece<Span>>> = Vec::new();
+    let mut last: Option<Span> = None;
+
+    for tree in input {
+        let span = tree.span();
+        let piece = Piece { text: token_text(&tree)?, span };
+
+        match words.last_mut() {
+            Some(word) if last.is_some_and(|last| touching(last, span)) => word.push(piece),
+            _ => words.push(vec![piece]),
+        }
+
+        last = Some(span);
+    }
+
+    Ok(join_operands(words))
+}
+
+/// Joins each directive with the words that follow it while they are symbols or numbers, which
+/// are its operands, keeping a space before each operand.
+///
+fn join_operands<S>(words: Vec<Vec<Piece<S>>>) -> Vec<Vec<Piece<S>>> {
+    let mut instructions: Vec<Vec<Piece<S>>> = Vec::new();
+
+    for mut word in words {
+        match instructions.last_mut() {
+            Some(instruction) if instruction[0].text == "." && is_operand(&word) => {
+                word[0].text.insert(0, ' ');
+                instruction.extend(word);
+            },
+            _ => instructions.push(word),
+        }
+    }
+
+    instructions
+}
+
+/// Returns true if the word is a symbol or number, made up of letters, digits and any of '_.$'.
+///
+fn is_operand<S>(word: &[Piece<S>]) -> bool {
+    word.iter().all(|piece| {
+        piece.text.chars().all(|c| c.is_ascii_alphanumeric() || "_.$".contains(c))
+    })
+}
+
+/// Returns the source text of a token.  The contents of a group are joined, keeping a space
+/// wherever there was one so that the assembler can report it.
+///
+fn token_text(tree: &TokenTree) -> Result<String, (String, Span)> {
+    let group = match tree {
+        TokenTree::Group(group) => group,
+        TokenTree::Ident(ident) => return Ok(ident.to_string()),
+        TokenTree::Punct(punct) => return Ok(punct.as_char().to_string()),
+        TokenTree::Literal(literal) => return Ok(literal.to_string()),
+    };
+
+    if group.delimiter() != Delimiter::Parenthesis {
+        return Err((String::from("only labels can be enclosed in brackets, as '(LABEL)'"),
+            group.span()));
+    }
+
+    let mut text = String::from("(");
+    let mut last: Option<Span> = None;
+
+    for tree in group.stream() {
+        if last.is_some_and(|last| !touching(last, tree.span())) {
+            text.push(' ');
+        }
+
+        text.push_str(&token_text(&tree)?);
+        last = Some(tree.span());
+    }
+
+    text.push(')');
+
+    Ok(text)
+}
+
+/// Returns true if the second token starts exactly where the first ends.
+///
+fn touching(first: Span, second: Span) -> bool {
+    let (end, start) = (first.end(), second.start());
+
+    end.line() == start.line() && end.column() == start.column()
+}
+
+/// Returns the assembly source for the instructions, one per line.
+///
+fn source<S>(instructions: &[Vec<Piece<S>>]) -> String {
+    let mut source = String::new();
+
+    for instruction in instructions {
+        for piece in instruction {
+            source.push_str(&piece.text);
+        }
+        source.push('\n');
+    }
+
+    source
+}
+
+/// Finds the token at the start of an error's span within the source built from the
+/// instructions.
+///
+fn locate<S: Copy>(instructions: &[Vec<Piece<S>>], span: Option<assembler::Span>) -> Option<S> {
+    let span = span?;
+    let instruction = instructions.get(span.line.checked_sub(1)?)?;
+    let mut offset = 0;
+
+    for piece in instruction {
+        offset += piece.text.chars().count();
+
+        if span.column <= offset {
+            return Some(piece.span);
+        }
+    }
+
+    instruction.last().map(|piece| piece.span)
+}
+
+/// Returns the tokens for an array expression of the given words, as '[2u16, 3u16]'.
+///
+fn array(words: &[u16]) -> TokenStream {
+    let mut elements = TokenStream::new();
+
+    for &word in words {
+        elements.extend([
+            TokenTree::Literal(Literal::u16_suffixed(word)),
+            TokenTree::Punct(Punct::new(',', Spacing::Alone)),
+        ]);
+    }
+
+    TokenTree::Group(Group::new(Delimiter::Bracket, elements)).into()
+}
+
+/// Returns the tokens for '::core::compile_error!("message")', with every token given the span
+/// of the error so that the compiler points at it.
+///
+fn compile_error(message: &str, span: Span) -> TokenStream {
+    let mut message = TokenTree::Literal(Literal::string(message));
+    message.set_span(span);
+
+    let tokens = [
+        TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+        TokenTree::Ident(Ident::new("core", span)),
+        TokenTree::Punct(Punct::new(':', Spacing::Joint)),
+        TokenTree::Punct(Punct::new(':', Spacing::Alone)),
+        TokenTree::Ident(Ident::new("compile_error", span)),
+        TokenTree::Punct(Punct::new('!', Spacing::Alone)),
+        TokenTree::Group(Group::new(Delimiter::Parenthesis, message.into())),
+    ];
+
+    IntoIterator::into_iter(tokens)
+        .map(|mut token| {
+            token.set_span(span);
+            token
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn pieces(instructions: &[&[&str]]) -> Vec<Vec<Piece<usize>>> {
+        let mut index = 0;
+
+        instructions.iter()
+            .map(|instruction| {
+                instruction.iter()
+                    .map(|text| {
+                        index += 1;
+                        Piece { text: String::from(*text), span: index }
+                    })
+                    .collect()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn build_source() {
+        let instructions = pieces(&[&["(LOOP)"], &["@", "LOOP"], &["0", ";", "JMP"]]);
+
+        assert_eq!(source(&instructions), "(LOOP)\n@LOOP\n0;JMP\n");
+    }
+
+    #[test]
+    fn join_directive_operands() {
+        let words = pieces(&[&[".", "global"], &["Main", ".", "run"], &["i"], &["(LOOP)"],
+            &["@", "i"], &[".", "global"], &["j"]]);
+
+        assert_eq!(
+            source(&join_operands(words)),
+            ".global Main.run i\n(LOOP)\n@i\n.global j\n",
+        );
+    }
+
+    #[test]
+    fn locate_error_token() {
+        // @1  D=D+Q;JMP
+        let instructions = pieces(&[&["@", "1"], &["D", "=", "D", "+", "Q", ";", "JMP"]]);
+
+        let at = |line, column| locate(&instructions, Some(assembler::Span { line, column,
+            length: 1 }));
+
+        assert_eq!(at(1, 1), Some(1));
+        assert_eq!(at(2, 3), Some(5));
+        assert_eq!(at(2, 7), Some(9));
+        assert_eq!(at(3, 1), None);
+        assert_eq!(locate(&instructions, None), None);
+    }
+}
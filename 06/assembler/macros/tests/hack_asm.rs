@@ -0,0 +1,60 @@
+use assembler_macros::hack_asm;
+
+#[test]
+fn assemble_program() {
+    // RAM[0] = 2 + 3
+    const ADD: [u16; 6] = hack_asm!{ @2 D=A @3 D=D+A @0 M=D };
+
+    assert_eq!(
+        ADD,
+        [
+            0b0000_0000_0000_0010,
+            0b1110_1100_0001_0000,
+            0b0000_0000_0000_0011,
+            0b1110_0000_1001_0000,
+            0b0000_0000_0000_0000,
+            0b1110_0011_0000_1000,
+        ],
+    );
+}
+
+#[test]
+fn labels_variables_and_comments() {
+    let words = hack_asm! {
+        // Counts down from 3 in 'i'.
+        @3
+        D=A
+        @i
+        M=D
+    (LOOP)
+        @i
+        MD=M-1      // i = i - 1
+        @LOOP
+        D;JGT
+    (END)
+        @END
+        0;JMP
+    };
+
+    assert_eq!(
+        words,
+        [3, 0xec10, 16, 0xe308, 16, 0xfc98, 4, 0xe301, 8, 0xea87],
+    );
+}
+
+#[test]
+fn predefined_symbols() {
+    assert_eq!(hack_asm!(@SCREEN @KBD @R15 @SP @THIS), [16384, 24576, 15, 0, 3]);
+}
+
+#[test]
+fn directive_operands() {
+    assert_eq!(hack_asm!{ .global START i (START) @i 0;JMP }, [16, 0xea87]);
+}
+
+#[test]
+fn errors_at_token() {
+    let cases = trybuild::TestCases::new();
+
+    cases.compile_fail("tests/ui/*.rs");
+}
@@ -0,0 +1,12 @@
+use assembler_macros::hack_asm;
+
+const PROGRAM: [u16; 4] = hack_asm! {
+    @2
+    D=A
+    @3
+    D=D+Q
+};
+
+fn main() {
+    let _ = PROGRAM;
+}
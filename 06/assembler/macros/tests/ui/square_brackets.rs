@@ -0,0 +1,7 @@
+use assembler_macros::hack_asm;
+
+const PROGRAM: [u16; 2] = hack_asm! { @2 [LOOP] };
+
+fn main() {
+    let _ = PROGRAM;
+}
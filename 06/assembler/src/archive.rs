@@ -0,0 +1,197 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use crate::error::{Error, ErrorKind, Result, Span};
+use crate::object::Object;
+
+/// The file extension of static libraries.
+///
+pub const EXTENSION: &str = "hlib";
+
+/// The first line of every archive, naming the format and its version.
+///
+const HEADER: &str = "hacklib 1";
+
+/// A static library of relocatable objects, so that routines shared by many programs can be
+/// assembled once and linked into each.  When linking, only the members that define a symbol
+/// the program still needs are included.
+///
+/// Each member is named, usually after the object file it was made from, and is written as a
+/// 'member' line followed by the text of the object:
+///
+/// '''
+/// hacklib 1
+/// member Math.hobj
+/// hackobj 1
+/// word 0000
+/// export Math.multiply 0
+/// member Screen.hobj
+/// hackobj 1
+/// '''
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Archive {
+    pub members: Vec<(String, Object)>,
+}
+
+impl Archive {
+    /// Creates an archive of the given members.  Member names must be unique, and cannot be
+    /// empty or contain line breaks.
+    ///
+    pub fn new(members: Vec<(String, Object)>) -> Result<Archive> {
+        let mut names = HashSet::new();
+
+        for (name, _) in members.iter() {
+            if name.trim().is_empty() || name.trim() != name || name.contains('\n') {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidObjectFile,
+                    format!("'{}' is not a valid archive member name", name),
+                ));
+            }
+
+            if !names.insert(name) {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidObjectFile,
+                    format!("the archive has more than one member named '{}'", name),
+                ));
+            }
+        }
+
+        Ok(Archive { members })
+    }
+}
+
+impl fmt::Display for Archive {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+
+        for (name, object) in self.members.iter() {
+            writeln!(f, "member {}", name)?;
+            write!(f, "{}", object)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Archive {
+    type Err = Error;
+
+    /// Reads an archive from its text form, as written by 'Display'.  Errors carry the span of
+    /// the line at fault, including those within a member.
+    ///
+    fn from_str(s: &str) -> Result<Archive> {
+        let lines: Vec<&str> = s.lines().collect();
+        let mut header = false;
+        let mut starts = Vec::new();
+
+        for (index, line) in lines.iter().enumerate() {
+            let span = Span { line: index + 1, column: 1, length: line.chars().count() };
+
+            if line.trim().is_empty() {
+                continue;
+            }
+
+            if !header {
+                if line.split_whitespace().collect::<Vec<&str>>().join(" ") != HEADER {
+                    return Err(Error::with_message(
+                        ErrorKind::InvalidObjectFile,
+                        format!("expected '{}' at the start of the archive", HEADER),
+                    ).with_span(Some(span))
+                        .with_help(String::from("archives are written by 'hack archive'")));
+                }
+                header = true;
+                continue;
+            }
+
+            match line.strip_prefix("member ") {
+                Some(name) => starts.push((index, name.trim())),
+                None if starts.is_empty() => {
+                    return Err(Error::with_message(
+                        ErrorKind::InvalidObjectFile,
+                        String::from("expected a 'member' line before the first object"),
+                    ).with_span(Some(span)));
+                },
+                None => (),
+            }
+        }
+
+        if !header {
+            return Err(Error::with_message(
+                ErrorKind::InvalidObjectFile,
+                String::from("the archive is empty"),
+            ));
+        }
+
+        let mut members = Vec::with_capacity(starts.len());
+
+        for (i, &(start, name)) in starts.iter().enumerate() {
+            let end = starts.get(i + 1).map_or(lines.len(), |&(end, _)| end);
+            let text = lines[start + 1..end].join("\n");
+
+            members.push((String::from(name), Object::parse(&text, start + 2)?));
+        }
+
+        Archive::new(members)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object;
+    use crate::symbols::SymbolTable;
+
+    fn member(name: &str, source: &str) -> (String, Object) {
+        let module = name.trim_end_matches(".hobj");
+
+        (String::from(name), object::assemble(source, module, SymbolTable::new()).unwrap().0)
+    }
+
+    #[test]
+    fn archive_text() {
+        let archive = Archive::new(vec![
+            member("Math.hobj", ".global Math.inc\n(Math.inc)\nD=D+1\n"),
+            member("Screen.hobj", "@SCREEN\n"),
+        ]).unwrap();
+
+        let text = archive.to_string();
+
+        assert_eq!(text, "\
+            hacklib 1\n\
+            member Math.hobj\n\
+            hackobj 1\n\
+            word e7d0\n\
+            export Math.inc 0\n\
+            member Screen.hobj\n\
+            hackobj 1\n\
+            word 4000\n\
+            ");
+        assert_eq!(text.parse::<Archive>().unwrap(), archive);
+    }
+
+    #[test]
+    fn invalid_archive_text() {
+        let cases = [
+            ("", "the archive is empty", None),
+            ("hackobj 1\n", "expected 'hacklib 1' at the start of the archive", Some(1)),
+            ("hacklib 1\nhackobj 1\n", "expected a 'member' line before the first object", Some(2)),
+            ("hacklib 1\nmember A\nhackobj 1\nmember B\nhackobj 1\nword 1\n",
+                "'1' is not a 4-digit machine word", Some(6)),
+            ("hacklib 1\nmember A\nhackobj 1\nmember A\nhackobj 1\n",
+                "the archive has more than one member named 'A'", None),
+        ];
+
+        for (text, message, line) in cases.iter() {
+            let error = text.parse::<Archive>().unwrap_err();
+
+            assert_eq!(error.kind(), Some(ErrorKind::InvalidObjectFile));
+            assert_eq!(error.message(), *message);
+            assert_eq!(error.span().map(|span| span.line), *line);
+        }
+
+        let error = Archive::new(vec![member(" A", "")]).unwrap_err();
+
+        assert_eq!(error.message(), "' A' is not a valid archive member name");
+    }
+}
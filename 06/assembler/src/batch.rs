@@ -0,0 +1,190 @@
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::thread;
+use crate::config::Config;
+use crate::error::{Error, Result};
+use crate::runner::{self, Assembly};
+
+/// A set of assemblies carried out together, spread across a number of worker threads.
+///
+#[derive(Debug, PartialEq)]
+pub struct Batch {
+    pub configs: Vec<Config>,
+    pub jobs: usize,
+}
+
+/// The result of assembling one input file of a Batch.
+///
+#[derive(Debug)]
+pub struct Outcome {
+    pub infile: PathBuf,
+    pub outfile: PathBuf,
+    pub result: Result<Assembly>,
+}
+
+impl Batch {
+    /// Creates a Batch that runs the given assemblies on as many threads as the machine can run
+    /// in parallel.
+    ///
+    /// # Examples
+    ///
+    /// '''
+    /// use assembler::batch::Batch;
+    /// use assembler::config::Config;
+    ///
+    /// let batch = Batch::new(vec![Config::for_input("Add.asm"), Config::for_input("Max.asm")]);
+    ///
+    /// for outcome in batch.run() {
+    ///     println!("{}: {}", outcome.infile.display(), outcome.result.is_ok());
+    /// }
+    /// '''
+    pub fn new(configs: Vec<Config>) -> Batch {
+        let jobs = thread::available_parallelism().map_or(1, |n| n.get());
+
+        Batch { configs, jobs }
+    }
+
+    /// Runs every assembly in the Batch, returning an Outcome for each Config in the order they
+    /// were given.  A failed assembly does not stop the others, and any error it returns is
+    /// tagged with the file it relates to.
+    ///
+    pub fn run(self) -> Vec<Outcome> {
+        let count = self.configs.len();
+        let workers = self.jobs.clamp(1, std::cmp::max(count, 1));
+
+        // Configs are handed out from the front of the queue, so files are started in order.
+        let queue = Mutex::new(self.configs.into_iter().enumerate().rev().collect::<Vec<_>>());
+        let outcomes = Mutex::new((0..count).map(|_| None).collect::<Vec<Option<Outcome>>>());
+
+        thread::scope(|scope| {
+            for _ in 0..workers {
+                scope.spawn(|| loop {
+                    let (index, config) = match queue.lock().unwrap().pop() {
+                        Some(job) => job,
+                        None => break,
+                    };
+
+                    let infile = config.infile.clone();
+                    let outfile = config.outfile.clone();
+                    let result = runner::run(config);
+
+                    outcomes.lock().unwrap()[index] = Some(Outcome { infile, outfile, result });
+                });
+            }
+        });
+
+        outcomes.into_inner().unwrap().into_iter().map(Option::unwrap).collect()
+    }
+}
+
+/// Returns the paths of all '.asm' files within a directory and its subdirectories, sorted so
+/// that the order is the same on every platform.
+///
+/// Symbolic links to directories are not followed.
+///
+pub fn find_sources(dir: &Path) -> Result<Vec<PathBuf>> {
+    let mut sources = Vec::new();
+
+    visit(dir, &mut sources)?;
+    sources.sort();
+
+    Ok(sources)
+}
+
+fn visit(dir: &Path, sources: &mut Vec<PathBuf>) -> Result<()> {
+    let entries = fs::read_dir(dir).map_err(|e| Error::from(e).with_file(dir))?;
+
+    for entry in entries {
+        let entry = entry.map_err(|e| Error::from(e).with_file(dir))?;
+        let path = entry.path();
+        let file_type = entry.file_type().map_err(|e| Error::from(e).with_file(&path))?;
+
+        if file_type.is_dir() {
+            visit(&path, sources)?;
+        } else if path.extension() == Some(OsStr::new("asm")) {
+            sources.push(path);
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_sources_recursively() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+
+        fs::create_dir_all(dir.join("sub/deeper")).unwrap();
+
+        for name in ["b.asm", "a.asm", "notes.txt", "sub/c.asm", "sub/deeper/d.asm", "sub/e.hack"] {
+            fs::write(dir.join(name), "").unwrap();
+        }
+
+        let sources = find_sources(dir).unwrap();
+
+        assert_eq!(
+            sources,
+            vec![
+                dir.join("a.asm"),
+                dir.join("b.asm"),
+                dir.join("sub/c.asm"),
+                dir.join("sub/deeper/d.asm"),
+            ],
+        );
+    }
+
+    #[test]
+    fn find_sources_in_missing_directory() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path().join("missing");
+
+        let err = find_sources(&dir).unwrap_err();
+
+        assert_eq!(err.file(), Some(dir.as_path()));
+    }
+
+    #[test]
+    fn run_batch_in_order() {
+        let temp = tempfile::tempdir().unwrap();
+        let dir = temp.path();
+        let mut configs = Vec::new();
+
+        for i in 0..6 {
+            let infile = dir.join(format!("Prog{}.asm", i));
+            let source = match i {
+                3 => String::from("@1\nD=D+Q\n"),
+                _ => format!("@{}\nD=A\n", i),
+            };
+
+            fs::write(&infile, source).unwrap();
+            configs.push(Config::for_input(infile));
+        }
+
+        let outcomes = Batch { configs, jobs: 4 }.run();
+
+        assert_eq!(outcomes.len(), 6);
+
+        for (i, outcome) in outcomes.iter().enumerate() {
+            assert_eq!(outcome.infile, dir.join(format!("Prog{}.asm", i)));
+
+            match i {
+                3 => {
+                    let err = outcome.result.as_ref().unwrap_err();
+
+                    assert_eq!(err.file(), Some(outcome.infile.as_path()));
+                    assert!(!outcome.outfile.exists());
+                },
+                _ => {
+                    assert_eq!(outcome.result.as_ref().unwrap().words[0], i as u16);
+                    assert!(outcome.outfile.exists());
+                },
+            }
+        }
+    }
+}
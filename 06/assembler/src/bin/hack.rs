@@ -0,0 +1,8 @@
+use std::env;
+use std::process;
+
+fn main() {
+    env_logger::init();
+
+    process::exit(assembler::cli::hack(env::args_os()));
+}
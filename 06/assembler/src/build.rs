@@ -0,0 +1,351 @@
+use std::collections::HashMap;
+use std::env;
+use std::fmt::Write as FmtWrite;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use crate::diagnostics::{self, Diagnostic};
+use crate::error::{Error, ErrorKind, Result};
+use crate::runner::{self, Assembly};
+use crate::symbols::SymbolTable;
+
+/// The name of the generated module when none is given.
+///
+const DEFAULT_MODULE: &str = "hack_programs";
+
+/// Strict and reserved keywords, which cannot be used as module names.
+///
+const KEYWORDS: [&str; 51] = [
+    "as", "break", "const", "continue", "crate", "else", "enum", "extern", "false", "fn", "for",
+    "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return",
+    "self", "static", "struct", "super", "trait", "true", "type", "unsafe", "use", "where",
+    "while", "async", "await", "dyn", "abstract", "become", "box", "do", "final", "macro",
+    "override", "priv", "typeof", "unsized", "virtual", "yield", "try", "gen",
+];
+
+/// Assembles Hack programs from a cargo build script, generating a Rust module that holds the
+/// ROM image and symbol addresses of each, so that crates which embed Hack programs stay in sync
+/// with their sources.
+///
+/// Each source file becomes a module named after the file, in lower case, containing a 'ROM'
+/// array and 'labels' and 'variables' modules of constants named after the symbols, in upper
+/// case.  Characters that cannot appear in identifiers become '_'.
+///
+/// # Examples
+///
+/// In 'build.rs':
+///
+/// '''
+/// fn main() {
+///     assembler::build::Builder::new()
+///         .file("src/boot.asm")
+///         .build()
+///         .unwrap();
+/// }
+/// '''
+///
+/// And in the crate:
+///
+/// '''
+/// include!(concat!(env!("OUT_DIR"), "/hack_programs.rs"));
+///
+/// let rom: &[u16] = &boot::ROM;
+/// let start = boot::labels::START;
+/// '''
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Builder {
+    files: Vec<PathBuf>,
+    out_dir: Option<PathBuf>,
+    module: Option<String>,
+    defines: Vec<(String, u16)>,
+}
+
+impl Builder {
+    /// Creates a Builder with no source files, which writes its module to the 'OUT_DIR' that
+    /// cargo gives the build script.
+    ///
+    pub fn new() -> Builder {
+        Builder::default()
+    }
+
+    /// Adds a source file to be assembled.
+    ///
+    pub fn file<P: AsRef<Path>>(&mut self, path: P) -> &mut Builder {
+        self.files.push(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the directory that the generated module is written to, in place of 'OUT_DIR'.
+    ///
+    pub fn out_dir<P: AsRef<Path>>(&mut self, dir: P) -> &mut Builder {
+        self.out_dir = Some(dir.as_ref().to_path_buf());
+        self
+    }
+
+    /// Sets the name of the generated module, which is written to '<NAME>.rs'.  The default is
+    /// 'hack_programs'.
+    ///
+    pub fn module(&mut self, name: &str) -> &mut Builder {
+        self.module = Some(String::from(name));
+        self
+    }
+
+    /// Defines a symbol with a constant value in every program, as '--define' does.
+    ///
+    pub fn define(&mut self, name: &str, value: u16) -> &mut Builder {
+        self.defines.push((String::from(name), value));
+        self
+    }
+
+    /// Assembles the source files and writes the generated module, returning its path.
+    ///
+    /// A 'cargo:rerun-if-changed' line is printed for each source file, so that cargo runs the
+    /// build script again whenever one changes.  An error is also written to stderr as a
+    /// diagnostic, which cargo shows when the build script fails.
+    ///
+    pub fn build(&self) -> Result<PathBuf> {
+        let generated = match self.out_dir {
+            Some(ref dir) => self.generate(dir, &mut io::stdout()),
+            None => match env::var_os("OUT_DIR") {
+                Some(dir) => self.generate(Path::new(&dir), &mut io::stdout()),
+                None => Err(Error::with_message(
+                    ErrorKind::InvalidBuildConfig,
+                    String::from("OUT_DIR is not set, so an output directory must be given"),
+                )),
+            },
+        };
+
+        if let Err(ref e) = generated {
+            let _ = diagnostics::emit_human(&mut io::stderr(), &[Diagnostic::from(e)], false);
+        }
+
+        generated
+    }
+
+    /// Writes the cargo instructions to the given writer and the generated module to the output
+    /// directory.
+    ///
+    fn generate<W: Write>(&self, out_dir: &Path, cargo: &mut W) -> Result<PathBuf> {
+        for file in self.files.iter() {
+            writeln!(cargo, "cargo:rerun-if-changed={}", file.display())?;
+        }
+
+        let mut modules: HashMap<String, &Path> = HashMap::new();
+        let mut names = Vec::new();
+
+        for file in self.files.iter() {
+            let name = module_name(&file.file_stem().unwrap_or_default().to_string_lossy());
+
+            names.push(name.clone());
+
+            if let Some(other) = modules.insert(name.clone(), file) {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidBuildConfig,
+                    format!("'{}' and '{}' would both become the module '{}'",
+                        other.display(), file.display(), name),
+                ));
+            }
+        }
+
+        let mut code = String::from(
+            "// Generated by 'assembler::build' from Hack assembly sources.  Do not edit.\n");
+
+        for (file, name) in self.files.iter().zip(names.iter()) {
+            let mut symbols = SymbolTable::new();
+
+            for (name, value) in self.defines.iter() {
+                symbols.insert_constant(name, *value).map_err(|e| e.with_file(file))?;
+            }
+
+            let assembly = runner::assemble_file(file, symbols)?;
+
+            write_module(&mut code, name, file, &assembly).map_err(|e| e.with_file(file))?;
+        }
+
+        let module = self.module.as_deref().unwrap_or(DEFAULT_MODULE);
+        let path = out_dir.join(format!("{}.rs", module));
+
+        runner::write_output(&path, code.as_bytes()).map_err(|e| e.with_file(&path))?;
+
+        Ok(path)
+    }
+}
+
+/// Appends the module for one program to the generated code.
+///
+fn write_module(code: &mut String, name: &str, file: &Path, assembly: &Assembly) -> Result<()> {
+    writeln!(code, "\n/// Assembled from '{}'.", file.display()).unwrap();
+    writeln!(code, "pub mod {} {{", name).unwrap();
+    writeln!(code, "    /// The machine instructions of the program, in ROM order.").unwrap();
+    writeln!(code, "    pub const ROM: [u16; {}] = [", assembly.words.len()).unwrap();
+
+    for words in assembly.words.chunks(8) {
+        let words: Vec<String> = words.iter().map(|word| format!("0x{:04x},", word)).collect();
+
+        writeln!(code, "        {}", words.join(" ")).unwrap();
+    }
+
+    writeln!(code, "    ];").unwrap();
+
+    let tables = [
+        ("labels", "ROM addresses of the program's labels", assembly.symbols.labels()),
+        ("variables", "RAM addresses of the program's variables", assembly.symbols.variables()),
+    ];
+
+    for (table, description, symbols) in tables.iter() {
+        let mut constants: HashMap<String, &str> = HashMap::new();
+
+        writeln!(code, "\n    /// The {}.", description).unwrap();
+        writeln!(code, "    pub mod {} {{", table).unwrap();
+
+        for &(symbol, address) in symbols {
+            let constant = constant_name(symbol);
+
+            if let Some(other) = constants.insert(constant.clone(), symbol) {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidBuildConfig,
+                    format!("the symbols '{}' and '{}' would both become the constant '{}'",
+                        other, symbol, constant),
+                ));
+            }
+
+            writeln!(code, "        pub const {}: u16 = {};", constant, address).unwrap();
+        }
+
+        writeln!(code, "    }}").unwrap();
+    }
+
+    writeln!(code, "}}").unwrap();
+
+    Ok(())
+}
+
+/// Returns the module name for a source file stem, such as 'boot' for 'Boot.asm'.
+///
+fn module_name(stem: &str) -> String {
+    let name = identifier(&stem.to_lowercase());
+
+    match KEYWORDS.contains(&name.as_str()) {
+        true => name + "_",
+        false => name,
+    }
+}
+
+/// Returns the constant name for a symbol, such as 'MAIN_LOOP' for 'Main.loop'.
+///
+fn constant_name(symbol: &str) -> String {
+    identifier(&symbol.to_uppercase())
+}
+
+/// Replaces the characters of a name that cannot appear in an identifier with '_', and adds a
+/// leading '_' if it would otherwise start with a digit or be empty.
+///
+fn identifier(name: &str) -> String {
+    let mut identifier: String = name.chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+
+    if identifier.chars().next().is_none_or(|c| c.is_ascii_digit()) {
+        identifier.insert(0, '_');
+    }
+
+    identifier
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn generate_module() {
+        let dir = tempfile::tempdir().unwrap();
+        let boot = dir.path().join("Boot.asm");
+        let loop_file = dir.path().join("loop.asm");
+
+        fs::write(&boot, "(START)\n@i\nM=1\n@Main.end\nD=A\n(Main.end)\n@START\n0;JMP\n").unwrap();
+        fs::write(&loop_file, "@WIDTH\n").unwrap();
+
+        let mut cargo = Vec::new();
+
+        let path = Builder::new()
+            .file(&boot)
+            .file(&loop_file)
+            .define("WIDTH", 512)
+            .module("programs")
+            .generate(dir.path(), &mut cargo)
+            .unwrap();
+
+        assert_eq!(path, dir.path().join("programs.rs"));
+        assert_eq!(
+            String::from_utf8(cargo).unwrap(),
+            format!("cargo:rerun-if-changed={}\ncargo:rerun-if-changed={}\n",
+                boot.display(), loop_file.display()),
+        );
+
+        let code = fs::read_to_string(&path).unwrap();
+
+        assert!(code.contains(
+            &format!("\n/// Assembled from '{}'.\npub mod boot {{\n", boot.display())));
+        assert!(code.contains("\
+            \x20   pub const ROM: [u16; 6] = [\n\
+            \x20       0x0010, 0xefc8, 0x0004, 0xec10, 0x0000, 0xea87,\n\
+            \x20   ];\n"));
+        assert!(code.contains("\
+            \x20   pub mod labels {\n\
+            \x20       pub const START: u16 = 0;\n\
+            \x20       pub const MAIN_END: u16 = 4;\n\
+            \x20   }\n"));
+        assert!(code.contains("\
+            \x20   pub mod variables {\n\
+            \x20       pub const I: u16 = 16;\n\
+            \x20   }\n"));
+        assert!(code.contains("pub mod loop_ {\n    /// The machine instructions of the program, \
+            in ROM order.\n    pub const ROM: [u16; 1] = [\n        0x0200,\n    ];\n"));
+    }
+
+    #[test]
+    fn assembly_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let infile = dir.path().join("Bad.asm");
+
+        fs::write(&infile, "D=D+Q\n").unwrap();
+
+        let err = Builder::new().file(&infile).generate(dir.path(), &mut Vec::new()).unwrap_err();
+
+        assert_eq!(err.kind(), Some(ErrorKind::InvalidComp));
+        assert_eq!(err.file(), Some(infile.as_path()));
+        assert!(!dir.path().join("hack_programs.rs").exists());
+    }
+
+    #[test]
+    fn clashing_names() {
+        let dir = tempfile::tempdir().unwrap();
+        let infile = dir.path().join("Clash.asm");
+
+        fs::write(&infile, "@a.b\n@A_B\n").unwrap();
+
+        let err = Builder::new()
+            .file(&infile)
+            .file(dir.path().join("sub").join("clash.asm"))
+            .generate(dir.path(), &mut Vec::new())
+            .unwrap_err();
+
+        assert!(err.message().ends_with("would both become the module 'clash'"));
+
+        let err = Builder::new().file(&infile).generate(dir.path(), &mut Vec::new()).unwrap_err();
+
+        assert_eq!(
+            err.message(),
+            "the symbols 'a.b' and 'A_B' would both become the constant 'A_B'",
+        );
+    }
+
+    #[test]
+    fn identifiers() {
+        assert_eq!(module_name("Max"), "max");
+        assert_eq!(module_name("my-prog.v2"), "my_prog_v2");
+        assert_eq!(module_name("self"), "self_");
+        assert_eq!(module_name("2048"), "_2048");
+        assert_eq!(constant_name("ponggame.0$if_end"), "PONGGAME_0_IF_END");
+    }
+}
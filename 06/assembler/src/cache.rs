@@ -0,0 +1,457 @@
+use std::fmt::Write;
+use std::fs;
+use std::path::Path;
+use crate::config::Config;
+use crate::error::{Error, ErrorKind, Result, Span};
+use crate::runner::{self, Assembly};
+use crate::symbols::SymbolTable;
+
+/// The file extension of cache entries.
+///
+pub const EXTENSION: &str = "hcache";
+
+/// The first line of every cache entry, naming the format and its version.
+///
+const HEADER: &str = "hackcache 1";
+
+/// The version of the assembler, which is part of every key so that an upgrade never reuses the
+/// results of an earlier version.
+///
+const VERSION: &str = env!("CARGO_PKG_VERSION");
+
+/// Returns the key under which the result of assembling the source with the given Config is
+/// cached.  It is a hash of the version of the assembler, the source text, and every option that
+/// can change the result: whether an object is written and the name of its module, the
+/// predefined symbols, the base address of variables, the memory map and the defines.
+///
+/// Options that only affect how the result is written, such as the output format and paths, are
+/// not part of the key.
+///
+pub fn key(config: &Config, source: &str) -> String {
+    let mut options = String::new();
+
+    writeln!(options, "version {}", VERSION).unwrap();
+    writeln!(options, "predefined {:?}", config.predefined_symbols).unwrap();
+    writeln!(options, "variable-base {}", config.variable_base).unwrap();
+
+    if let Some(ref map) = config.memory_map {
+        write!(options, "memory-map\n{}", map).unwrap();
+    }
+
+    if config.object {
+        writeln!(options, "object {}", module_name(&config.infile)).unwrap();
+    }
+
+    for (name, value) in config.defines.iter() {
+        writeln!(options, "define {}={}", name, value).unwrap();
+    }
+
+    // The length of the options keeps them apart from the source that follows.
+    let length = options.len().to_string();
+    let hash = fnv1a(&[length.as_bytes(), options.as_bytes(), source.as_bytes()]);
+
+    format!("{:016x}", hash)
+}
+
+/// Returns the result of assembling the source with the given Config from the cache in 'dir', or
+/// assembles it with 'assemble' and stores the result for next time.
+///
+/// The result is the Assembly along with the contents of the output file, or the error that
+/// stopped assembly.  Both are cached, so that an input known to be wrong is reported again
+/// without being assembled.
+///
+/// The cache is only an aid to speed, so an entry that cannot be read is assembled again, and a
+/// failure to store an entry is logged and otherwise ignored.
+///
+pub fn assemble<F>(dir: &Path, config: &Config, source: &str, assemble: F)
+    -> Result<(Assembly, Vec<u8>)>
+    where F: FnOnce() -> Result<(Assembly, Vec<u8>)>
+{
+    let key = key(config, source);
+    let path = dir.join(&key).with_extension(EXTENSION);
+
+    if let Ok(text) = fs::read_to_string(&path) {
+        if let Some(result) = load(&text, config) {
+            log::debug!("Using the cached result {:?} for {:?}.", path, config.infile);
+            return result;
+        }
+
+        log::debug!("Removing the unreadable cache entry {:?}.", path);
+
+        if let Err(e) = fs::remove_file(&path) {
+            log::debug!("The cache entry {:?} could not be removed: {}", path, e);
+        }
+    }
+
+    let result = assemble();
+
+    match store(&result, config) {
+        Some(text) => {
+            let written = fs::create_dir_all(dir)
+                .map_err(|e| Error::from(e).with_file(dir))
+                .and_then(|_| runner::write_output(&path, text.as_bytes()));
+
+            if let Err(e) = written {
+                log::debug!("The result for {:?} could not be cached: {}", config.infile, e);
+            }
+        },
+        None => log::debug!("The result for {:?} cannot be cached.", config.infile),
+    }
+
+    result
+}
+
+/// Writes a result as the text of a cache entry, or returns None if it cannot be cached, which is
+/// the case for errors that do not come from the assembler itself.
+///
+/// A successful result is written as a record for each word, with the source line it came from,
+/// followed by the labels and variables.  The output of an object is written in full after an
+/// 'object' line, whilst other outputs are made from the words again when the entry is read:
+///
+/// '''
+/// hackcache 1
+/// version 0.1.0
+/// word 0010 2
+/// word ea87 3
+/// label LOOP 0
+/// variable i 16
+/// end 6
+/// '''
+///
+/// An error is written as its code, span, help and message, each on one line.
+///
+/// Every entry ends with an 'end' line counting the lines before it, so that an entry cut short,
+/// by a crash part way through writing it, is never mistaken for a complete one.
+///
+fn store(result: &Result<(Assembly, Vec<u8>)>, config: &Config) -> Option<String> {
+    let mut text = String::new();
+
+    writeln!(text, "{}\nversion {}", HEADER, VERSION).unwrap();
+
+    match result {
+        Ok((assembly, output)) => {
+            for (word, line) in assembly.words.iter().zip(assembly.lines.iter()) {
+                writeln!(text, "word {:04x} {}", word, line).unwrap();
+            }
+
+            for (label, address) in assembly.symbols.labels() {
+                writeln!(text, "label {} {}", label, address).unwrap();
+            }
+
+            for (variable, address) in assembly.symbols.variables() {
+                writeln!(text, "variable {} {}", variable, address).unwrap();
+            }
+
+            if config.object {
+                text.push_str("object\n");
+                text.push_str(std::str::from_utf8(output).ok()?);
+            }
+        },
+        Err(error) => {
+            error.kind()?;
+
+            let message = error.message();
+            let help = error.help();
+
+            if message.contains('\n') || help.is_some_and(|help| help.contains('\n')) {
+                return None;
+            }
+
+            writeln!(text, "error {}", error.code()).unwrap();
+
+            if let Some(span) = error.span() {
+                writeln!(text, "span {} {} {}", span.line, span.column, span.length).unwrap();
+            }
+
+            if let Some(help) = help {
+                writeln!(text, "help {}", help).unwrap();
+            }
+
+            writeln!(text, "message {}", message).unwrap();
+        },
+    }
+
+    if !text.ends_with('\n') {
+        text.push('\n');
+    }
+
+    writeln!(text, "end {}", text.lines().count()).unwrap();
+
+    Some(text)
+}
+
+/// Reads a result from the text of a cache entry, as written by 'store'.  Returns None if the
+/// entry is malformed, incomplete or was written by another version of the assembler.
+///
+fn load(text: &str, config: &Config) -> Option<Result<(Assembly, Vec<u8>)>> {
+    let (body, end) = text.strip_suffix('\n')?.rsplit_once('\n')?;
+
+    if end != format!("end {}", body.lines().count()) {
+        return None;
+    }
+
+    let mut lines = body.lines();
+
+    if lines.next() != Some(HEADER) || lines.next() != Some(&format!("version {}", VERSION)) {
+        return None;
+    }
+
+    let mut words = Vec::new();
+    let mut source_lines = Vec::new();
+    let mut labels = Vec::new();
+    let mut variables = Vec::new();
+    let mut error: Option<Error> = None;
+    let mut span = None;
+    let mut help = None;
+
+    while let Some(line) = lines.next() {
+        let (record, rest) = line.split_once(' ').unwrap_or((line, ""));
+        let fields: Vec<&str> = rest.split(' ').collect();
+
+        match (record, fields.as_slice()) {
+            ("word", [word, line]) if word.len() == 4 => {
+                words.push(u16::from_str_radix(word, 16).ok()?);
+                source_lines.push(line.parse().ok()?);
+            },
+            ("label", [label, address]) => labels.push((*label, address.parse::<u16>().ok()?)),
+            ("variable", [variable, address]) => {
+                variables.push((*variable, address.parse::<u16>().ok()?));
+            },
+            ("object", [""]) => {
+                let object: Vec<&str> = lines.by_ref().collect();
+                let symbols = symbols(config, &words, &labels, &variables)?;
+                let assembly = Assembly { words, lines: source_lines, symbols };
+
+                return Some(Ok((assembly, format!("{}\n", object.join("\n")).into_bytes())));
+            },
+            ("error", [code]) => {
+                error = Some(Error::new(ErrorKind::from_code(code)?));
+            },
+            ("span", [line, column, length]) => {
+                span = Some(Span {
+                    line: line.parse().ok()?,
+                    column: column.parse().ok()?,
+                    length: length.parse().ok()?,
+                });
+            },
+            ("help", _) => help = Some(String::from(rest)),
+            ("message", _) => {
+                let kind = error?.kind()?;
+                let error = Error::with_message(kind, String::from(rest)).with_span(span);
+
+                return Some(Err(match help {
+                    Some(help) => error.with_help(help),
+                    None => error,
+                }));
+            },
+            _ => return None,
+        }
+    }
+
+    if config.object || error.is_some() {
+        return None;
+    }
+
+    let symbols = symbols(config, &words, &labels, &variables)?;
+    let output = config.format.format(&words);
+
+    Some(Ok((Assembly { words, lines: source_lines, symbols }, output)))
+}
+
+/// Builds the symbol table of a cached assembly again, from the labels and variables recorded,
+/// on top of the predefined symbols and constants of the Config.
+///
+fn symbols(config: &Config, words: &[u16], labels: &[(&str, u16)], variables: &[(&str, u16)])
+    -> Option<SymbolTable>
+{
+    let mut symbols = runner::symbol_table(config).ok()?;
+
+    for (label, address) in labels.iter() {
+        symbols.insert_label_at(label, *address).ok()?;
+    }
+
+    for (variable, address) in variables.iter() {
+        if symbols.insert_variable(variable).ok()? != *address {
+            return None;
+        }
+        symbols.inc_ram_address().ok()?;
+    }
+
+    for _ in words.iter() {
+        symbols.inc_rom_address().ok()?;
+    }
+
+    Some(symbols)
+}
+
+/// Returns the name of the module assembled from an input file, which is its file stem.
+///
+pub(crate) fn module_name(infile: &Path) -> String {
+    infile.file_stem().unwrap_or_default().to_string_lossy().into_owned()
+}
+
+/// Computes the 64-bit FNV-1a hash of a sequence of byte strings.
+///
+fn fnv1a(parts: &[&[u8]]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+
+    for byte in parts.iter().flat_map(|part| part.iter()) {
+        hash ^= u64::from(*byte);
+        hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+    }
+
+    hash
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::memory::MemoryMap;
+    use crate::output::OutputFormat;
+
+    fn config(dir: &Path, name: &str, source: &str) -> Config {
+        let infile = dir.join(name);
+
+        fs::write(&infile, source).unwrap();
+
+        Config::for_input(infile)
+    }
+
+    #[test]
+    fn cache_keys() {
+        let config = Config::for_input("Prog.asm");
+        let key = key(&config, "@i\n");
+
+        assert_eq!(key.len(), 16);
+        assert_eq!(key, super::key(&Config::for_input("other/Prog.asm"), "@i\n"));
+        assert_eq!(key, super::key(&Config { format: OutputFormat::Hex, ..config.clone() }, "@i\n"));
+        assert_ne!(key, super::key(&config, "@j\n"));
+
+        let changed = [
+            Config { object: true, ..config.clone() },
+            Config { variable_base: 32, ..config.clone() },
+            Config { memory_map: Some(MemoryMap::default()), ..config.clone() },
+            Config { defines: vec![(String::from("WIDTH"), 32)], ..config.clone() },
+        ];
+
+        for changed in changed.iter() {
+            assert_ne!(super::key(changed, "@i\n"), key);
+        }
+
+        assert_ne!(fnv1a(&[b"ab", b"c"]), fnv1a(&[b"ab", b"d"]));
+        assert_eq!(fnv1a(&[b"a"]), 0xaf63_dc4c_8601_ec8c);
+    }
+
+    #[test]
+    fn reuse_cached_results() {
+        let dir = tempfile::tempdir().unwrap();
+        let cache = dir.path().join("cache");
+        let source = "(LOOP)\n@i\nM=M+1\n@LOOP\n0;JMP\n";
+        let config = config(dir.path(), "Prog.asm", source);
+        let fresh = || runner::assemble(source, runner::symbol_table(&config).unwrap())
+            .map(|assembly| {
+                let output = config.format.format(&assembly.words);
+                (assembly, output)
+            });
+
+        let first = assemble(&cache, &config, source, fresh).unwrap();
+        let second = assemble(&cache, &config, source, || panic!("assembled again")).unwrap();
+
+        assert_eq!(second, first);
+        assert_eq!(fs::read_dir(&cache).unwrap().count(), 1);
+
+        let entry = fs::read_to_string(cache.join(key(&config, source)).with_extension(EXTENSION))
+            .unwrap();
+
+        assert!(entry.starts_with("hackcache 1\nversion "));
+        assert!(entry.ends_with("word ea87 5\nlabel LOOP 0\nvariable i 16\nend 8\n"));
+    }
+
+    #[test]
+    fn rebuild_truncated_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = "@i\nM=1\n@j\nM=0\n";
+        let config = config(dir.path(), "Prog.asm", source);
+        let path = dir.path().join(key(&config, source)).with_extension(EXTENSION);
+        let fresh = || runner::assemble(source, runner::symbol_table(&config).unwrap())
+            .map(|assembly| {
+                let output = config.format.format(&assembly.words);
+                (assembly, output)
+            });
+
+        let first = assemble(dir.path(), &config, source, fresh).unwrap();
+        let entry = fs::read_to_string(&path).unwrap();
+
+        // Cut the entry short after its first word, as a crash part way through writing it would.
+        let cut = entry.match_indices("\nword ").nth(1).unwrap().0 + 1;
+        fs::write(&path, &entry[..cut]).unwrap();
+
+        let mut rebuilt = false;
+        let second = assemble(dir.path(), &config, source, || {
+            rebuilt = true;
+            fresh()
+        }).unwrap();
+
+        assert!(rebuilt);
+        assert_eq!(second, first);
+        assert_eq!(fs::read_to_string(&path).unwrap(), entry);
+    }
+
+    #[test]
+    fn reuse_cached_objects_and_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = ".global LOOP\n(LOOP)\n@i\n";
+        let config = Config { object: true, ..config(dir.path(), "Main.asm", source) };
+        let fresh = || crate::object::assemble(source, "Main", runner::symbol_table(&config)?)
+            .map(|(object, assembly)| (assembly, object.to_string().into_bytes()));
+
+        let first = assemble(dir.path(), &config, source, fresh).unwrap();
+        let second = assemble(dir.path(), &config, source, || panic!("assembled again")).unwrap();
+
+        assert_eq!(second, first);
+
+        let config = Config { object: false, ..config };
+        let failed = || runner::assemble("D=D+Q\n", runner::symbol_table(&config)?)
+            .map(|assembly| (assembly, Vec::new()));
+
+        let first = assemble(dir.path(), &config, "D=D+Q\n", failed).unwrap_err();
+        let second = assemble(dir.path(), &config, "D=D+Q\n", || panic!("assembled again"))
+            .unwrap_err();
+
+        assert_eq!(second.kind(), first.kind());
+        assert_eq!(second.message(), first.message());
+        assert_eq!(second.help(), first.help());
+        assert_eq!(second.span(), first.span());
+    }
+
+    #[test]
+    fn ignore_invalid_entries() {
+        let config = Config::for_input("Prog.asm");
+        let header = format!("{}\nversion {}\n", HEADER, VERSION);
+
+        let cases = [
+            String::new(),
+            String::from("hackcache 1\nversion 0.0.0\nword 0000 1\nend 3\n"),
+            format!("{}word 00000 1\nend 3\n", header),
+            format!("{}variable i\nend 3\n", header),
+            format!("{}variable i 17\nend 3\n", header),
+            format!("{}error E9999\nmessage unknown\nend 4\n", header),
+            format!("{}unknown record\nend 3\n", header),
+            format!("{}word 0010 1\n", header),
+            format!("{}word 0010 1\nend 2\n", header),
+            format!("{}word 0010 1\nend 3", header),
+        ];
+
+        for text in cases.iter() {
+            assert!(load(text, &config).is_none(), "{:?}", text);
+        }
+
+        let assembly = load(&format!("{}word 0010 1\nvariable i 16\nend 4\n", header), &config)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(assembly.0.words, vec![16]);
+        assert_eq!(assembly.0.symbols.get_address("i"), Some(16));
+        assert_eq!(assembly.1, b"0000000000010000\n");
+    }
+}
@@ -0,0 +1,844 @@
+use std::env;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::io::{self, IsTerminal};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::archive::{self, Archive};
+use crate::batch;
+use crate::config::{self, Config, Invocation};
+use crate::diagnostics::{self, Diagnostic, Format, Severity};
+use crate::disasm;
+use crate::emulator::{Cpu, Status, RAM_SIZE};
+use crate::error::{Error, ErrorKind, Result};
+use crate::formatter;
+use crate::linker;
+use crate::lint;
+use crate::memory::MemoryMap;
+use crate::object::{self, Object};
+use crate::output::OutputFormat;
+use crate::runner::{self, Assembly};
+use crate::stats::Stats;
+use crate::symbols::SymbolTable;
+use crate::watch;
+
+/// The usage message printed in response to 'hack --help'.
+///
+pub const HACK_USAGE: &str = "\
+The Hack toolchain: assembler, linker, disassembler, emulator, formatter and linter.
+
+Usage: hack <COMMAND> [ARGS]
+
+Commands:
+  asm      Assemble programs, accepting the same options as 'assembler'
+  link     Link object files written by 'hack asm -c' into a program
+  archive  Package object files into a static library for 'hack link'
+  disasm   Translate machine code back into assembly
+  run      Run a program on an emulated Hack CPU
+  fmt      Format assembly source files
+  lint     Check programs for likely mistakes
+  info     Show the symbols and statistics of programs
+
+Run 'hack <COMMAND> --help' for the options of each command.
+";
+
+const LINK_USAGE: &str = "\
+Links relocatable object files, written by 'hack asm -c', into a single program.
+
+Usage: hack link [OPTIONS] <INPUT.hobj|LIBRARY.hlib>...
+
+The code of each object is placed in ROM in the order given.  Symbols are private to the source
+file they are written in, as 'File.name', unless declared with '.global NAME'.  A global label can
+be used by every object that also declares it global, and a symbol that no object defines as a
+label becomes a variable.
+
+Members of the static libraries, written by 'hack archive', are only linked when they define a
+label that the program refers to but does not otherwise define.  They follow the objects in ROM.
+
+With '--strip', code that cannot be reached from the start of the first object is left out.  Code
+is reachable if execution can fall through into it, or if a label within it is referred to from
+reachable code.  Each region removed is reported, along with the number of words saved.
+
+Options:
+  -o, --output <PATH>     Write the program to PATH [default: the first INPUT with the extension
+                          of the output format]
+      --format <FORMAT>   Output format: hack, bin or hex [default: hack]
+      --map <PATH>        Also write a link map of the modules, labels and variables
+      --strip             Leave out code that can never be run
+      --memory-map <PATH> Read the predefined symbols and memory layout from PATH
+  -q, --quiet             Do not report a summary on success
+";
+
+const ARCHIVE_USAGE: &str = "\
+Packages object files, written by 'hack asm -c', into a static library for 'hack link'.
+
+Usage: hack archive -o <OUTPUT.hlib> <INPUT.hobj>...
+
+Each object becomes a member of the library, named after its file.
+
+Options:
+  -o, --output <PATH>   Write the library to PATH
+";
+
+const DISASM_USAGE: &str = "\
+Translates Hack machine code back into assembly.
+
+Usage: hack disasm [OPTIONS] <INPUT.hack|INPUT.bin|INPUT.hex>
+
+Options:
+  -o, --output <PATH>   Write the assembly to PATH rather than stdout
+      --addresses       Start each line with the ROM address of its instruction
+      --bits            Show the bits of each instruction before it
+
+Words that no mnemonic describes are written in a form that assembles back to the same word,
+such as an 'alu(...)' comp for undocumented comp bits, and flagged with a comment.
+";
+
+const RUN_USAGE: &str = "\
+Runs a program on an emulated Hack CPU until it loops forever, runs past its last instruction
+or reaches the step limit, then prints the registers and RAM.
+
+Usage: hack run [OPTIONS] <INPUT.asm|INPUT.hack|INPUT.bin|INPUT.hex>
+
+Options:
+      --steps <N>            Stop after N instructions [default: 1000000]
+      --set <ADDR=VALUE>     Set a RAM word before running
+      --ram <ADDR[..END]>    Print RAM from ADDR up to, but not including, END [default: 0..16]
+
+An assembly source is assembled first, and accepts the options of 'hack asm'.
+";
+
+const FMT_USAGE: &str = "\
+Formats Hack assembly source files in place.
+
+Usage: hack fmt [OPTIONS] <INPUT.asm|DIR>...
+
+Options:
+      --check   Report files that are not formatted, rather than changing them
+";
+
+const LINT_USAGE: &str = "\
+Checks programs for likely mistakes that still assemble.
+
+Usage: hack lint [OPTIONS] <INPUT.asm|DIR>...
+
+Lints:
+  unused-label          A label that is never referred to
+  unreachable-code      An instruction straight after an unconditional jump
+  single-use-variable   A variable that is only referred to once
+
+Lint levels are set with '--allow', '--warn' or '--deny', or in the [lints] table of
+'hackasm.toml'.  The other options of 'hack asm' are also accepted.
+";
+
+const INFO_USAGE: &str = "\
+Shows the ROM/RAM usage, instruction mix, labels and variables of programs.
+
+Usage: hack info [OPTIONS] <INPUT.asm|DIR>...
+
+The options of 'hack asm' are also accepted.
+";
+
+/// Runs the assembler command line, as either the 'assembler' binary or 'hack asm', and returns
+/// the exit status.  'program' is the name the command was run as, used in messages.
+///
+pub fn assemble<T>(args: T, program: &str) -> i32
+where
+    T: IntoIterator,
+    T::Item: Into<OsString>,
+{
+    let batch = match Config::parse(args) {
+        Ok(Invocation::Assemble(batch)) => batch,
+        Ok(Invocation::Help) => {
+            print!("{}", config::USAGE.replace("assembler [", &format!("{} [", program)));
+            return 0;
+        },
+        Ok(Invocation::Version) => {
+            println!("{} {}", program, env!("CARGO_PKG_VERSION"));
+            return 0;
+        },
+        Err(e) => return report_parse_error(&e),
+    };
+
+    log::debug!("Config generated from arguments\n{:#?}", batch);
+
+    let options = Options::new(&batch.configs);
+
+    if batch.configs[0].watch {
+        return watch(&batch.configs, &options);
+    }
+
+    let outcomes = batch.run();
+
+    let mut diagnostics = Vec::new();
+
+    for outcome in outcomes.iter() {
+        match outcome.result {
+            Ok(ref assembly) => {
+                report_success(&outcome.infile, &outcome.outfile, assembly, &options);
+            },
+            Err(ref e) => diagnostics.push(Diagnostic::from(e)),
+        }
+    }
+
+    emit_diagnostics(&diagnostics, options.format);
+
+    if options.multiple && !options.quiet {
+        eprintln!("{} {} of {} files, {} failed",
+            if options.check { "Checked" } else { "Assembled" },
+            outcomes.len() - diagnostics.len(), outcomes.len(), diagnostics.len());
+    }
+
+    match diagnostics.is_empty() {
+        true => 0,
+        false => 1,
+    }
+}
+
+/// Runs the 'hack' command line, dispatching to the requested subcommand, and returns the exit
+/// status.
+///
+pub fn hack<T>(args: T) -> i32
+where
+    T: IntoIterator,
+    T::Item: Into<OsString>,
+{
+    let mut args: Vec<OsString> = args.into_iter().map(Into::into).collect();
+
+    if args.len() < 2 {
+        eprint!("{}", HACK_USAGE);
+        return 1;
+    }
+
+    let command = args.remove(1);
+
+    // The remaining arguments are passed on with the command in place of the executable path.
+    args[0] = OsString::from(format!("hack {}", command.to_string_lossy()));
+
+    match command.to_str().unwrap_or("") {
+        "asm" => assemble(args, "hack asm"),
+        "link" => run_command(args, LINK_USAGE, link),
+        "archive" => run_command(args, ARCHIVE_USAGE, archive),
+        "disasm" => run_command(args, DISASM_USAGE, disassemble),
+        "run" => run_command(args, RUN_USAGE, emulate),
+        "fmt" => run_command(args, FMT_USAGE, format),
+        "lint" => run_command(args, LINT_USAGE, lint),
+        "info" => run_command(args, INFO_USAGE, info),
+        "-h" | "--help" | "help" => {
+            print!("{}", HACK_USAGE);
+            0
+        },
+        "-V" | "--version" => {
+            println!("hack {}", env!("CARGO_PKG_VERSION"));
+            0
+        },
+        _ => report_parse_error(&Error::with_message(
+            ErrorKind::InvalidOption,
+            format!("unrecognised command '{}'", command.to_string_lossy()),
+        )),
+    }
+}
+
+/// Runs a subcommand, printing its usage if help is requested.  Errors in the arguments are
+/// reported as such, and errors from the command itself as diagnostics.
+///
+fn run_command<F>(args: Vec<OsString>, usage: &str, command: F) -> i32
+    where F: FnOnce(Vec<OsString>) -> Result<i32>
+{
+    if args.iter().skip(1).any(|arg| arg == "-h" || arg == "--help") {
+        print!("{}", usage);
+        return 0;
+    }
+
+    match command(args) {
+        Ok(status) => status,
+        Err(ref e) if e.file().is_none() && e.code().starts_with("E01") => report_parse_error(e),
+        Err(ref e) => {
+            emit_diagnostics(&[Diagnostic::from(e)], Format::Human);
+            1
+        },
+    }
+}
+
+/// 'hack link': links object files into a program, and optionally writes a link map.
+///
+fn link(mut args: Vec<OsString>) -> Result<i32> {
+    let outfile = take_option(&mut args, &["-o", "--output"])?.pop().map(PathBuf::from);
+    let format = match take_option(&mut args, &["--format"])?.pop() {
+        Some(format) => format.parse::<OutputFormat>()?,
+        None => OutputFormat::Hack,
+    };
+    let map = take_option(&mut args, &["--map"])?.pop().map(PathBuf::from);
+    let strip = take_flag(&mut args, "--strip");
+    let memory_map = match take_option(&mut args, &["--memory-map"])?.pop() {
+        Some(path) => MemoryMap::load(Path::new(&path))?,
+        None => MemoryMap::default(),
+    };
+    let quiet = take_flag(&mut args, "-q") | take_flag(&mut args, "--quiet");
+    let infiles = inputs(args)?;
+
+    let mut modules = Vec::with_capacity(infiles.len());
+    let mut archives = Vec::new();
+
+    for infile in infiles.iter() {
+        let name = infile.display().to_string();
+
+        match infile.extension().and_then(OsStr::to_str) {
+            Some(archive::EXTENSION) => archives.push((name, read_text::<Archive>(infile)?)),
+            Some(object::EXTENSION) => modules.push((name, read_text::<Object>(infile)?)),
+            _ => {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidInFileExt,
+                    format!("invalid input file extension, only '.{}' or '.{}' accepted",
+                        object::EXTENSION, archive::EXTENSION),
+                ));
+            },
+        }
+    }
+
+    if modules.is_empty() {
+        return Err(Error::with_message(
+            ErrorKind::MissingArguments,
+            String::from("at least one object file must be given to link"),
+        ));
+    }
+
+    let outfile = outfile.unwrap_or_else(|| PathBuf::from(&modules[0].0)
+        .with_extension(format.extension()));
+
+    linker::add_members(&mut modules, &archives);
+
+    if outfile.extension() != Some(OsStr::new(format.extension())) {
+        return Err(Error::with_message(
+            ErrorKind::InvalidOutFileExt,
+            format!("invalid output file extension, only '.{}' accepted", format.extension()),
+        ));
+    }
+
+    if strip {
+        let (remaining, stripped) = linker::strip(&modules);
+
+        if !quiet {
+            for region in stripped.iter() {
+                eprintln!("Stripped {}", region);
+            }
+
+            eprintln!("Saved {} words",
+                stripped.iter().map(|region| usize::from(region.size)).sum::<usize>());
+        }
+
+        modules = remaining;
+    }
+
+    let image = linker::link(&modules, SymbolTable::with_memory_map(&memory_map)?)?;
+
+    runner::write_output(&outfile, &format.format(&image.words))
+        .map_err(|e| e.with_file(&outfile))?;
+
+    if let Some(map) = map {
+        runner::write_output(&map, image.map().as_bytes()).map_err(|e| e.with_file(&map))?;
+    }
+
+    if !quiet {
+        eprintln!("Linked {} objects into {} ({} words)",
+            modules.len(), outfile.display(), image.words.len());
+    }
+
+    Ok(0)
+}
+
+/// 'hack archive': packages object files into a static library.
+///
+fn archive(mut args: Vec<OsString>) -> Result<i32> {
+    let outfile = match take_option(&mut args, &["-o", "--output"])?.pop() {
+        Some(outfile) => PathBuf::from(outfile),
+        None => {
+            return Err(Error::with_message(
+                ErrorKind::MissingArguments,
+                String::from("the path of the library must be given with '-o'"),
+            ));
+        },
+    };
+
+    if outfile.extension() != Some(OsStr::new(archive::EXTENSION)) {
+        return Err(Error::with_message(
+            ErrorKind::InvalidOutFileExt,
+            format!("invalid output file extension, only '.{}' accepted", archive::EXTENSION),
+        ));
+    }
+
+    let mut members = Vec::new();
+
+    for infile in inputs(args)? {
+        let name = infile.file_name().unwrap_or_default().to_string_lossy().into_owned();
+
+        members.push((name, read_object(&infile)?));
+    }
+
+    let library = Archive::new(members).map_err(|e| e.with_file(&outfile))?;
+
+    runner::write_output(&outfile, library.to_string().as_bytes())
+        .map_err(|e| e.with_file(&outfile))?;
+
+    Ok(0)
+}
+
+/// 'hack disasm': writes the assembly for a file of machine code.
+///
+fn disassemble(mut args: Vec<OsString>) -> Result<i32> {
+    let output = take_option(&mut args, &["-o", "--output"])?.pop().map(PathBuf::from);
+    let address = take_flag(&mut args, "--addresses");
+    let bits = take_flag(&mut args, "--bits");
+    let infile = single_input(args)?;
+    let words = read_machine_code(&infile)?;
+
+    let text = disasm::listing(&words, disasm::Columns { address, bits });
+
+    match output {
+        Some(outfile) => {
+            runner::write_output(&outfile, text.as_bytes()).map_err(|e| e.with_file(&outfile))?;
+        },
+        None => print!("{}", text),
+    }
+
+    Ok(0)
+}
+
+/// 'hack run': runs a program on the emulator and prints the final state.
+///
+fn emulate(mut args: Vec<OsString>) -> Result<i32> {
+    let steps = match take_option(&mut args, &["--steps"])?.pop() {
+        Some(steps) => steps.parse::<u64>().map_err(|_| invalid_value("--steps", &steps))?,
+        None => 1_000_000,
+    };
+
+    let mut sets = Vec::new();
+
+    for set in take_option(&mut args, &["--set"])? {
+        let (address, value) = set.split_once('=').ok_or_else(|| invalid_value("--set", &set))?;
+        let address = parse_address(address).ok_or_else(|| invalid_value("--set", &set))?;
+        let value = match value.parse::<i32>() {
+            Ok(value) if (-32768..=65535).contains(&value) => value as u16,
+            _ => return Err(invalid_value("--set", &set)),
+        };
+
+        sets.push((address, value));
+    }
+
+    let mut ranges = Vec::new();
+
+    for range in take_option(&mut args, &["--ram"])? {
+        let (start, end) = match range.split_once("..") {
+            Some((start, end)) => (parse_address(start), end.parse::<usize>().ok()),
+            None => (parse_address(&range), parse_address(&range).map(|a| a + 1)),
+        };
+
+        match (start, end) {
+            (Some(start), Some(end)) if start < end && end <= RAM_SIZE => ranges.push(start..end),
+            _ => return Err(invalid_value("--ram", &range)),
+        }
+    }
+
+    if ranges.is_empty() {
+        ranges.push(0..16);
+    }
+
+    let words = match args.get(1).map(Path::new).and_then(OutputFormat::from_path) {
+        Some(_) => read_machine_code(&single_input(args)?)?,
+        None => {
+            args.push(OsString::from("--check"));
+
+            runner::run(Config::new(args)?)?.words
+        },
+    };
+
+    let mut cpu = Cpu::new(words);
+
+    for (address, value) in sets {
+        cpu.ram[address] = value;
+    }
+
+    let (steps, status) = cpu.run(steps);
+
+    match status {
+        Status::Looping => println!("Reached an endless loop after {} steps", steps),
+        Status::Finished => println!("Ran past the last instruction after {} steps", steps),
+        Status::Running => println!("Stopped at the step limit of {} steps", steps),
+    }
+
+    println!("A = {}  D = {}  PC = {}", cpu.a as i16, cpu.d as i16, cpu.pc);
+
+    for range in ranges {
+        for address in range {
+            println!("RAM[{}] = {}", address, cpu.ram[address] as i16);
+        }
+    }
+
+    Ok(0)
+}
+
+/// 'hack fmt': formats source files in place, or reports those that need formatting.
+///
+fn format(mut args: Vec<OsString>) -> Result<i32> {
+    let check = take_flag(&mut args, "--check");
+    let infiles = inputs(args)?;
+    let mut unformatted = 0;
+
+    // Only source files are rewritten, never machine code given by mistake.
+    if let Some(infile) = infiles.iter().find(|path| path.extension() != Some(OsStr::new("asm"))) {
+        return Err(Error::new(ErrorKind::InvalidInFileExt).with_file(infile));
+    }
+
+    for infile in infiles {
+        let source = fs::read_to_string(&infile).map_err(|e| Error::from(e).with_file(&infile))?;
+        let formatted = formatter::format_source(&source);
+
+        if formatted == source {
+            continue;
+        }
+
+        unformatted += 1;
+
+        if check {
+            println!("{} is not formatted", infile.display());
+        } else {
+            runner::write_output(&infile, formatted.as_bytes()).map_err(|e| e.with_file(&infile))?;
+            eprintln!("Formatted {}", infile.display());
+        }
+    }
+
+    match check && unformatted > 0 {
+        true => Ok(1),
+        false => Ok(0),
+    }
+}
+
+/// 'hack lint': reports the problems found by the lints in each program.
+///
+fn lint(mut args: Vec<OsString>) -> Result<i32> {
+    args.push(OsString::from("--check"));
+
+    let configs = parse_configs(args)?;
+    let options = Options::new(&configs);
+    let mut diagnostics = Vec::new();
+
+    for config in configs.iter() {
+        match lint::lint(config) {
+            Ok(found) => diagnostics.extend(found),
+            Err(ref e) => diagnostics.push(Diagnostic::from(e)),
+        }
+    }
+
+    emit_diagnostics(&diagnostics, options.format);
+
+    let errors = diagnostics.iter().filter(|d| d.severity == Severity::Error).count();
+
+    if !options.quiet {
+        eprintln!("Linted {} files: {} errors, {} warnings",
+            configs.len(), errors, diagnostics.len() - errors);
+    }
+
+    match errors {
+        0 => Ok(0),
+        _ => Ok(1),
+    }
+}
+
+/// 'hack info': prints the statistics, labels and variables of each program.
+///
+fn info(mut args: Vec<OsString>) -> Result<i32> {
+    args.push(OsString::from("--check"));
+
+    let configs = parse_configs(args)?;
+    let options = Options::new(&configs);
+    let mut diagnostics = Vec::new();
+
+    for config in configs {
+        let infile = config.infile.clone();
+
+        let assembly = match runner::run(config) {
+            Ok(assembly) => assembly,
+            Err(ref e) => {
+                diagnostics.push(Diagnostic::from(e));
+                continue;
+            },
+        };
+
+        if options.multiple {
+            println!("{}:", infile.display());
+        }
+
+        print!("{}", Stats::new(&assembly));
+
+        let variables = assembly.symbols.variables();
+
+        if !variables.is_empty() {
+            println!("\nVariables:");
+
+            let width = variables.iter().map(|(name, _)| name.len()).max().unwrap_or(0);
+
+            for (name, address) in variables {
+                println!("  {:<width$}  {:>5}", name, address, width = width);
+            }
+        }
+    }
+
+    emit_diagnostics(&diagnostics, options.format);
+
+    match diagnostics.is_empty() {
+        true => Ok(0),
+        false => Ok(1),
+    }
+}
+
+/// The options that control how the results of assembling are reported.  Options other than
+/// paths are shared by every Config of a batch, so they are taken from the first.
+///
+struct Options {
+    check: bool,
+    format: Format,
+    stats: bool,
+    quiet: bool,
+    multiple: bool,
+}
+
+impl Options {
+    fn new(configs: &[Config]) -> Options {
+        Options {
+            check: configs[0].check,
+            format: configs[0].diagnostics_format,
+            stats: configs[0].stats,
+            quiet: configs[0].quiet,
+            multiple: configs.len() > 1,
+        }
+    }
+}
+
+/// Assembles the configured files every time they change, until interrupted or the files can no
+/// longer be watched.
+///
+fn watch(configs: &[Config], options: &Options) -> i32 {
+    if !options.quiet {
+        eprintln!("Watching {} file(s) for changes, press Ctrl-C to stop", configs.len());
+    }
+
+    let watched = watch::watch(configs, |config, result| {
+        match result {
+            Ok(ref assembly) => report_success(&config.infile, &config.outfile, assembly, options),
+            Err(ref e) => emit_diagnostics(&[Diagnostic::from(e)], options.format),
+        }
+        ControlFlow::Continue(())
+    });
+
+    match watched {
+        Ok(()) => 0,
+        Err(ref e) => {
+            emit_diagnostics(&[Diagnostic::from(e)], options.format);
+            1
+        },
+    }
+}
+
+fn report_success(infile: &Path, outfile: &Path, assembly: &Assembly, options: &Options) {
+    if options.stats {
+        let mut report = String::new();
+
+        if options.multiple {
+            report.push_str(&format!("{}:\n", infile.display()));
+        }
+        report.push_str(&Stats::new(assembly).to_string());
+
+        // Structured diagnostics are written to stdout, which must hold nothing else.
+        match options.format {
+            Format::Human => print!("{}", report),
+            _ => eprint!("{}", report),
+        }
+    }
+
+    if options.quiet {
+        return;
+    }
+
+    if options.check {
+        eprintln!("Checked {} ({} words)", infile.display(), assembly.words.len());
+    } else {
+        eprintln!("Assembled {} into {} ({} words)",
+            infile.display(), outfile.display(), assembly.words.len());
+    }
+}
+
+/// Reports errors, as human-readable text on stderr or in a structured format on stdout.
+///
+pub fn emit_diagnostics(diagnostics: &[Diagnostic], format: Format) {
+    let emitted = match format {
+        Format::Human => {
+            let color = io::stderr().is_terminal() && env::var_os("NO_COLOR").is_none();
+
+            diagnostics::emit_human(&mut io::stderr(), diagnostics, color)
+        },
+        _ => diagnostics::emit(&mut io::stdout(), format, diagnostics),
+    };
+
+    if let Err(e) = emitted {
+        eprintln!("Problem writing diagnostics: {}", e);
+    }
+}
+
+/// Reports an error in the command line arguments and returns the exit status.  Errors in a
+/// file, such as the project file, are reported along with their location.
+///
+fn report_parse_error(err: &Error) -> i32 {
+    if err.file().is_some() {
+        emit_diagnostics(&[Diagnostic::from(err)], Format::Human);
+    } else {
+        eprintln!("Problem parsing arguments: {}", err);
+        eprintln!("For more information, try '--help'.");
+    }
+    1
+}
+
+/// Parses the arguments of a subcommand that shares the options of the assembler.
+///
+fn parse_configs(args: Vec<OsString>) -> Result<Vec<Config>> {
+    match Config::parse(args)? {
+        Invocation::Assemble(batch) => Ok(batch.configs),
+        _ => Err(Error::new(ErrorKind::UnexpectedArgument)),
+    }
+}
+
+/// Removes every occurrence of the named option, as '--name value' or '--name=value', from the
+/// arguments and returns their values in order.
+///
+fn take_option(args: &mut Vec<OsString>, names: &[&str]) -> Result<Vec<String>> {
+    let mut values = Vec::new();
+    let mut i = 1;
+
+    while i < args.len() {
+        let arg = args[i].to_string_lossy().into_owned();
+        let (name, value) = match arg.split_once('=') {
+            Some((name, value)) if name.starts_with("--") => (name, Some(value)),
+            _ => (arg.as_str(), None),
+        };
+
+        if !names.contains(&name) {
+            i += 1;
+            continue;
+        }
+
+        args.remove(i);
+
+        let value = match value {
+            Some(value) => String::from(value),
+            None if i < args.len() => args.remove(i).to_string_lossy().into_owned(),
+            None => {
+                return Err(Error::with_message(
+                    ErrorKind::MissingOptionValue,
+                    format!("a value was not provided for option '{}'", name),
+                ));
+            },
+        };
+
+        values.push(value);
+    }
+
+    Ok(values)
+}
+
+/// Removes every occurrence of a flag from the arguments, returning whether it was present.
+///
+fn take_flag(args: &mut Vec<OsString>, name: &str) -> bool {
+    let count = args.len();
+
+    args.retain(|arg| arg != name);
+
+    args.len() != count
+}
+
+/// Returns the single input path of a subcommand, rejecting any unexpected options.
+///
+fn single_input(args: Vec<OsString>) -> Result<PathBuf> {
+    let mut inputs = inputs(args)?;
+
+    match inputs.len() {
+        1 => Ok(inputs.remove(0)),
+        _ => Err(Error::new(ErrorKind::UnexpectedArgument)),
+    }
+}
+
+/// Returns the input paths of a subcommand, searching any directories for '.asm' files.
+///
+fn inputs(args: Vec<OsString>) -> Result<Vec<PathBuf>> {
+    let mut inputs = Vec::new();
+
+    for arg in args.into_iter().skip(1) {
+        if arg.to_string_lossy().starts_with('-') && arg != "-" {
+            return Err(Error::with_message(
+                ErrorKind::InvalidOption,
+                format!("unrecognised command line option '{}'", arg.to_string_lossy()),
+            ));
+        }
+
+        let path = PathBuf::from(arg);
+
+        match path.is_dir() {
+            true => inputs.extend(batch::find_sources(&path)?),
+            false => inputs.push(path),
+        }
+    }
+
+    if inputs.is_empty() {
+        return Err(Error::new(ErrorKind::MissingArguments));
+    }
+
+    Ok(inputs)
+}
+
+/// Reads an object file, which must have the object file extension.
+///
+fn read_object(path: &Path) -> Result<Object> {
+    if path.extension() != Some(OsStr::new(object::EXTENSION)) {
+        return Err(Error::with_message(
+            ErrorKind::InvalidInFileExt,
+            format!("invalid input file extension, only '.{}' accepted", object::EXTENSION),
+        ));
+    }
+
+    read_text(path)
+}
+
+/// Reads a file of text and parses it, attaching the path to any error.
+///
+fn read_text<T>(path: &Path) -> Result<T>
+    where T: FromStr<Err = Error>
+{
+    let text = fs::read_to_string(path).map_err(|e| Error::from(e).with_file(path))?;
+
+    text.parse::<T>().map_err(|e| e.with_file(path))
+}
+
+/// Reads the machine instructions from a file, in the format given by its extension.
+///
+fn read_machine_code(path: &Path) -> Result<Vec<u16>> {
+    let format = OutputFormat::from_path(path).ok_or_else(|| {
+        Error::with_message(
+            ErrorKind::InvalidInFileExt,
+            String::from("invalid input file extension, only '.hack', '.bin' or '.hex' accepted"),
+        )
+    })?;
+
+    let contents = fs::read(path).map_err(|e| Error::from(e).with_file(path))?;
+
+    format.decode(&contents).map_err(|e| e.with_file(path))
+}
+
+fn parse_address(text: &str) -> Option<usize> {
+    text.parse::<usize>().ok().filter(|&address| address < RAM_SIZE)
+}
+
+fn invalid_value(option: &str, value: &str) -> Error {
+    Error::with_message(
+        ErrorKind::InvalidOption,
+        format!("invalid value '{}' for option '{}'", value, option),
+    )
+}
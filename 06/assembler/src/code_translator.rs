@@ -1,4 +1,119 @@
+use std::convert::TryFrom;
+use std::fmt::{self, Write};
+use std::ops::{BitAnd, BitOr};
 use crate::error::{Error, ErrorKind, Result};
+use crate::emulator;
+
+/// The valid 'dest' mnemonics along with their 16-bit binary representations.
+///
+const DEST: [(&str, u16); 8] = [
+    ("null", 0b000 << 3),
+    ("M", 0b001 << 3),
+    ("D", 0b010 << 3),
+    ("MD", 0b011 << 3),
+    ("A", 0b100 << 3),
+    ("AM", 0b101 << 3),
+    ("AD", 0b110 << 3),
+    ("AMD", 0b111 << 3),
+];
+
+/// A computation of the Hack ALU that has a documented 'comp' mnemonic.
+///
+/// The ISA is defined by the table of these, COMPUTATIONS, from which the assembler's encoding,
+/// the disassembler's decoding and the reference card are all derived.  What each computes is
+/// not restated here; it follows from the 'c' bits through the emulator's ALU.
+///
+#[derive(Debug, Clone, Copy)]
+pub struct Computation {
+    /// The 'comp' mnemonic, such as 'D+M'.
+    pub mnemonic: &'static str,
+    /// The 'a' bit, which selects M rather than A as the ALU's y input.
+    pub a: u16,
+    /// The six control bits 'zx nx zy ny f no', with 'zx' as the most significant.
+    pub c: u16,
+}
+
+impl Computation {
+    /// Returns the bits of the computation within a C-instruction.
+    ///
+    pub const fn bits(&self) -> u16 {
+        (self.a << 12) | (self.c << 6)
+    }
+
+    /// Returns the value computed from the D register and the y input, which is A or M as the
+    /// 'a' bit selects, by the ALU that the 'c' bits control.
+    ///
+    pub fn compute(&self, d: u16, y: u16) -> u16 {
+        emulator::alu(d, y, self.c)
+    }
+}
+
+/// The documented computations of the Hack ALU.
+///
+pub const COMPUTATIONS: [Computation; 28] = [
+    Computation { mnemonic: "0", a: 0, c: 0b101010 },
+    Computation { mnemonic: "1", a: 0, c: 0b111111 },
+    Computation { mnemonic: "-1", a: 0, c: 0b111010 },
+    Computation { mnemonic: "D", a: 0, c: 0b001100 },
+    Computation { mnemonic: "A", a: 0, c: 0b110000 },
+    Computation { mnemonic: "!D", a: 0, c: 0b001101 },
+    Computation { mnemonic: "!A", a: 0, c: 0b110001 },
+    Computation { mnemonic: "-D", a: 0, c: 0b001111 },
+    Computation { mnemonic: "-A", a: 0, c: 0b110011 },
+    Computation { mnemonic: "D+1", a: 0, c: 0b011111 },
+    Computation { mnemonic: "A+1", a: 0, c: 0b110111 },
+    Computation { mnemonic: "D-1", a: 0, c: 0b001110 },
+    Computation { mnemonic: "A-1", a: 0, c: 0b110010 },
+    Computation { mnemonic: "D+A", a: 0, c: 0b000010 },
+    Computation { mnemonic: "D-A", a: 0, c: 0b010011 },
+    Computation { mnemonic: "A-D", a: 0, c: 0b000111 },
+    Computation { mnemonic: "D&A", a: 0, c: 0b000000 },
+    Computation { mnemonic: "D|A", a: 0, c: 0b010101 },
+    Computation { mnemonic: "M", a: 1, c: 0b110000 },
+    Computation { mnemonic: "!M", a: 1, c: 0b110001 },
+    Computation { mnemonic: "-M", a: 1, c: 0b110011 },
+    Computation { mnemonic: "M+1", a: 1, c: 0b110111 },
+    Computation { mnemonic: "M-1", a: 1, c: 0b110010 },
+    Computation { mnemonic: "D+M", a: 1, c: 0b000010 },
+    Computation { mnemonic: "D-M", a: 1, c: 0b010011 },
+    Computation { mnemonic: "M-D", a: 1, c: 0b000111 },
+    Computation { mnemonic: "D&M", a: 1, c: 0b000000 },
+    Computation { mnemonic: "D|M", a: 1, c: 0b010101 },
+];
+
+/// The valid 'comp' mnemonics along with their 16-bit binary representations, as derived from
+/// COMPUTATIONS.
+///
+const COMP: [(&str, u16); 28] = comp_table();
+
+const fn comp_table() -> [(&'static str, u16); 28] {
+    let mut table = [("", 0); 28];
+    let mut i = 0;
+
+    while i < table.len() {
+        table[i] = (COMPUTATIONS[i].mnemonic, COMPUTATIONS[i].bits());
+        i += 1;
+    }
+
+    table
+}
+
+/// The names of the ALU's six control bits, from the most significant 'c' bit to the least.
+///
+const ALU_CONTROLS: [&str; 6] = ["zx", "nx", "zy", "ny", "f", "no"];
+
+/// The valid 'jump' mnemonics along with their 16-bit binary representations.
+///
+const JUMP: [(&str, u16); 8] = [
+    ("null", 0b000),
+    ("JGT", 0b001),
+    ("JEQ", 0b010),
+    ("JGE", 0b011),
+    ("JLT", 0b100),
+    ("JNE", 0b101),
+    ("JLE", 0b110),
+    ("JMP", 0b111),
+];
 
 /// Translates the 'dest' mnemonic in a C-command into its 16-bit binary
 /// representation.
@@ -9,61 +124,90 @@ use crate::error::{Error, ErrorKind, Result};
 /// assert_eq!(dest("AM").unwrap(), 0b0000000000000101);
 /// '''
 pub fn dest(mnemonic: &str) -> Result<u16> {
-    match mnemonic {
-        "null" => Ok(0b000 << 3),
-        "M" => Ok(0b001 << 3),
-        "D" => Ok(0b010 << 3),
-        "MD" => Ok(0b011 << 3),
-        "A" => Ok(0b100 << 3),
-        "AM" => Ok(0b101 << 3),
-        "AD" => Ok(0b110 << 3),
-        "AMD" => Ok(0b111 << 3),
-        _ => Err(Error::new(ErrorKind::InvalidSyntax)),
-    }
+    translate(mnemonic, &DEST, "dest", ErrorKind::InvalidDest)
 }
 
 /// Translates the 'comp' mnemonic in a C-command into its 16-bit binary
 /// representation.
 ///
+/// Besides the documented mnemonics, the 'a' bit and the ALU's control bits can be given
+/// directly, as in 'alu(a=0,zx,nx,f,no)', to reach the combinations that have no mnemonic.  The
+/// control bits named are set, 'a' is 0 unless given, and the order does not matter.
+///
 /// # Examples
 /// "
 /// '''
 /// assert_eq!(comp("D+A").unwrap(), 0b0000000000000010);
+/// assert_eq!(comp("alu(a=1,f)").unwrap(), comp("D+M").unwrap());
 /// '''
 pub fn comp(mnemonic: &str) -> Result<u16> {
-    match mnemonic {
-        "0" => Ok(0b0101010 << 6),
-        "1" => Ok(0b0111111 << 6),
-        "-1" => Ok(0b0111010 << 6),
-        "D" => Ok(0b0001100 << 6),
-        "A" => Ok(0b0110000 << 6),
-        "!D" => Ok(0b0001101 << 6),
-        "!A" => Ok(0b0110001 << 6),
-        "-D" => Ok(0b0001111 << 6),
-        "-A" => Ok(0b0110011 << 6),
-        "D+1" => Ok(0b0011111 << 6),
-        "A+1" => Ok(0b0110111 << 6),
-        "D-1" => Ok(0b0001110 << 6),
-        "A-1" => Ok(0b0110010 << 6),
-        "D+A" => Ok(0b0000010 << 6),
-        "D-A" => Ok(0b0010011 << 6),
-        "A-D" => Ok(0b0000111 << 6),
-        "D&A" => Ok(0b0000000 << 6),
-        "D|A" => Ok(0b0010101 << 6),
-        "M" => Ok(0b1110000 << 6),
-        "!M" => Ok(0b1110001 << 6),
-        "-M" => Ok(0b1110011 << 6),
-        "M+1" => Ok(0b1110111 << 6),
-        "M-1" => Ok(0b1110010 << 6),
-        "D+M" => Ok(0b1000010 << 6),
-        "D-M" => Ok(0b1010011 << 6),
-        "M-D" => Ok(0b1000111 << 6),
-        "D&M" => Ok(0b1000000 << 6),
-        "D|M" => Ok(0b1010101 << 6),
-        _ => Err(Error::new(ErrorKind::InvalidSyntax)),
+    match mnemonic.strip_prefix("alu(").and_then(|rest| rest.strip_suffix(')')) {
+        Some(controls) => alu_controls(controls),
+        None => translate(mnemonic, &COMP, "comp", ErrorKind::InvalidComp),
     }
 }
 
+/// Translates the comma-separated control bits within an 'alu(...)' comp into their 16-bit binary
+/// representation.
+///
+fn alu_controls(controls: &str) -> Result<u16> {
+    let error = |message: String| Error::with_message(ErrorKind::InvalidComp, message);
+    let mut a = None;
+    let mut c = 0;
+
+    for control in controls.split(',').filter(|_| !controls.is_empty()) {
+        let (bits, name) = match control.strip_prefix("a=") {
+            Some("0") => (0, "a"),
+            Some("1") => (1 << 12, "a"),
+            _ => match ALU_CONTROLS.iter().position(|&name| name == control) {
+                Some(position) => (1 << (11 - position), control),
+                None => {
+                    return Err(error(format!("'{}' is not an ALU control bit", control))
+                        .with_help(String::from("the control bits are a=0, a=1, zx, nx, zy, \
+                            ny, f and no")));
+                },
+            },
+        };
+
+        let repeated = match name {
+            "a" => a.replace(bits).is_some(),
+            _ => c & bits != 0,
+        };
+
+        if repeated {
+            return Err(error(format!("'{}' is given more than once", name)));
+        }
+
+        if name != "a" {
+            c |= bits;
+        }
+    }
+
+    Ok(a.unwrap_or(0) | c)
+}
+
+/// Writes the 'a' and 'c' bits of a C-instruction in the 'alu(...)' form that 'comp' accepts,
+/// which can express the combinations that have no mnemonic.
+///
+/// # Examples
+///
+/// '''
+/// use assembler::code_translator;
+///
+/// assert_eq!(code_translator::alu_syntax(0b1111_1111_1000_0000), "alu(a=1,zx,nx,zy,ny,f)");
+/// '''
+pub fn alu_syntax(instruction: u16) -> String {
+    let mut fields = vec![format!("a={}", (instruction >> 12) & 1)];
+
+    for (position, name) in ALU_CONTROLS.iter().enumerate() {
+        if instruction & (1 << (11 - position)) != 0 {
+            fields.push(String::from(*name));
+        }
+    }
+
+    format!("alu({})", fields.join(","))
+}
+
 /// Translates the 'jump' mnemonic in a C-command into its 16-bit binary
 /// representation.
 ///
@@ -73,22 +217,572 @@ pub fn comp(mnemonic: &str) -> Result<u16> {
 /// assert_eq!(jump("AM").unwrap(), 0b0000000000000101);
 /// '''
 pub fn jump(mnemonic: &str) -> Result<u16> {
-    match mnemonic {
-        "null" => Ok(0b000),
-        "JGT" => Ok(0b001),
-        "JEQ" => Ok(0b010),
-        "JGE" => Ok(0b011),
-        "JLT" => Ok(0b100),
-        "JNE" => Ok(0b101),
-        "JLE" => Ok(0b110),
-        "JMP" => Ok(0b111),
-        _ => Err(Error::new(ErrorKind::InvalidSyntax)),
+    translate(mnemonic, &JUMP, "jump", ErrorKind::InvalidJump)
+}
+
+/// Returns the 'dest' mnemonic encoded in a C-instruction.
+///
+pub fn decode_dest(instruction: u16) -> &'static str {
+    decode(instruction & (0b111 << 3), &DEST).unwrap()
+}
+
+/// Returns an Option containing the 'comp' mnemonic encoded in a C-instruction, or None if the
+/// bits do not correspond to one of the documented mnemonics.
+///
+pub fn decode_comp(instruction: u16) -> Option<&'static str> {
+    computation(instruction).map(|computation| computation.mnemonic)
+}
+
+/// Returns the documented computation selected by the 'a' and 'c' bits of a C-instruction, or
+/// None if they are not one of COMPUTATIONS.
+///
+pub fn computation(instruction: u16) -> Option<&'static Computation> {
+    let bits = instruction & (0b1111111 << 6);
+
+    COMPUTATIONS.iter().find(|computation| computation.bits() == bits)
+}
+
+/// Returns the 'jump' mnemonic encoded in a C-instruction.
+///
+pub fn decode_jump(instruction: u16) -> &'static str {
+    decode(instruction & 0b111, &JUMP).unwrap()
+}
+
+fn decode(bits: u16, table: &[(&'static str, u16)]) -> Option<&'static str> {
+    table.iter().find(|&&(_, b)| b == bits).map(|&(m, _)| m)
+}
+
+/// Returns a reference card for the standard Hack instruction set, listing the bits of each
+/// 'comp', 'dest' and 'jump' mnemonic as they are encoded.
+///
+/// # Examples
+///
+/// '''
+/// use assembler::code_translator;
+///
+/// assert!(code_translator::reference_card().contains("D+M   1  0  0  0  0  1  0\n"));
+/// '''
+pub fn reference_card() -> String {
+    let mut card = String::from("\
+        C-instruction: 1 1 1 a c1 c2 c3 c4 c5 c6 d1 d2 d3 j1 j2 j3\n\
+        \n\
+        comp  a  zx nx zy ny f  no\n\
+        ");
+
+    for computation in COMPUTATIONS.iter() {
+        let (mnemonic, a) = (computation.mnemonic, computation.a);
+
+        writeln!(card, "{:<6}{}  {}", mnemonic, a, columns(computation.c, 6)).unwrap();
+    }
+
+    card.push_str("\ndest  d1 d2 d3\n");
+
+    for &(mnemonic, bits) in DEST.iter() {
+        writeln!(card, "{:<6}{}", mnemonic, columns(bits >> 3, 3)).unwrap();
+    }
+
+    card.push_str("\njump  j1 j2 j3\n");
+
+    for &(mnemonic, bits) in JUMP.iter() {
+        writeln!(card, "{:<6}{}", mnemonic, columns(bits, 3)).unwrap();
     }
+
+    card
+}
+
+/// Writes the given number of low bits, most significant first, as columns of the reference card.
+///
+fn columns(bits: u16, count: u16) -> String {
+    (0..count).rev()
+        .map(|n| if bits & (1 << n) != 0 { "1" } else { "0" })
+        .collect::<Vec<_>>()
+        .join("  ")
+}
+
+/// The bits that mark a machine word as a C-instruction in standard Hack.
+///
+const C_PREFIX: u16 = 0b111 << 13;
+
+/// A machine word that instructions are encoded in, such as the 16-bit word of standard Hack.
+///
+pub trait Word: Copy + PartialEq + fmt::Debug + fmt::Display + fmt::Binary + From<u16>
+    + Into<u64> + TryFrom<u64> + BitAnd<Output = Self> + BitOr<Output = Self>
+{
+    /// The number of bits in the word.
+    const BITS: u32;
+}
+
+impl Word for u16 {
+    const BITS: u32 = u16::BITS;
+}
+
+impl Word for u32 {
+    const BITS: u32 = u32::BITS;
+}
+
+/// An instruction set that C-commands are parsed, encoded and decoded in.
+///
+/// Implementations translate each field of a C-command between its mnemonic and its bits within
+/// an instruction, and decide which machine words are valid instructions.  The parser, assembler
+/// and disassembler are generic over the InstructionSet, so that variants of Hack, such as one
+/// with extra shift operations or one with wider instructions, can be supported without changes
+/// to them.  'Hack', the standard instruction set, is used when none is given.
+///
+/// A-instructions are common to every variant: a word with its top bit clear loads the value of
+/// the remaining bits, so the largest value that can be loaded depends on the size of the Word.
+///
+pub trait InstructionSet {
+    /// The machine word that instructions are encoded in.
+    ///
+    type Word: Word;
+
+    /// Translates a 'dest' mnemonic into its bits within a C-instruction.
+    ///
+    fn dest(&self, mnemonic: &str) -> Result<Self::Word>;
+
+    /// Translates a 'comp' mnemonic into its bits within a C-instruction.  These include the
+    /// opcode bits that mark the word as a C-instruction, so that a variant may select its
+    /// operations with them.
+    ///
+    fn comp(&self, mnemonic: &str) -> Result<Self::Word>;
+
+    /// Translates a 'jump' mnemonic into its bits within a C-instruction.
+    ///
+    fn jump(&self, mnemonic: &str) -> Result<Self::Word>;
+
+    /// Returns the 'dest' mnemonic encoded in a C-instruction, or None if there is none.
+    ///
+    fn decode_dest(&self, instruction: Self::Word) -> Option<&str>;
+
+    /// Returns the 'comp' mnemonic encoded in a C-instruction, or None if there is none.
+    ///
+    fn decode_comp(&self, instruction: Self::Word) -> Option<&str>;
+
+    /// Returns the 'jump' mnemonic encoded in a C-instruction, or None if there is none.
+    ///
+    fn decode_jump(&self, instruction: Self::Word) -> Option<&str>;
+
+    /// Returns the largest value that an A-instruction can load, which is every bit of the word
+    /// other than the top one.
+    ///
+    fn a_limit(&self) -> u64 {
+        (1 << (Self::Word::BITS - 1)) - 1
+    }
+
+    /// Returns the characters other than letters that may appear in a 'comp' mnemonic, which
+    /// the parser uses to recognise C-commands.
+    ///
+    fn comp_operators(&self) -> &str {
+        "01-!+&|"
+    }
+
+    /// Encodes a C-command from its mnemonics, leaving out a null dest or jump.
+    ///
+    fn encode(&self, dest: Option<&str>, comp: &str, jump: Option<&str>) -> Result<Self::Word> {
+        let none = Self::Word::from(0u16);
+        let dest = dest.map_or(Ok(none), |m| self.dest(m))?;
+        let jump = jump.map_or(Ok(none), |m| self.jump(m))?;
+
+        Ok(self.comp(comp)? | dest | jump)
+    }
+
+    /// Returns true if the machine word is a C-instruction rather than an A-instruction.
+    ///
+    fn is_c_instruction(&self, instruction: Self::Word) -> bool {
+        instruction.into() >> (Self::Word::BITS - 1) != 0
+    }
+
+    /// Checks that a machine word is an instruction of this set, which for a C-instruction means
+    /// that each of its fields decodes to a mnemonic.
+    ///
+    fn validate(&self, instruction: Self::Word) -> Result<()> {
+        validate_fields(self, instruction)
+    }
+}
+
+impl<I: InstructionSet + ?Sized> InstructionSet for &I {
+    type Word = I::Word;
+
+    fn dest(&self, mnemonic: &str) -> Result<I::Word> {
+        (**self).dest(mnemonic)
+    }
+
+    fn comp(&self, mnemonic: &str) -> Result<I::Word> {
+        (**self).comp(mnemonic)
+    }
+
+    fn jump(&self, mnemonic: &str) -> Result<I::Word> {
+        (**self).jump(mnemonic)
+    }
+
+    fn decode_dest(&self, instruction: I::Word) -> Option<&str> {
+        (**self).decode_dest(instruction)
+    }
+
+    fn decode_comp(&self, instruction: I::Word) -> Option<&str> {
+        (**self).decode_comp(instruction)
+    }
+
+    fn decode_jump(&self, instruction: I::Word) -> Option<&str> {
+        (**self).decode_jump(instruction)
+    }
+
+    fn a_limit(&self) -> u64 {
+        (**self).a_limit()
+    }
+
+    fn comp_operators(&self) -> &str {
+        (**self).comp_operators()
+    }
+
+    fn is_c_instruction(&self, instruction: I::Word) -> bool {
+        (**self).is_c_instruction(instruction)
+    }
+
+    fn validate(&self, instruction: I::Word) -> Result<()> {
+        (**self).validate(instruction)
+    }
+}
+
+/// Checks that each field of a C-instruction decodes to a mnemonic of the instruction set, as the
+/// default 'validate' does.  A-instructions are always valid.
+///
+fn validate_fields<I>(isa: &I, instruction: I::Word) -> Result<()>
+    where I: InstructionSet + ?Sized
+{
+    if !isa.is_c_instruction(instruction) {
+        return Ok(());
+    }
+
+    let fields = [
+        ("comp", isa.decode_comp(instruction)),
+        ("dest", isa.decode_dest(instruction)),
+        ("jump", isa.decode_jump(instruction)),
+    ];
+
+    match fields.iter().find(|(_, mnemonic)| mnemonic.is_none()) {
+        Some((field, _)) => Err(Error::with_message(ErrorKind::InvalidMachineCode,
+            format!("the {} bits of instruction {:0width$b} are not a documented mnemonic",
+                field, instruction, width = I::Word::BITS as usize))),
+        None => Ok(()),
+    }
+}
+
+/// The standard Hack instruction set, as encoded by the tables in this module.
+///
+/// # Examples
+///
+/// '''
+/// use assembler::code_translator::{Hack, InstructionSet};
+///
+/// assert_eq!(Hack.encode(Some("D"), "D+A", None).unwrap(), 0b1110_0000_1001_0000);
+/// assert_eq!(Hack.decode_comp(0b1110_0000_1001_0000), Some("D+A"));
+/// assert!(Hack.validate(0b1000_1100_0001_0000).is_err());
+/// '''
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Hack;
+
+impl InstructionSet for Hack {
+    type Word = u16;
+
+    fn dest(&self, mnemonic: &str) -> Result<u16> {
+        dest(mnemonic)
+    }
+
+    fn comp(&self, mnemonic: &str) -> Result<u16> {
+        comp(mnemonic).map(|bits| C_PREFIX | bits)
+    }
+
+    fn jump(&self, mnemonic: &str) -> Result<u16> {
+        jump(mnemonic)
+    }
+
+    fn decode_dest(&self, instruction: u16) -> Option<&str> {
+        Some(decode_dest(instruction))
+    }
+
+    fn decode_comp(&self, instruction: u16) -> Option<&str> {
+        decode_comp(instruction)
+    }
+
+    fn decode_jump(&self, instruction: u16) -> Option<&str> {
+        Some(decode_jump(instruction))
+    }
+
+    /// Checks that a machine word is a standard Hack instruction.  The two bits below the top bit
+    /// of a C-instruction are unused, but must be set.
+    ///
+    fn validate(&self, instruction: u16) -> Result<()> {
+        if self.is_c_instruction(instruction) && instruction & C_PREFIX != C_PREFIX {
+            return Err(Error::with_message(ErrorKind::InvalidMachineCode,
+                format!("the unused bits of instruction {:016b} are not set", instruction)));
+        }
+
+        validate_fields(self, instruction)
+    }
+}
+
+/// Looks up the mnemonic in the given table and returns its binary representation.
+///
+/// If the mnemonic is not present, the error returned names the offending mnemonic and, where a
+/// valid mnemonic is sufficiently similar, suggests it as a correction.
+///
+fn translate(mnemonic: &str, table: &[(&'static str, u16)], field: &str, kind: ErrorKind)
+    -> Result<u16>
+{
+    if let Some(&(_, bits)) = table.iter().find(|(m, _)| *m == mnemonic) {
+        return Ok(bits);
+    }
+
+    let error = Error::with_message(kind, format!("{} '{}' is not valid", field, mnemonic));
+
+    match suggest(mnemonic, table) {
+        Some(s) => Err(error.with_help(format!("did you mean '{}'?", s))),
+        None => Err(error),
+    }
+}
+
+/// Returns the valid mnemonic from the table that most closely resembles the given invalid one,
+/// or None if nothing is similar enough to be a likely correction.
+///
+/// In order of preference, a suggestion is a mnemonic that differs only in case, one that uses
+/// the same characters in a different order (e.g. 'MA' for 'AM'), or the closest mnemonic by edit
+/// distance.
+///
+fn suggest(mnemonic: &str, table: &[(&'static str, u16)]) -> Option<&'static str> {
+    let mnemonics = table.iter().map(|&(m, _)| m);
+
+    if let Some(m) = mnemonics.clone().find(|m| m.eq_ignore_ascii_case(mnemonic)) {
+        return Some(m);
+    }
+
+    let sorted = |s: &str| {
+        let mut chars: Vec<char> = s.to_ascii_uppercase().chars().collect();
+        chars.sort_unstable();
+        chars
+    };
+
+    let target = sorted(mnemonic);
+
+    if let Some(m) = mnemonics.clone().find(|m| sorted(m) == target) {
+        return Some(m);
+    }
+
+    let threshold = std::cmp::max(2, mnemonic.chars().count() / 2);
+
+    mnemonics
+        .map(|m| (edit_distance(mnemonic, m), m))
+        .filter(|&(distance, _)| distance <= threshold)
+        .min_by_key(|&(distance, _)| distance)
+        .map(|(_, m)| m)
+}
+
+/// Returns the edit distance between two strings.
+///
+/// Insertions and deletions cost 1.  Substituting a letter for a letter, or a symbol for a
+/// symbol, also costs 1, whilst substituting between the two classes costs 2.  This favours
+/// suggesting a register where a register was intended, e.g. 'D+A' rather than 'D+1' for 'D+Q'.
+///
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, &ca) in a.iter().enumerate() {
+        let mut current = vec![i + 1];
+
+        for (j, &cb) in b.iter().enumerate() {
+            let substitution = if ca == cb {
+                0
+            } else if ca.is_ascii_alphabetic() == cb.is_ascii_alphabetic() {
+                1
+            } else {
+                2
+            };
+
+            let cost = (previous[j] + substitution)
+                .min(previous[j + 1] + 1)
+                .min(current[j] + 1);
+
+            current.push(cost);
+        }
+
+        previous = current;
+    }
+
+    previous[b.len()]
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::{disasm, runner};
+    use crate::symbols::{PredefinedSymbols, SymbolTable};
+
+    /// Standard Hack extended with the shift operations of the nand2tetris CPU emulator, which
+    /// are selected by clearing the second opcode bit.
+    ///
+    struct ShiftHack;
+
+    const SHIFTS: [(&str, u16); 4] = [
+        ("D<<", 0b1010_1100_0000_0000),
+        ("D>>", 0b1010_0100_0000_0000),
+        ("A<<", 0b1010_1000_0000_0000),
+        ("M<<", 0b1011_1000_0000_0000),
+    ];
+
+    impl InstructionSet for ShiftHack {
+        type Word = u16;
+
+        fn dest(&self, mnemonic: &str) -> Result<u16> {
+            Hack.dest(mnemonic)
+        }
+
+        fn comp(&self, mnemonic: &str) -> Result<u16> {
+            match SHIFTS.iter().find(|&&(m, _)| m == mnemonic) {
+                Some(&(_, bits)) => Ok(bits),
+                None => Hack.comp(mnemonic),
+            }
+        }
+
+        fn jump(&self, mnemonic: &str) -> Result<u16> {
+            Hack.jump(mnemonic)
+        }
+
+        fn decode_dest(&self, instruction: u16) -> Option<&str> {
+            Hack.decode_dest(instruction)
+        }
+
+        fn decode_comp(&self, instruction: u16) -> Option<&str> {
+            match instruction >> 13 {
+                0b111 => Hack.decode_comp(instruction),
+                0b101 => decode(instruction & 0b1111_1111_1100_0000, &SHIFTS),
+                _ => None,
+            }
+        }
+
+        fn decode_jump(&self, instruction: u16) -> Option<&str> {
+            Hack.decode_jump(instruction)
+        }
+
+        fn comp_operators(&self) -> &str {
+            "01-!+&|<>"
+        }
+    }
+
+    /// Standard Hack widened to 32-bit instructions, with the fields of a C-instruction in the
+    /// low 13 bits, as they are in standard Hack, and the bits between them and the top bit
+    /// unused.
+    ///
+    struct WideHack;
+
+    impl WideHack {
+        fn narrow(instruction: u32) -> Option<u16> {
+            match instruction & 0x7fff_e000 {
+                0 => Some(C_PREFIX | instruction as u16),
+                _ => None,
+            }
+        }
+    }
+
+    impl InstructionSet for WideHack {
+        type Word = u32;
+
+        fn dest(&self, mnemonic: &str) -> Result<u32> {
+            Hack.dest(mnemonic).map(u32::from)
+        }
+
+        fn comp(&self, mnemonic: &str) -> Result<u32> {
+            Hack.comp(mnemonic).map(|bits| 1 << 31 | u32::from(bits & !C_PREFIX))
+        }
+
+        fn jump(&self, mnemonic: &str) -> Result<u32> {
+            Hack.jump(mnemonic).map(u32::from)
+        }
+
+        fn decode_dest(&self, instruction: u32) -> Option<&str> {
+            WideHack::narrow(instruction).and_then(|narrow| Hack.decode_dest(narrow))
+        }
+
+        fn decode_comp(&self, instruction: u32) -> Option<&str> {
+            WideHack::narrow(instruction).and_then(|narrow| Hack.decode_comp(narrow))
+        }
+
+        fn decode_jump(&self, instruction: u32) -> Option<&str> {
+            WideHack::narrow(instruction).and_then(|narrow| Hack.decode_jump(narrow))
+        }
+    }
+
+    #[test]
+    fn standard_instruction_set() {
+        assert_eq!(Hack.encode(None, "0", Some("JMP")).unwrap(), 0b1110_1010_1000_0111);
+        assert_eq!(Hack.encode(Some("AMD"), "M+1", None).unwrap(), 0b1111_1101_1111_1000);
+        assert_eq!(Hack.comp("D+Q").unwrap_err().kind(), Some(ErrorKind::InvalidComp));
+        assert!(Hack.validate(0b1110_1010_1000_0111).is_ok());
+        assert!(Hack.validate(0b0111_1111_1111_1111).is_ok());
+        assert!(Hack.validate(0b1110_1111_1000_0000).is_err());
+        assert_eq!(
+            Hack.validate(0b1000_1100_0001_0000).unwrap_err().message(),
+            "the unused bits of instruction 1000110000010000 are not set",
+        );
+        assert_eq!(Hack.a_limit(), 32767);
+    }
+
+    #[test]
+    fn extended_instruction_set() {
+        let source = "@SCREEN\nD=D<<\nM=M<<;JMP\nD=D+A\n";
+        let symbols = SymbolTable::with_predefined(PredefinedSymbols::Hack, 16);
+        let assembly = runner::assemble_with(source, symbols, ShiftHack).unwrap();
+
+        assert_eq!(assembly.words, vec![
+            16384,
+            0b1010_1100_0001_0000,
+            0b1011_1000_0000_1111,
+            0b1110_0000_1001_0000,
+        ]);
+        assert_eq!(
+            disasm::disassemble_with(&assembly.words, ShiftHack).unwrap(),
+            "@16384\nD=D<<\nM=M<<;JMP\nD=D+A\n",
+        );
+        assert!(runner::assemble("D=D<<\n", SymbolTable::new()).is_err());
+        assert!(disasm::disassemble(&assembly.words).is_err());
+        assert!(ShiftHack.validate(0b1000_0000_0000_0000).is_err());
+    }
+
+    #[test]
+    fn wide_instruction_set() {
+        let source = "@100000\nD=A\n@2147483647\nAM=M+1;JMP\n";
+        let symbols = SymbolTable::with_predefined(PredefinedSymbols::Hack, 16);
+        let assembly = runner::assemble_with(source, symbols, WideHack).unwrap();
+
+        assert_eq!(assembly.words, vec![
+            100000,
+            0x8000_0000 | 0b0_1100_0001_0000,
+            0x7fff_ffff,
+            0x8000_0000 | 0b1_1101_1110_1111,
+        ]);
+        assert_eq!(
+            disasm::disassemble_with(&assembly.words, WideHack).unwrap(),
+            "@100000\nD=A\n@2147483647\nAM=M+1;JMP\n",
+        );
+        assert_eq!(WideHack.a_limit(), 0x7fff_ffff);
+        assert!(WideHack.validate(0x8001_0c10).is_err());
+
+        let symbols = SymbolTable::with_predefined(PredefinedSymbols::Hack, 16);
+
+        assert!(runner::assemble_with("@2147483648\n", symbols, WideHack).is_err());
+        assert!(runner::assemble("@100000\n", SymbolTable::new()).is_err());
+
+        let symbols = SymbolTable::with_predefined(PredefinedSymbols::Hack, 16);
+        let assembly = runner::assemble_with(".inst 0x8001_0000\n", symbols, WideHack).unwrap();
+
+        assert_eq!(assembly.words, vec![0x8001_0000]);
+
+        let err = runner::assemble(".inst 0x8001_0000\n", SymbolTable::new()).unwrap_err();
+
+        assert_eq!(err.message(), "'0x8001_0000' is not a 16-bit word");
+    }
 
     #[test]
     fn check_dest() {
@@ -163,4 +857,154 @@ mod tests {
     fn jump_syntax_error() {
         jump("AERTGwed").unwrap();
     }
+
+    #[test]
+    fn check_decode() {
+        for &(mnemonic, bits) in DEST.iter() {
+            assert_eq!(decode_dest(0b1110_0000_0000_0000 | bits), mnemonic);
+        }
+
+        for &(mnemonic, bits) in COMP.iter() {
+            assert_eq!(decode_comp(0b1110_0000_0011_1111 | bits), Some(mnemonic));
+        }
+
+        for &(mnemonic, bits) in JUMP.iter() {
+            assert_eq!(decode_jump(0b1111_1111_1111_1000 | bits), mnemonic);
+        }
+
+        // 'a' bit set with a 'c' pattern only documented for the A register.
+        assert_eq!(decode_comp(0b1111_1111_1100_0000), None);
+    }
+
+    #[test]
+    fn computations_round_trip() {
+        for computation in COMPUTATIONS.iter() {
+            let instruction = 0b1110_0000_0000_0000 | computation.bits();
+
+            assert_eq!(comp(computation.mnemonic).unwrap(), computation.bits());
+            assert_eq!(decode_comp(instruction), Some(computation.mnemonic));
+            assert_eq!(Hack.comp(computation.mnemonic).unwrap(), instruction);
+        }
+
+        // Every pattern of 'a' and 'c' bits either decodes to a mnemonic that encodes back to
+        // it, or is undocumented.
+        let documented = (0..0b1000_0000).filter(|bits| {
+            match decode_comp(bits << 6) {
+                Some(mnemonic) => {
+                    assert_eq!(comp(mnemonic).unwrap(), bits << 6);
+                    true
+                },
+                None => false,
+            }
+        });
+
+        assert_eq!(documented.count(), COMPUTATIONS.len());
+    }
+
+    #[test]
+    fn dest_and_jump_round_trip() {
+        for bits in 0..0b1000 {
+            assert_eq!(dest(decode_dest(bits << 3)).unwrap(), bits << 3);
+            assert_eq!(jump(decode_jump(bits)).unwrap(), bits);
+        }
+    }
+
+    #[test]
+    fn computations_compute_their_mnemonics() {
+        let values = [0, 1, 2, 7, 0x7fff, 0x8000, 0xfffe, 0xffff];
+        let meaning = |mnemonic: &str, d: u16, y: u16| match &mnemonic.replace('M', "A")[..] {
+            "0" => 0,
+            "1" => 1,
+            "-1" => 0xffff,
+            "D" => d,
+            "A" => y,
+            "!D" => !d,
+            "!A" => !y,
+            "-D" => d.wrapping_neg(),
+            "-A" => y.wrapping_neg(),
+            "D+1" => d.wrapping_add(1),
+            "A+1" => y.wrapping_add(1),
+            "D-1" => d.wrapping_sub(1),
+            "A-1" => y.wrapping_sub(1),
+            "D+A" => d.wrapping_add(y),
+            "D-A" => d.wrapping_sub(y),
+            "A-D" => y.wrapping_sub(d),
+            "D&A" => d & y,
+            "D|A" => d | y,
+            other => panic!("no meaning given for {}", other),
+        };
+
+        for computation in COMPUTATIONS.iter() {
+            for &d in values.iter() {
+                for &y in values.iter() {
+                    assert_eq!(
+                        computation.compute(d, y),
+                        meaning(computation.mnemonic, d, y),
+                        "{} with D={} and y={}", computation.mnemonic, d, y,
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn check_reference_card() {
+        let card = reference_card();
+        let lines: Vec<&str> = card.lines().collect();
+
+        assert_eq!(lines.len(), 2 + 1 + 28 + 2 + 8 + 2 + 8);
+        assert_eq!(lines[3], "0     0  1  0  1  0  1  0");
+        assert_eq!(lines[30], "D|M   1  0  1  0  1  0  1");
+        assert_eq!(lines[40], "AMD   1  1  1");
+        assert_eq!(lines[44], "JGT   0  0  1");
+    }
+
+    #[test]
+    fn check_alu_syntax() {
+        assert_eq!(comp("alu(a=0,zx,nx,f,no)").unwrap(), 0b0110011 << 6);
+        assert_eq!(comp("alu(no,f,a=1)").unwrap(), comp("alu(a=1,f,no)").unwrap());
+        assert_eq!(comp("alu()").unwrap(), comp("D&A").unwrap());
+
+        // Every pattern of 'a' and 'c' bits can be written, and read back, in the raw form.
+        for bits in 0..0b1000_0000 {
+            assert_eq!(comp(&alu_syntax(bits << 6)).unwrap(), bits << 6);
+        }
+
+        let invalid = [
+            ("alu(zx,xz)", "'xz' is not an ALU control bit"),
+            ("alu(a=2)", "'a=2' is not an ALU control bit"),
+            ("alu(zx,,f)", "'' is not an ALU control bit"),
+            ("alu(f,zx,f)", "'f' is given more than once"),
+            ("alu(a=0,a=1)", "'a' is given more than once"),
+        ];
+
+        for (mnemonic, message) in invalid.iter() {
+            let error = comp(mnemonic).unwrap_err();
+
+            assert_eq!(error.kind(), Some(ErrorKind::InvalidComp));
+            assert_eq!(error.message(), *message);
+        }
+    }
+
+    #[test]
+    fn check_suggestions() {
+        let help = |r: Result<u16>| r.unwrap_err().help().map(String::from);
+
+        assert_eq!(help(comp("D+Q")), Some(String::from("did you mean 'D+A'?")));
+        assert_eq!(help(comp("A+D")), Some(String::from("did you mean 'D+A'?")));
+        assert_eq!(help(comp("d|m")), Some(String::from("did you mean 'D|M'?")));
+        assert_eq!(help(dest("MA")), Some(String::from("did you mean 'AM'?")));
+        assert_eq!(help(dest("DMA")), Some(String::from("did you mean 'AMD'?")));
+        assert_eq!(help(jump("JMPX")), Some(String::from("did you mean 'JMP'?")));
+        assert_eq!(help(jump("jne")), Some(String::from("did you mean 'JNE'?")));
+        assert_eq!(help(comp("AERTGwed")), None);
+    }
+
+    #[test]
+    fn check_error_message() {
+        let error = comp("D+Q").unwrap_err();
+
+        assert_eq!(error.message(), "comp 'D+Q' is not valid");
+        assert_eq!(error.kind(), Some(ErrorKind::InvalidComp));
+    }
 }
@@ -1,17 +1,148 @@
+use std::collections::HashSet;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use crate::batch::{self, Batch};
+use crate::diagnostics::Format;
 use crate::error::{Error, ErrorKind, Result};
-use regex::Regex;
+use crate::object;
+use crate::output::OutputFormat;
+use crate::project::{LintLevel, Project};
+use crate::memory::MemoryMap;
+use crate::symbols::{PredefinedSymbols, VARIABLE_BASE};
 
-/// A struct to hold configuration options used when running the assembler.
+/// The usage message printed in response to '--help'.
+///
+pub const USAGE: &str = "\
+Translates a Hack assembly program into Hack machine code.
+
+Usage: assembler [OPTIONS] <INPUT.asm> [OUTPUT]
+       assembler [OPTIONS] <INPUT.asm|DIR>...
+
+Each DIR is searched recursively for '.asm' files.  When more than one file is assembled, each
+output is written alongside its input and a summary is reported at the end.
+
+Settings are also read from the nearest 'hackasm.toml' in the directory of each input or one of
+its ancestors.  Options given on the command line take precedence over the project file.
+
+Options:
+  -o, --output <PATH>              Write the machine code to PATH [default: INPUT with the
+                                   extension of the output format]
+      --format <FORMAT>            Output format: hack, bin or hex [default: hack]
+  -c, --compile                    Write a relocatable object file for linking, rather than
+                                   machine code [default output: INPUT.hobj]
+      --listing[=PATH]             Also write a listing of each source line alongside its
+                                   address and machine instruction [default: INPUT.lst]
+      --symbols[=PATH]             Also write the label and variable symbols [default: INPUT.sym]
+      --out-dir <DIR>              Write output files to DIR rather than alongside each input
+  -D, --define <NAME=VALUE>        Define a symbol with a constant value
+      --memory-map <PATH>          Read the predefined symbols and memory layout from PATH
+      --cache-dir <DIR>            Reuse the results of assembling unchanged inputs, kept in DIR
+      --no-cache                   Assemble every input, ignoring any cache directory set
+      --no-project                 Ignore any 'hackasm.toml' project file
+      --allow/--warn/--deny <LINT> Set the level at which a lint is reported
+      --stats                      Report ROM/RAM usage and the instruction mix
+      --diagnostics-format <FMT>   Report errors as human, json or sarif [default: human]
+  -j, --jobs <N>                   Assemble up to N files in parallel [default: number of CPUs]
+      --check                      Check the input for errors without writing any files
+      --watch                      Keep running, and assemble again whenever an input changes
+  -q, --quiet                      Do not report a summary on success
+  -h, --help                       Print this help message
+  -V, --version                    Print version information
+";
+
+/// The action requested by the command line arguments.
 ///
 #[derive(Debug, PartialEq)]
+pub enum Invocation {
+    /// Assemble one or more programs.
+    Assemble(Batch),
+    /// Print the usage message.
+    Help,
+    /// Print the version of the assembler.
+    Version,
+}
+
+/// A struct to hold configuration options used when running the assembler.
+///
+/// A Config can be generated from command line arguments with 'new' or 'parse', or created
+/// programmatically with 'for_input' and then adjusted through its public fields.
+///
+/// 'lints' are carried from the project file and command line for tools that make use of them,
+/// such as the 'lint' module; they do not affect assembly.
+///
+/// When a 'memory_map' is set, its predefined symbols and layout are used in place of
+/// 'predefined_symbols' and 'variable_base'.
+///
+#[derive(Debug, Clone, PartialEq)]
 pub struct Config {
-    pub infile: String,
-    pub outfile: String,
+    pub infile: PathBuf,
+    pub outfile: PathBuf,
+    pub format: OutputFormat,
+    pub object: bool,
+    pub listing: Option<PathBuf>,
+    pub symbols: Option<PathBuf>,
+    pub out_dir: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>,
+    pub defines: Vec<(String, u16)>,
+    pub predefined_symbols: PredefinedSymbols,
+    pub variable_base: u16,
+    pub memory_map: Option<MemoryMap>,
+    pub lints: Vec<(String, LintLevel)>,
+    pub diagnostics_format: Format,
+    pub stats: bool,
+    pub quiet: bool,
+    pub watch: bool,
+    pub check: bool,
 }
 
 impl Config {
+    /// Creates a Config that assembles the given input file with the default options.  The
+    /// machine code is written alongside the input file, with its extension replaced by '.hack'.
+    ///
+    /// # Examples
+    ///
+    /// '''
+    /// use assembler::config::Config;
+    ///
+    /// let config = Config::for_input("prog/Max.asm");
+    ///
+    /// assert_eq!(config.outfile, std::path::PathBuf::from("prog/Max.hack"));
+    /// '''
+    pub fn for_input<P>(infile: P) -> Config
+        where P: Into<PathBuf>
+    {
+        let infile = infile.into();
+        let format = OutputFormat::Hack;
+
+        Config {
+            outfile: infile.with_extension(format.extension()),
+            infile,
+            format,
+            object: false,
+            listing: None,
+            symbols: None,
+            out_dir: None,
+            cache_dir: None,
+            defines: Vec::new(),
+            predefined_symbols: PredefinedSymbols::Hack,
+            variable_base: VARIABLE_BASE,
+            memory_map: None,
+            lints: Vec::new(),
+            diagnostics_format: Format::Human,
+            stats: false,
+            quiet: false,
+            watch: false,
+            check: false,
+        }
+    }
+
     /// The constructor method takes command line arguments, provided to it as an
-    /// iterator that yields Strings.
+    /// iterator that yields Strings or OsStrings.
+    ///
+    /// See 'parse' for the arguments accepted.  A request for help or version information, or
+    /// for more than one input file, is rejected as an unexpected argument, as it does not
+    /// describe a single assembly.
     ///
     /// # Examples
     ///
@@ -20,47 +151,381 @@ impl Config {
     /// use assembler::config::Config;
     ///
     /// fn main() {
-    ///     // env::args() returns the arguments this program was started with
-    ///     // as an 'Args' iterator that yields Strings.
-    ///     let config = Config::new(env::args()).unwrap();
+    ///     // env::args_os() returns the arguments this program was started with
+    ///     // as an 'ArgsOs' iterator that yields OsStrings.
+    ///     let config = Config::new(env::args_os()).unwrap();
     /// }
     /// '''
-    pub fn new<T>(mut args: T) -> Result<Config>
+    pub fn new<T>(args: T) -> Result<Config>
     where
-        T: Iterator<Item = String>,
+        T: IntoIterator,
+        T::Item: Into<OsString>,
     {
+        match Config::parse(args)? {
+            Invocation::Assemble(batch) if batch.configs.len() == 1 => {
+                Ok(batch.configs.into_iter().next().unwrap())
+            },
+            _ => Err(Error::new(ErrorKind::UnexpectedArgument)),
+        }
+    }
+
+    /// Parses command line arguments, provided as an iterator that yields Strings or OsStrings,
+    /// the first of which is the path of the executable.
+    ///
+    /// Options may appear anywhere amongst the input and output paths, and '--' marks the end of
+    /// the options.  Paths need not be valid Unicode, except when given in the '--name=value'
+    /// form.  See 'USAGE' for the options accepted.
+    ///
+    /// The output path may be given either with '-o/--output' or as a second positional
+    /// argument that is neither a directory nor an '.asm' file.  If it is omitted, the input path
+    /// is used with its extension replaced by that of the output format.
+    ///
+    /// Any other positional arguments are further inputs.  Directories are searched recursively
+    /// for '.asm' files.  Output, listing and symbol paths cannot be given when more than one
+    /// file is to be assembled.
+    ///
+    /// Unless '--no-project' is given, the settings of the project file nearest to each input are
+    /// merged into its Config, with the command line options taking precedence.  When an output
+    /// directory is set, inputs found by searching a directory keep their relative location
+    /// within it.
+    ///
+    pub fn parse<T>(args: T) -> Result<Invocation>
+    where
+        T: IntoIterator,
+        T::Item: Into<OsString>,
+    {
+        let mut args = args.into_iter().map(Into::into);
+
         args.next();  // Ignore path of executable.
 
-        let re_asm_ext = Regex::new(r"\.asm$").unwrap();
+        let mut config = Config::for_input(PathBuf::new());
+        let mut flags = Flags::default();
+        let mut jobs = None;
+        let mut no_project = false;
+        let mut positional = Vec::new();
+        let mut options_ended = false;
+
+        while let Some(arg) = args.next() {
+            let text = match arg.to_str() {
+                Some(text) if !options_ended && text.starts_with('-') && text.len() > 1 => text,
+                _ => {
+                    positional.push(arg);
+                    continue;
+                },
+            };
+
+            // Split '--name=value' into its name and value.
+            let (name, value) = match text.find('=') {
+                Some(i) if text.starts_with("--") => (&text[..i], Some(&text[i + 1..])),
+                _ => (text, None),
+            };
+
+            match name {
+                "--" => options_ended = true,
+                "-h" | "--help" => return Ok(Invocation::Help),
+                "-V" | "--version" => return Ok(Invocation::Version),
+                "-q" | "--quiet" => config.quiet = true,
+                "--stats" => config.stats = true,
+                "--watch" => config.watch = true,
+                "--check" => config.check = true,
+                "-c" | "--compile" => config.object = true,
+                "--no-project" => no_project = true,
+                "--no-cache" => flags.no_cache = true,
+                "-o" | "--output" => {
+                    flags.outfile = Some(PathBuf::from(option_value(name, value, &mut args)?));
+                },
+                "--format" => {
+                    let value = option_value(name, value, &mut args)?;
+                    flags.format = Some(option_str(name, value)?.parse()?);
+                },
+                "--out-dir" => {
+                    flags.out_dir = Some(PathBuf::from(option_value(name, value, &mut args)?));
+                },
+                "-D" | "--define" => {
+                    let value = option_value(name, value, &mut args)?;
+                    flags.defines.push(parse_define(name, &option_str(name, value)?)?);
+                },
+                "--memory-map" => {
+                    flags.memory_map = Some(PathBuf::from(option_value(name, value, &mut args)?));
+                },
+                "--cache-dir" => {
+                    flags.cache_dir = Some(PathBuf::from(option_value(name, value, &mut args)?));
+                },
+                "--allow" | "--warn" | "--deny" => {
+                    let lint = option_str(name, option_value(name, value, &mut args)?)?;
+                    flags.lints.push((lint, name[2..].parse()?));
+                },
+                "--diagnostics-format" => {
+                    let value = option_value(name, value, &mut args)?;
+                    config.diagnostics_format = option_str(name, value)?.parse()?;
+                },
+                "-j" | "--jobs" => {
+                    let value = option_value(name, value, &mut args)?;
+                    let value = option_str(name, value)?;
+
+                    jobs = match value.parse::<usize>() {
+                        Ok(n) if n > 0 => Some(n),
+                        _ => {
+                            return Err(Error::with_message(
+                                ErrorKind::InvalidOption,
+                                format!("invalid value '{}' for option '{}'", value, name),
+                            ));
+                        },
+                    };
+                },
+                "--listing" => flags.listing = Some(value.map(PathBuf::from)),
+                "--symbols" => flags.symbols = Some(value.map(PathBuf::from)),
+                _ if name.starts_with("-o") => flags.outfile = Some(PathBuf::from(&name[2..])),
+                _ => {
+                    return Err(Error::with_message(
+                        ErrorKind::InvalidOption,
+                        format!("unrecognised command line option '{}'", name),
+                    ));
+                },
+            }
+
+            let takes_value = matches!(
+                name,
+                "-o" | "--output" | "--format" | "--diagnostics-format" | "-j" | "--jobs" |
+                "--listing" | "--symbols" | "--out-dir" | "-D" | "--define" | "--memory-map" |
+                "--cache-dir" | "--allow" | "--warn" | "--deny"
+            );
+
+            if value.is_some() && !takes_value {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidOption,
+                    format!("unrecognised command line option '{}'", text),
+                ));
+            }
+        }
+
+        if positional.is_empty() {
+            return Err(Error::new(ErrorKind::MissingArguments));
+        }
+
+        // A second argument that could not be an input is the output path of a single assembly.
+        if positional.len() == 2 {
+            let path = Path::new(&positional[1]);
 
-        let infile = match args.next() {
-            Some(arg) => {
-                if re_asm_ext.is_match(&arg[..]) {
-                    arg
-                } else {
-                    return Err(Error::new(ErrorKind::InvalidInFileExt));
+            if !path.is_dir() && !has_extension(path, "asm") {
+                if flags.outfile.is_some() {
+                    return Err(Error::new(ErrorKind::UnexpectedArgument));
                 }
-            },
-            None => return Err(Error::new(ErrorKind::MissingArguments)),
-        };
+                flags.outfile = positional.pop().map(PathBuf::from);
+            }
+        }
+
+        // Each input is paired with the directory it was found in, if it was found by searching.
+        let mut infiles: Vec<(PathBuf, Option<PathBuf>)> = Vec::new();
 
-        let outfile = match args.next() {
-            Some(arg) => {
-                let re_hack_ext = Regex::new(r"\.hack$").unwrap();
+        for arg in positional {
+            let path = PathBuf::from(arg);
 
-                if re_hack_ext.is_match(&arg[..]) {
-                    arg
-                } else {
-                    return Err(Error::new(ErrorKind::InvalidOutFileExt));
+            if path.is_dir() {
+                let sources = batch::find_sources(&path)?;
+
+                if sources.is_empty() {
+                    return Err(Error::with_message(
+                        ErrorKind::MissingArguments,
+                        format!("no '.asm' files were found in '{}'", path.display()),
+                    ));
                 }
+                infiles.extend(sources.into_iter().map(|source| (source, Some(path.clone()))));
+            } else if !has_extension(&path, "asm") {
+                return Err(Error::new(ErrorKind::InvalidInFileExt));
+            } else {
+                infiles.push((path, None));
+            }
+        }
+
+        // The same file given twice would be written by two threads at once, even when it is
+        // named by different paths, such as 'a.asm' and './a.asm' or through a symbolic link.
+        let mut seen = HashSet::new();
+        infiles.retain(|(path, _)| {
+            seen.insert(fs::canonicalize(path).unwrap_or_else(|_| path.clone()))
+        });
+
+        let explicit_paths = flags.outfile.is_some()
+            || matches!(flags.listing, Some(Some(_)))
+            || matches!(flags.symbols, Some(Some(_)));
+
+        if explicit_paths && infiles.len() > 1 {
+            return Err(Error::with_message(
+                ErrorKind::UnexpectedArgument,
+                String::from("output paths cannot be given when assembling more than one file"),
+            ));
+        }
+
+        if config.check && (explicit_paths || flags.listing.is_some() || flags.symbols.is_some()) {
+            return Err(Error::with_message(
+                ErrorKind::UnexpectedArgument,
+                String::from("output files cannot be requested with '--check'"),
+            ));
+        }
+
+        if config.object && flags.format.is_some() {
+            return Err(Error::with_message(
+                ErrorKind::UnexpectedArgument,
+                String::from("'--format' cannot be used with '-c', which writes an object file"),
+            ));
+        }
+
+        let mut configs = Vec::with_capacity(infiles.len());
+
+        for (infile, root) in infiles {
+            let project = match no_project {
+                true => None,
+                false => Project::discover(infile.parent().unwrap_or_else(|| Path::new("")))?,
+            };
+
+            let mut infile_config = Config { infile, ..config.clone() };
+
+            infile_config.configure(&project.unwrap_or_default(), &flags, root.as_deref())?;
+            configs.push(infile_config);
+        }
+
+        let mut batch = Batch::new(configs);
+
+        if let Some(jobs) = jobs {
+            batch.jobs = jobs;
+        }
+
+        Ok(Invocation::Assemble(batch))
+    }
+}
+
+/// The options given on the command line that are merged with the settings of a project file.
+///
+#[derive(Debug, Default)]
+struct Flags {
+    outfile: Option<PathBuf>,
+    listing: Option<Option<PathBuf>>,
+    symbols: Option<Option<PathBuf>>,
+    format: Option<OutputFormat>,
+    out_dir: Option<PathBuf>,
+    defines: Vec<(String, u16)>,
+    memory_map: Option<PathBuf>,
+    cache_dir: Option<PathBuf>,
+    no_cache: bool,
+    lints: Vec<(String, LintLevel)>,
+}
+
+impl Config {
+    /// Fills in the settings of a Config for its input file, taking each from the command line
+    /// flags if given, otherwise from the project, and otherwise keeping the default.  Output
+    /// paths are derived from the input path, and placed below the output directory if one is
+    /// set, where 'root' is the directory that the input was found by searching.
+    ///
+    fn configure(&mut self, project: &Project, flags: &Flags, root: Option<&Path>) -> Result<()> {
+        self.format = flags.format.or(project.format).unwrap_or(self.format);
+        self.out_dir = flags.out_dir.clone().or_else(|| project.out_dir.clone());
+        self.predefined_symbols = project.predefined_symbols.unwrap_or(self.predefined_symbols);
+        self.variable_base = project.variable_base.unwrap_or(self.variable_base);
+
+        if let Some(path) = flags.memory_map.as_ref().or(project.memory_map.as_ref()) {
+            self.memory_map = Some(MemoryMap::load(path)?);
+        }
+        self.lints = project.lints.clone();
+        self.lints.extend(flags.lints.iter().cloned());
+
+        self.cache_dir = match flags.no_cache {
+            true => None,
+            false => flags.cache_dir.clone().or_else(|| project.cache_dir.clone()),
+        };
+
+        self.defines = project.defines.clone();
+
+        for (name, value) in flags.defines.iter() {
+            self.defines.retain(|(defined, _)| defined != name);
+            self.defines.push((name.clone(), *value));
+        }
+
+        let extension = match self.object {
+            true => object::EXTENSION,
+            false => self.format.extension(),
+        };
+
+        if let Some(ref path) = flags.outfile {
+            if !has_extension(path, extension) {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidOutFileExt,
+                    format!("invalid output file extension, only '.{}' accepted", extension),
+                ));
             }
-            None => return Err(Error::new(ErrorKind::MissingOutputFilename)),
+        }
+
+        let base = match self.out_dir {
+            Some(ref dir) => {
+                let relative = root
+                    .and_then(|root| self.infile.strip_prefix(root).ok())
+                    .or_else(|| self.infile.file_name().map(Path::new))
+                    .unwrap_or(&self.infile);
+
+                dir.join(relative)
+            },
+            None => self.infile.clone(),
         };
 
-        Ok(Config { infile, outfile })
+        self.outfile = flags.outfile.clone().unwrap_or_else(|| base.with_extension(extension));
+        self.listing = flags.listing.clone()
+            .map(|path| path.unwrap_or_else(|| base.with_extension("lst")));
+        self.symbols = flags.symbols.clone()
+            .map(|path| path.unwrap_or_else(|| base.with_extension("sym")));
+
+        Ok(())
+    }
+}
+
+/// Parses the value of a '--define' option, in the form 'NAME=VALUE'.
+///
+fn parse_define(option: &str, define: &str) -> Result<(String, u16)> {
+    let invalid = || {
+        Error::with_message(
+            ErrorKind::InvalidOption,
+            format!("invalid value '{}' for option '{}', expected NAME=VALUE", define, option),
+        )
+    };
+
+    let (name, value) = define.split_once('=').ok_or_else(invalid)?;
+
+    match value.parse::<u16>() {
+        Ok(value) if !name.is_empty() && value <= 0x7fff => Ok((String::from(name), value)),
+        _ => Err(invalid()),
     }
 }
 
+/// Returns the value of an option, given either as part of the same argument or as the next
+/// argument.
+///
+fn option_value<T>(name: &str, value: Option<&str>, args: &mut T) -> Result<OsString>
+    where T: Iterator<Item = OsString>
+{
+    match value {
+        Some(value) => Ok(OsString::from(value)),
+        None => args.next().ok_or_else(|| {
+            Error::with_message(
+                ErrorKind::MissingOptionValue,
+                format!("a value was not provided for option '{}'", name),
+            )
+        }),
+    }
+}
+
+/// Converts an option value that must be valid Unicode into a String.
+///
+fn option_str(name: &str, value: OsString) -> Result<String> {
+    value.into_string().map_err(|_| {
+        Error::with_message(
+            ErrorKind::InvalidOption,
+            format!("the value for option '{}' is not valid Unicode", name),
+        )
+    })
+}
+
+fn has_extension(path: &Path, extension: &str) -> bool {
+    path.extension() == Some(OsStr::new(extension))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -78,14 +543,91 @@ mod tests {
         assert_eq!(
             Config::new(args).unwrap(),
             Config {
-                infile: String::from("test_input_file.asm"),
-                outfile: String::from("test_output_file.hack"),
+                infile: PathBuf::from("test_input_file.asm"),
+                outfile: PathBuf::from("test_output_file.hack"),
+                format: OutputFormat::Hack,
+                object: false,
+                listing: None,
+                symbols: None,
+                out_dir: None,
+                cache_dir: None,
+                defines: Vec::new(),
+                predefined_symbols: PredefinedSymbols::Hack,
+                variable_base: VARIABLE_BASE,
+                memory_map: None,
+                lints: Vec::new(),
+                diagnostics_format: Format::Human,
+                stats: false,
+                quiet: false,
+                watch: false,
+                check: false,
             }
         );
     }
 
     #[test]
-    #[should_panic(expected = "invalid input file extension, only \\\'.asm\\\' accepted")]
+    fn check_diagnostics_format() {
+        let mut args = vec![
+            String::from("ignore/the/path"),
+            String::from("--diagnostics-format=sarif"),
+            String::from("test_input_file.asm"),
+            String::from("test_output_file.hack"),
+        ];
+
+        let args = args.drain(..);
+
+        assert_eq!(Config::new(args).unwrap().diagnostics_format, Format::Sarif);
+    }
+
+    #[test]
+    fn check_stats_flag() {
+        let mut args = vec![
+            String::from("ignore/the/path"),
+            String::from("test_input_file.asm"),
+            String::from("--stats"),
+            String::from("test_output_file.hack"),
+        ];
+
+        let args = args.drain(..);
+
+        let config = Config::new(args).unwrap();
+
+        assert!(config.stats);
+        assert_eq!(config.outfile, PathBuf::from("test_output_file.hack"));
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid diagnostics format, only 'human', 'json' or 'sarif' accepted")]
+    fn check_invalid_diagnostics_format() {
+        let mut args = vec![
+            String::from("ignore/the/path"),
+            String::from("test_input_file.asm"),
+            String::from("test_output_file.hack"),
+            String::from("--diagnostics-format=xml"),
+        ];
+
+        let args = args.drain(..);
+
+        Config::new(args).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "unrecognised command line option")]
+    fn check_invalid_option() {
+        let mut args = vec![
+            String::from("ignore/the/path"),
+            String::from("--fast"),
+            String::from("test_input_file.asm"),
+            String::from("test_output_file.hack"),
+        ];
+
+        let args = args.drain(..);
+
+        Config::new(args).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid input file extension, only '.asm' accepted")]
     fn check_invalid_infilename() {
         let mut args = vec![
             String::from("ignore/the/path"),
@@ -99,7 +641,7 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "invalid output file extension, only \\\'.hack\\\' accepted")]
+    #[should_panic(expected = "invalid output file extension, only '.hack' accepted")]
     fn check_invalid_outfilename() {
         let mut args = vec![
             String::from("ignore/the/path"),
@@ -113,7 +655,7 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "input and output filenames were not provided")]
+    #[should_panic(expected = "an input filename was not provided")]
     fn check_missing_args() {
         let mut args = vec![
             String::from("ignore/the/path"),
@@ -125,15 +667,233 @@ mod tests {
     }
 
     #[test]
-    #[should_panic(expected = "output filename not provided")]
-    fn check_missing_outfilename() {
+    fn check_default_outfilename() {
         let mut args = vec![
             String::from("ignore/the/path"),
-            String::from("test_input_file.asm"),
+            String::from("dir/test_input_file.asm"),
         ];
 
         let args = args.drain(..);
 
-        Config::new(args).unwrap();
+        assert_eq!(
+            Config::new(args).unwrap(),
+            Config::for_input("dir/test_input_file.asm"),
+        );
+    }
+
+    #[test]
+    fn check_options() {
+        let args = vec![
+            "ignore/the/path",
+            "-q",
+            "--format",
+            "bin",
+            "--listing",
+            "--symbols=out/syms.txt",
+            "--diagnostics-format",
+            "json",
+            "test_input_file.asm",
+        ];
+
+        let config = Config::new(args).unwrap();
+
+        assert_eq!(config.format, OutputFormat::Binary);
+        assert_eq!(config.outfile, PathBuf::from("test_input_file.bin"));
+        assert_eq!(config.listing, Some(PathBuf::from("test_input_file.lst")));
+        assert_eq!(config.symbols, Some(PathBuf::from("out/syms.txt")));
+        assert_eq!(config.diagnostics_format, Format::Json);
+        assert!(config.quiet);
+    }
+
+    #[test]
+    fn check_output_option() {
+        for args in [
+            vec!["ignore/the/path", "-o", "out/test.hack", "test_input_file.asm"],
+            vec!["ignore/the/path", "test_input_file.asm", "--output=out/test.hack"],
+            vec!["ignore/the/path", "-oout/test.hack", "--", "test_input_file.asm"],
+        ] {
+            assert_eq!(Config::new(args).unwrap().outfile, PathBuf::from("out/test.hack"));
+        }
+    }
+
+    #[test]
+    fn check_compile_option() {
+        let config = Config::new(vec!["path", "-c", "dir/test_input_file.asm"]).unwrap();
+
+        assert!(config.object);
+        assert_eq!(config.outfile, PathBuf::from("dir/test_input_file.hobj"));
+
+        let config = Config::new(vec!["path", "--compile", "a.asm", "-o", "b.hobj"]).unwrap();
+
+        assert_eq!(config.outfile, PathBuf::from("b.hobj"));
+
+        let err = Config::new(vec!["path", "-c", "a.asm", "a.hack"]).unwrap_err();
+
+        assert_eq!(err.message(), "invalid output file extension, only '.hobj' accepted");
+
+        let err = Config::new(vec!["path", "-c", "--format=hex", "a.asm"]).unwrap_err();
+
+        assert_eq!(err.kind(), Some(ErrorKind::UnexpectedArgument));
+    }
+
+    #[test]
+    fn check_cache_options() {
+        let config = Config::new(vec!["path", "--cache-dir", "cache", "a.asm"]).unwrap();
+
+        assert_eq!(config.cache_dir, Some(PathBuf::from("cache")));
+
+        let project = Project {
+            cache_dir: Some(PathBuf::from("proj/cache")),
+            ..Project::default()
+        };
+        let mut config = Config::for_input("a.asm");
+
+        config.configure(&project, &Flags::default(), None).unwrap();
+
+        assert_eq!(config.cache_dir, Some(PathBuf::from("proj/cache")));
+
+        let flags = Flags { no_cache: true, ..Flags::default() };
+
+        config.configure(&project, &flags, None).unwrap();
+
+        assert_eq!(config.cache_dir, None);
+    }
+
+    #[test]
+    fn check_help_and_version() {
+        assert_eq!(Config::parse(vec!["path", "a.asm", "--help"]).unwrap(), Invocation::Help);
+        assert_eq!(Config::parse(vec!["path", "-V"]).unwrap(), Invocation::Version);
+    }
+
+    #[test]
+    #[should_panic(expected = "unexpected argument")]
+    fn check_output_given_twice() {
+        Config::new(vec!["path", "-o", "a.hack", "test_input_file.asm", "b.hack"]).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "a value was not provided for option '--output'")]
+    fn check_missing_option_value() {
+        Config::new(vec!["path", "test_input_file.asm", "--output"]).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "unrecognised command line option '--stats=yes'")]
+    fn check_unexpected_option_value() {
+        Config::new(vec!["path", "test_input_file.asm", "--stats=yes"]).unwrap();
+    }
+
+    #[test]
+    fn check_multiple_inputs() {
+        let args = vec!["path", "-j", "3", "--listing", "a.asm", "dir/b.asm", "a.asm"];
+
+        let batch = match Config::parse(args).unwrap() {
+            Invocation::Assemble(batch) => batch,
+            _ => panic!("expected an assembly"),
+        };
+
+        assert_eq!(batch.jobs, 3);
+        assert_eq!(
+            batch.configs,
+            vec![
+                Config { listing: Some(PathBuf::from("a.lst")), ..Config::for_input("a.asm") },
+                Config {
+                    listing: Some(PathBuf::from("dir/b.lst")),
+                    ..Config::for_input("dir/b.asm")
+                },
+            ],
+        );
+    }
+
+    #[test]
+    fn check_same_input_by_other_paths() {
+        let dir = tempfile::tempdir().unwrap();
+        let infile = dir.path().join("a.asm");
+
+        fs::write(&infile, "@0\n").unwrap();
+
+        let mut args = vec![
+            OsString::from("path"),
+            infile.clone().into_os_string(),
+            dir.path().join(".").join("a.asm").into_os_string(),
+        ];
+
+        #[cfg(unix)]
+        {
+            let link = dir.path().join("link.asm");
+
+            std::os::unix::fs::symlink(&infile, &link).unwrap();
+            args.push(link.into_os_string());
+        }
+
+        match Config::parse(args).unwrap() {
+            Invocation::Assemble(batch) => {
+                assert_eq!(batch.configs, vec![Config::for_input(&infile)]);
+            },
+            _ => panic!("expected an assembly"),
+        }
+    }
+
+    #[test]
+    #[should_panic(expected = "output paths cannot be given when assembling more than one file")]
+    fn check_multiple_inputs_with_output() {
+        Config::new(vec!["path", "-o", "out.hack", "a.asm", "b.asm"]).unwrap();
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid value '0' for option '--jobs'")]
+    fn check_invalid_jobs() {
+        Config::new(vec!["path", "--jobs=0", "a.asm"]).unwrap();
+    }
+
+    #[test]
+    fn check_check_flag() {
+        let config = Config::new(vec!["path", "--check", "test_input_file.asm"]).unwrap();
+
+        assert!(config.check);
+    }
+
+    #[test]
+    #[should_panic(expected = "output files cannot be requested with '--check'")]
+    fn check_check_flag_with_output() {
+        Config::new(vec!["path", "--check", "test_input_file.asm", "out.hack"]).unwrap();
+    }
+
+    #[test]
+    fn check_lint_levels() {
+        let args = vec![
+            "path", "--deny", "unused-label", "--allow=unreachable-code", "test_input_file.asm",
+        ];
+
+        let config = Config::new(args).unwrap();
+
+        assert_eq!(
+            config.lints,
+            vec![
+                (String::from("unused-label"), LintLevel::Deny),
+                (String::from("unreachable-code"), LintLevel::Allow),
+            ],
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn check_non_unicode_paths() {
+        use std::os::unix::ffi::OsStringExt;
+
+        let infile = OsString::from_vec(b"caf\xe9.asm".to_vec());
+        let outfile = OsString::from_vec(b"out/caf\xe9.hack".to_vec());
+
+        let args = vec![
+            OsString::from("ignore/the/path"),
+            infile.clone(),
+            OsString::from("-o"),
+            outfile.clone(),
+        ];
+
+        let config = Config::new(args).unwrap();
+
+        assert_eq!(config.infile, PathBuf::from(infile));
+        assert_eq!(config.outfile, PathBuf::from(outfile));
     }
 }
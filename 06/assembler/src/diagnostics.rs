@@ -0,0 +1,466 @@
+use std::fmt::Write as FmtWrite;
+use std::fs;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use crate::error::{Error, ErrorKind, Span};
+
+/// The formats in which diagnostics can be reported.
+///
+/// Human-readable text is written to stderr, whilst the machine-readable formats are written to
+/// stdout so that they can be captured separately by CI tooling.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Format {
+    /// Human-readable text.
+    Human,
+    /// One JSON object per diagnostic, each on its own line.
+    Json,
+    /// A single SARIF 2.1.0 log containing every diagnostic.
+    Sarif,
+}
+
+impl FromStr for Format {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Format, Error> {
+        match s {
+            "human" => Ok(Format::Human),
+            "json" => Ok(Format::Json),
+            "sarif" => Ok(Format::Sarif),
+            _ => Err(Error::new(ErrorKind::InvalidDiagnosticsFormat)),
+        }
+    }
+}
+
+/// The severity of a diagnostic.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Severity {
+    Error,
+    Warning,
+}
+
+impl Severity {
+    fn as_str(&self) -> &'static str {
+        match *self {
+            Severity::Error => "error",
+            Severity::Warning => "warning",
+        }
+    }
+}
+
+/// A single structured diagnostic, carrying everything needed to annotate the source location
+/// it relates to.
+///
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diagnostic {
+    pub severity: Severity,
+    pub code: &'static str,
+    pub message: String,
+    pub help: Option<String>,
+    pub file: Option<PathBuf>,
+    pub span: Option<Span>,
+}
+
+impl From<&Error> for Diagnostic {
+    fn from(error: &Error) -> Diagnostic {
+        Diagnostic {
+            severity: Severity::Error,
+            code: error.code(),
+            message: error.message(),
+            help: error.help().map(String::from),
+            file: error.file().map(|f| f.to_path_buf()),
+            span: error.span(),
+        }
+    }
+}
+
+/// Writes the given diagnostics to the writer in the requested format.
+///
+/// A SARIF log is always written, even when there are no diagnostics, so that CI tooling can rely
+/// on its presence.  The other formats write nothing when there are no diagnostics.
+///
+/// Returns Ok(()) if execution completes without error.
+///
+pub fn emit<W>(writer: &mut W, format: Format, diagnostics: &[Diagnostic]) -> io::Result<()>
+    where W: Write
+{
+    match format {
+        Format::Human => return emit_human(writer, diagnostics, false),
+        Format::Json => {
+            for diagnostic in diagnostics {
+                writeln!(writer, "{}", json_line(diagnostic))?;
+            }
+        },
+        Format::Sarif => writeln!(writer, "{}", sarif(diagnostics))?,
+    }
+
+    writer.flush()
+}
+
+/// Writes the given diagnostics to the writer as human-readable text, in the style of rustc.
+///
+/// Where the source file can be read, the offending line is shown with the span underlined by
+/// carets, followed by any suggested fix.  ANSI colour codes are included if 'color' is true.
+///
+/// Returns Ok(()) if execution completes without error.
+///
+pub fn emit_human<W>(writer: &mut W, diagnostics: &[Diagnostic], color: bool) -> io::Result<()>
+    where W: Write
+{
+    for diagnostic in diagnostics {
+        write!(writer, "{}", human(diagnostic, color))?;
+    }
+
+    writer.flush()
+}
+
+const RESET: &str = "\x1b[0m";
+const BOLD: &str = "\x1b[1m";
+const BOLD_RED: &str = "\x1b[1;31m";
+const BOLD_YELLOW: &str = "\x1b[1;33m";
+const BOLD_BLUE: &str = "\x1b[1;34m";
+const BOLD_CYAN: &str = "\x1b[1;36m";
+
+/// Formats a diagnostic as human-readable text, e.g.
+///
+/// '''
+/// error[E0205]: comp 'D+Q' is not valid
+///  --> Test.asm:3:5
+///   |
+/// 3 |   D=D+Q
+///   |     ^^^ did you mean 'D+A'?
+/// '''
+///
+fn human(diagnostic: &Diagnostic, color: bool) -> String {
+    let paint = |style: &'static str| if color { style } else { "" };
+    let reset = paint(RESET);
+
+    let severity_style = match diagnostic.severity {
+        Severity::Error => paint(BOLD_RED),
+        Severity::Warning => paint(BOLD_YELLOW),
+    };
+    let gutter_style = paint(BOLD_BLUE);
+
+    let mut text = format!(
+        "{}{}[{}]{}{}: {}{}\n",
+        severity_style,
+        diagnostic.severity.as_str(),
+        diagnostic.code,
+        reset,
+        paint(BOLD),
+        diagnostic.message,
+        reset,
+    );
+
+    let source_line = match (&diagnostic.file, diagnostic.span) {
+        (Some(file), Some(span)) => read_line(file, span.line),
+        _ => None,
+    };
+
+    let gutter_width = diagnostic.span.map_or(0, |span| span.line.to_string().len());
+    let indent = " ".repeat(gutter_width);
+
+    if let Some(ref file) = diagnostic.file {
+        write!(text, "{}{}-->{} {}", indent, gutter_style, reset, file.display()).unwrap();
+
+        if let Some(span) = diagnostic.span {
+            write!(text, ":{}:{}", span.line, span.column).unwrap();
+        }
+
+        text.push('\n');
+    }
+
+    match (source_line, diagnostic.span) {
+        (Some(line), Some(span)) => {
+            // Expand tabs so that the carets line up with the source whatever the tab width.
+            let prefix: String = line.chars().take(span.column - 1).collect();
+            let line = line.replace('\t', "    ");
+            let offset = prefix.replace('\t', "    ").chars().count();
+
+            writeln!(text, "{} {}|{}", indent, gutter_style, reset).unwrap();
+            writeln!(text, "{}{} |{} {}", gutter_style, span.line, reset, line).unwrap();
+            write!(
+                text,
+                "{} {}|{} {}{}{}{}",
+                indent,
+                gutter_style,
+                reset,
+                " ".repeat(offset),
+                severity_style,
+                "^".repeat(std::cmp::max(span.length, 1)),
+                reset,
+            ).unwrap();
+
+            if let Some(ref help) = diagnostic.help {
+                write!(text, " {}{}{}", severity_style, help, reset).unwrap();
+            }
+
+            text.push('\n');
+        },
+        _ => {
+            if let Some(ref help) = diagnostic.help {
+                writeln!(text, "{} {}={} {}help{}: {}",
+                    indent, gutter_style, reset, paint(BOLD_CYAN), reset, help).unwrap();
+            }
+        },
+    }
+
+    text
+}
+
+/// Returns the given line, counting from 1, of a source file, without its line ending.
+///
+/// Returns None if the file cannot be read or does not contain the line.
+///
+fn read_line(file: &Path, line_number: usize) -> Option<String> {
+    let contents = fs::read_to_string(file).ok()?;
+
+    contents
+        .lines()
+        .nth(line_number.checked_sub(1)?)
+        .map(|line| String::from(line.trim_end_matches('\r')))
+}
+
+/// Formats a diagnostic as a single-line JSON object.
+///
+/// Span columns are numbered from 1 and the end column is exclusive.
+///
+fn json_line(diagnostic: &Diagnostic) -> String {
+    let mut json = String::new();
+
+    write!(
+        json,
+        "{{\"severity\":{},\"code\":{},\"message\":{}",
+        json_string(diagnostic.severity.as_str()),
+        json_string(diagnostic.code),
+        json_string(&diagnostic.message),
+    ).unwrap();
+
+    json.push_str(",\"help\":");
+
+    match diagnostic.help {
+        Some(ref help) => json.push_str(&json_string(help)),
+        None => json.push_str("null"),
+    }
+
+    json.push_str(",\"file\":");
+
+    match diagnostic.file {
+        Some(ref file) => json.push_str(&json_string(&file.to_string_lossy())),
+        None => json.push_str("null"),
+    }
+
+    json.push_str(",\"span\":");
+
+    match diagnostic.span {
+        Some(span) => write!(
+            json,
+            "{{\"line\":{},\"column\":{},\"end_column\":{}}}",
+            span.line,
+            span.column,
+            span.column + span.length,
+        ).unwrap(),
+        None => json.push_str("null"),
+    }
+
+    json.push('}');
+
+    json
+}
+
+/// Formats the diagnostics as a SARIF 2.1.0 log with a single run.
+///
+/// A rule is listed for each distinct diagnostic code present.
+///
+fn sarif(diagnostics: &[Diagnostic]) -> String {
+    let mut rules: Vec<&Diagnostic> = Vec::new();
+
+    for diagnostic in diagnostics {
+        if !rules.iter().any(|rule| rule.code == diagnostic.code) {
+            rules.push(diagnostic);
+        }
+    }
+
+    let rules: Vec<String> = rules.iter()
+        .map(|rule| format!("{{\"id\":{}}}", json_string(rule.code)))
+        .collect();
+
+    let results: Vec<String> = diagnostics.iter().map(sarif_result).collect();
+
+    format!(
+        "{{\"$schema\":\"https://json.schemastore.org/sarif-2.1.0.json\",\"version\":\"2.1.0\",\
+        \"runs\":[{{\"tool\":{{\"driver\":{{\"name\":{},\"version\":{},\"rules\":[{}]}}}},\
+        \"results\":[{}]}}]}}",
+        json_string(env!("CARGO_PKG_NAME")),
+        json_string(env!("CARGO_PKG_VERSION")),
+        rules.join(","),
+        results.join(","),
+    )
+}
+
+/// Formats a single diagnostic as a SARIF result object.
+///
+/// SARIF has no separate place for a suggested fix in plain text, so any help is appended to the
+/// message.
+///
+fn sarif_result(diagnostic: &Diagnostic) -> String {
+    let message = match diagnostic.help {
+        Some(ref help) => format!("{}; {}", diagnostic.message, help),
+        None => diagnostic.message.clone(),
+    };
+
+    let mut result = format!(
+        "{{\"ruleId\":{},\"level\":{},\"message\":{{\"text\":{}}}",
+        json_string(diagnostic.code),
+        json_string(diagnostic.severity.as_str()),
+        json_string(&message),
+    );
+
+    if let Some(ref file) = diagnostic.file {
+        write!(
+            result,
+            ",\"locations\":[{{\"physicalLocation\":{{\"artifactLocation\":{{\"uri\":{}}}",
+            json_string(&file.to_string_lossy().replace('\\', "/")),
+        ).unwrap();
+
+        if let Some(span) = diagnostic.span {
+            write!(
+                result,
+                ",\"region\":{{\"startLine\":{},\"startColumn\":{},\"endColumn\":{}}}",
+                span.line,
+                span.column,
+                span.column + span.length,
+            ).unwrap();
+        }
+
+        result.push_str("}}]");
+    }
+
+    result.push('}');
+
+    result
+}
+
+/// Returns the given text as a quoted and escaped JSON string.
+///
+fn json_string(text: &str) -> String {
+    let mut json = String::with_capacity(text.len() + 2);
+
+    json.push('"');
+
+    for c in text.chars() {
+        match c {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\r' => json.push_str("\\r"),
+            '\t' => json.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(json, "\\u{:04x}", c as u32).unwrap(),
+            c => json.push(c),
+        }
+    }
+
+    json.push('"');
+
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn test_diagnostic() -> Diagnostic {
+        let error = Error::new(ErrorKind::InvalidSyntax)
+            .with_span(Some(Span { line: 3, column: 5, length: 4 }))
+            .with_file(Path::new("dir/Test.asm"));
+
+        Diagnostic::from(&error)
+    }
+
+    #[test]
+    fn parse_format() {
+        assert_eq!("human".parse::<Format>().unwrap(), Format::Human);
+        assert_eq!("json".parse::<Format>().unwrap(), Format::Json);
+        assert_eq!("sarif".parse::<Format>().unwrap(), Format::Sarif);
+        assert!("xml".parse::<Format>().is_err());
+    }
+
+    #[test]
+    fn escape_json_string() {
+        assert_eq!(json_string("a \"b\" \\ c\n\u{1}"), "\"a \\\"b\\\" \\\\ c\\n\\u0001\"");
+    }
+
+    #[test]
+    fn check_json_line() {
+        assert_eq!(
+            json_line(&test_diagnostic()),
+            "{\"severity\":\"error\",\"code\":\"E0201\",\"message\":\"invalid syntax\",\
+            \"help\":null,\"file\":\"dir/Test.asm\",\"span\":{\"line\":3,\"column\":5,\"end_column\":9}}",
+        );
+
+        let diagnostic = Diagnostic::from(&Error::new(ErrorKind::RAMFull));
+
+        assert_eq!(
+            json_line(&diagnostic),
+            "{\"severity\":\"error\",\"code\":\"E0302\",\
+            \"message\":\"there are no more free RAM addresses\",\"help\":null,\"file\":null,\
+            \"span\":null}",
+        );
+    }
+
+    #[test]
+    fn check_sarif() {
+        let diagnostics = vec![test_diagnostic(), test_diagnostic()];
+
+        let log = sarif(&diagnostics);
+
+        assert!(log.starts_with("{\"$schema\":\"https://json.schemastore.org/sarif-2.1.0.json\""));
+        assert_eq!(log.matches("{\"id\":\"E0201\"}").count(), 1);
+        assert_eq!(log.matches("\"ruleId\":\"E0201\"").count(), 2);
+        assert!(log.contains("\"artifactLocation\":{\"uri\":\"dir/Test.asm\"}"));
+        assert!(log.contains("\"region\":{\"startLine\":3,\"startColumn\":5,\"endColumn\":9}"));
+
+        assert!(sarif(&[]).contains("\"results\":[]"));
+    }
+
+    #[test]
+    fn check_human() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+
+        file.write_all(b"@2\nD=A\n\tD=D+Q // Bad comp.\n").unwrap();
+
+        let error = crate::code_translator::comp("D+Q").unwrap_err()
+            .with_span(Some(Span { line: 3, column: 4, length: 3 }))
+            .with_file(file.path());
+
+        let text = human(&Diagnostic::from(&error), false);
+
+        let expected = format!("\
+            error[E0205]: comp 'D+Q' is not valid\n \
+            --> {}:3:4\n  \
+            |\n\
+            3 |     D=D+Q // Bad comp.\n  \
+            |       ^^^ did you mean 'D+A'?\n",
+            file.path().display(),
+        );
+
+        assert_eq!(text, expected);
+    }
+
+    #[test]
+    fn check_human_without_source() {
+        let error = Error::new(ErrorKind::RAMFull);
+
+        assert_eq!(
+            human(&Diagnostic::from(&error), false),
+            "error[E0302]: there are no more free RAM addresses\n",
+        );
+
+        let colored = human(&Diagnostic::from(&error), true);
+
+        assert!(colored.starts_with("\x1b[1;31merror[E0302]\x1b[0m"));
+    }
+}
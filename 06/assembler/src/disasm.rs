@@ -0,0 +1,251 @@
+use std::fmt::Write;
+use crate::code_translator::{self, Hack, InstructionSet, Word};
+use crate::error::{Error, ErrorKind, Result};
+
+/// Translates a single machine instruction back into a line of Hack assembly.
+///
+/// A-instructions become '@value'.  C-instructions are written as 'dest=comp;jump', leaving out
+/// a null dest or jump unless both are null.  An error is returned if the comp bits of a
+/// C-instruction do not match a documented mnemonic, or if its unused bits are not set.
+///
+/// # Examples
+///
+/// '''
+/// use assembler::disasm;
+///
+/// assert_eq!(disasm::instruction(0b1110_1010_1000_0111).unwrap(), "0;JMP");
+/// '''
+pub fn instruction(word: u16) -> Result<String> {
+    instruction_with(word, Hack)
+}
+
+/// Translates a single machine instruction of the given instruction set back into a line of
+/// assembly, as 'instruction' does for standard Hack.  The word is one of the instruction set.
+///
+pub fn instruction_with<I: InstructionSet>(word: I::Word, isa: I) -> Result<String> {
+    if !isa.is_c_instruction(word) {
+        return Ok(format!("@{}", word));
+    }
+
+    isa.validate(word)?;
+
+    let field = |mnemonic: Option<&str>| mnemonic.map(String::from).unwrap_or_default();
+
+    Ok(c_command(
+        &field(isa.decode_dest(word)),
+        &field(isa.decode_comp(word)),
+        &field(isa.decode_jump(word)),
+    ))
+}
+
+/// Writes a C-command as 'dest=comp;jump', leaving out a null dest or jump.  If both are null the
+/// dest is kept, as a comp on its own is not a command.
+///
+fn c_command(dest: &str, comp: &str, jump: &str) -> String {
+    let mut text = String::new();
+
+    if dest != "null" || jump == "null" {
+        write!(text, "{}=", dest).unwrap();
+    }
+
+    text.push_str(comp);
+
+    if jump != "null" {
+        write!(text, ";{}", jump).unwrap();
+    }
+
+    text
+}
+
+/// Translates a program of machine instructions back into Hack assembly, one instruction per
+/// line.  Symbols cannot be recovered, so all addresses are written as numbers.
+///
+/// Errors are returned with a message that includes the ROM address of the instruction.
+///
+pub fn disassemble(words: &[u16]) -> Result<String> {
+    disassemble_with(words, Hack)
+}
+
+/// Translates a program of machine instructions of the given instruction set back into assembly,
+/// as 'disassemble' does for standard Hack.
+///
+pub fn disassemble_with<I: InstructionSet>(words: &[I::Word], isa: I) -> Result<String> {
+    let mut text = String::new();
+
+    for (address, &word) in words.iter().enumerate() {
+        let line = instruction_with(word, &isa).map_err(|e| {
+            Error::with_message(ErrorKind::InvalidMachineCode, format!("{} at address {}",
+                e.message(), address))
+        })?;
+
+        writeln!(text, "{}", line).unwrap();
+    }
+
+    Ok(text)
+}
+
+/// The columns that 'listing' writes before each instruction.
+///
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Columns {
+    /// The ROM address of the instruction.
+    pub address: bool,
+    /// The bits of the instruction.
+    pub bits: bool,
+}
+
+/// Translates a program of machine instructions back into Hack assembly, as 'disassemble' does,
+/// for reading machine code that may not have come from this assembler.
+///
+/// Rather than failing, words that no mnemonic describes are written in a form that assembles
+/// back to the same word and flagged with a comment.  An undocumented combination of comp bits
+/// is written as 'alu(...)', and a C-instruction whose two unused bits are not set, which the CPU
+/// ignores, as an '.inst' directive.
+///
+/// The requested columns are written before each instruction, as in the listing of an assembled
+/// program.
+///
+/// # Examples
+///
+/// '''
+/// use assembler::disasm::{self, Columns};
+///
+/// let text = disasm::listing(&[2, 0b1110_1111_1000_0000], Columns { address: true, bits: false });
+///
+/// assert_eq!(text, "00000  @2\n00001  null=alu(a=0,zx,nx,zy,ny,f) // undocumented comp\n");
+/// '''
+pub fn listing(words: &[u16], columns: Columns) -> String {
+    listing_with(words, columns, Hack)
+}
+
+/// Translates a program of machine instructions of the given instruction set back into assembly,
+/// as 'listing' does for standard Hack.
+///
+/// A word that the instruction set rejects is written in the 'alu(...)' form of standard Hack if
+/// that assembles back to the same word, and otherwise as an '.inst' directive flagged with the
+/// reason it was rejected.
+///
+pub fn listing_with<I: InstructionSet>(words: &[I::Word], columns: Columns, isa: I) -> String {
+    let mut text = String::new();
+
+    for (address, &word) in words.iter().enumerate() {
+        if columns.address {
+            write!(text, "{:05}  ", address).unwrap();
+        }
+
+        if columns.bits {
+            write!(text, "{:0width$b}  ", word, width = I::Word::BITS as usize).unwrap();
+        }
+
+        let line = instruction_with(word, &isa).unwrap_or_else(|e| flagged(word, &e, &isa));
+
+        writeln!(text, "{}", line).unwrap();
+    }
+
+    text
+}
+
+/// Writes a word that the instruction set rejected with the given error, in a form that assembles
+/// back to the same word, along with a comment saying why it was flagged.
+///
+fn flagged<I: InstructionSet>(word: I::Word, error: &Error, isa: I) -> String {
+    // The fields of a C-instruction are in the low bits of the word, as in standard Hack.
+    let bits = word.into() as u16;
+    let comp = code_translator::alu_syntax(bits);
+    let dest = code_translator::decode_dest(bits);
+    let jump = code_translator::decode_jump(bits);
+
+    match isa.encode(Some(dest), &comp, Some(jump)) {
+        Ok(encoded) if encoded == word => {
+            format!("{} // undocumented comp", c_command(dest, &comp, jump))
+        },
+        _ => format!(".inst 0b{:0width$b} // {}", word, error.message(),
+            width = I::Word::BITS as usize),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn disassemble_instructions() {
+        let words = [
+            0b0000_0000_0001_0000,  // @16
+            0b1110_1111_1100_1000,  // M=1
+            0b1111_0000_1001_0000,  // D=D+M
+            0b1110_0011_0000_0001,  // D;JGT
+            0b1110_1010_1000_0111,  // 0;JMP
+            0b1110_0011_0011_1111,  // AMD=D;JMP
+        ];
+
+        assert_eq!(
+            disassemble(&words).unwrap(),
+            "@16\nM=1\nD=D+M\nD;JGT\n0;JMP\nAMD=D;JMP\n",
+        );
+    }
+
+    #[test]
+    fn listing_columns() {
+        let words = [2, 0b1110_1100_0001_0000, 0b1111_1111_1001_0001, 0b1000_1100_0001_0000];
+
+        assert_eq!(
+            listing(&words, Columns::default()),
+            "@2\n\
+            D=A\n\
+            D=alu(a=1,zx,nx,zy,ny,f);JGT // undocumented comp\n\
+            .inst 0b1000110000010000 // the unused bits of instruction 1000110000010000 are not \
+                set\n",
+        );
+        assert_eq!(
+            listing(&words[..2], Columns { address: true, bits: true }),
+            "00000  0000000000000010  @2\n00001  1110110000010000  D=A\n",
+        );
+        assert_eq!(
+            listing(&words[1..2], Columns { address: false, bits: true }),
+            "1110110000010000  D=A\n",
+        );
+        assert_eq!(
+            listing_with(&words, Columns::default(), Hack),
+            listing(&words, Columns::default()),
+        );
+    }
+
+    #[test]
+    fn listing_reassembles() {
+        // Every combination of comp bits, with a variety of dest and jump bits, along with
+        // A-instructions and C-instructions whose unused bits are not set.
+        let mut words: Vec<u16> = (0..0b1000_0000)
+            .map(|bits| 0b1110_0000_0000_0000 | (bits << 6) | (bits & 0b11_1111))
+            .collect();
+
+        words.extend_from_slice(&[0, 1, 0x7fff, 0b1000_0000_0000_0111, 0b1100_1010_1000_0111]);
+
+        let source = listing(&words, Columns::default());
+        let assembly = crate::assemble(&source, crate::SymbolTable::new()).unwrap();
+
+        assert_eq!(assembly.words, words);
+    }
+
+    #[test]
+    fn undocumented_comp() {
+        let err = disassemble(&[0, 0b1110_1111_1000_0000]).unwrap_err();
+
+        assert_eq!(
+            err.message(),
+            "the comp bits of instruction 1110111110000000 are not a documented mnemonic at \
+                address 1",
+        );
+    }
+
+    #[test]
+    fn unused_bits_not_set() {
+        let err = disassemble(&[0b1000_1100_0001_0000]).unwrap_err();
+
+        assert_eq!(
+            err.message(),
+            "the unused bits of instruction 1000110000010000 are not set at address 0",
+        );
+        assert!(instruction(0b1010_1100_0001_0000).is_err());
+    }
+}
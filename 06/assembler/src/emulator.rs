@@ -0,0 +1,200 @@
+/// The number of words of data memory addressable by the Hack CPU.
+///
+pub const RAM_SIZE: usize = 32768;
+
+/// The state of the CPU after executing an instruction.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Status {
+    /// The program can continue.
+    Running,
+    /// The program has jumped into a loop that can never exit, the conventional way for a Hack
+    /// program to end, such as '(END) @END 0;JMP'.
+    Looping,
+    /// The program counter has moved past the last instruction of the program.
+    Finished,
+}
+
+/// An emulated Hack CPU, with its instruction memory (ROM) and data memory (RAM).
+///
+/// The registers and RAM are public so that they can be set up before running a program and
+/// examined afterwards.  The screen and keyboard are ordinary RAM.
+///
+/// # Examples
+///
+/// '''
+/// use assembler::emulator::{Cpu, Status};
+///
+/// // @2 D=A @3 D=D+A @0 M=D (END) @6 0;JMP
+/// let mut cpu = Cpu::new(vec![2, 0xec10, 3, 0xe090, 0, 0xe308, 6, 0xea87]);
+///
+/// assert_eq!(cpu.run(1000), (8, Status::Looping));
+/// assert_eq!(cpu.ram[0], 5);
+/// '''
+#[derive(Debug)]
+pub struct Cpu {
+    pub a: u16,
+    pub d: u16,
+    pub pc: u16,
+    pub ram: Vec<u16>,
+    rom: Vec<u16>,
+}
+
+impl Cpu {
+    /// Creates a CPU with the given program loaded into ROM, and the registers and RAM cleared.
+    ///
+    pub fn new(rom: Vec<u16>) -> Cpu {
+        Cpu { a: 0, d: 0, pc: 0, ram: vec![0; RAM_SIZE], rom }
+    }
+
+    /// Executes the instruction at the program counter, returning the status afterwards.
+    ///
+    pub fn step(&mut self) -> Status {
+        let word = match self.rom.get(self.pc as usize) {
+            Some(&word) => word,
+            None => return Status::Finished,
+        };
+
+        if word & 0b1000_0000_0000_0000 == 0 {
+            self.a = word;
+            self.pc += 1;
+            return self.status();
+        }
+
+        let address = self.a as usize % RAM_SIZE;
+        let y = match word & 0b0001_0000_0000_0000 {
+            0 => self.a,
+            _ => self.ram[address],
+        };
+
+        let out = alu(self.d, y, (word >> 6) & 0b11_1111);
+
+        if word & 0b0000_0000_0000_1000 != 0 {
+            self.ram[address] = out;
+        }
+        if word & 0b0000_0000_0001_0000 != 0 {
+            self.d = out;
+        }
+
+        let target = address as u16;
+
+        if word & 0b0000_0000_0010_0000 != 0 {
+            self.a = out;
+        }
+
+        let negative = (out as i16) < 0;
+        let jump = (word & 0b100 != 0 && negative)
+            || (word & 0b010 != 0 && out == 0)
+            || (word & 0b001 != 0 && !negative && out != 0);
+
+        if !jump {
+            self.pc += 1;
+            return self.status();
+        }
+
+        // A jump to itself, or to an A-instruction immediately before it that loads its own
+        // address, can never be left.
+        let looping = target == self.pc
+            || (target + 1 == self.pc && self.rom[target as usize] == target);
+
+        self.pc = target;
+
+        match looping {
+            true => Status::Looping,
+            false => self.status(),
+        }
+    }
+
+    /// Executes instructions until the program loops forever, runs past its end, or the given
+    /// number of instructions have been executed.
+    ///
+    /// Returns the number of instructions executed and the final status.
+    ///
+    pub fn run(&mut self, max_steps: u64) -> (u64, Status) {
+        let mut steps = 0;
+        let mut status = self.status();
+
+        while status == Status::Running && steps < max_steps {
+            status = self.step();
+            steps += 1;
+        }
+
+        (steps, status)
+    }
+
+    fn status(&self) -> Status {
+        match (self.pc as usize) < self.rom.len() {
+            true => Status::Running,
+            false => Status::Finished,
+        }
+    }
+}
+
+/// Computes the output of the Hack ALU for the inputs x and y, given the six control bits
+/// 'zx nx zy ny f no' of a C-instruction's comp field, with 'zx' as the most significant.
+///
+pub fn alu(x: u16, y: u16, control: u16) -> u16 {
+    let bit = |n: u16| control & (1 << n) != 0;
+
+    let x = if bit(5) { 0 } else { x };
+    let x = if bit(4) { !x } else { x };
+    let y = if bit(3) { 0 } else { y };
+    let y = if bit(2) { !y } else { y };
+    let out = if bit(1) { x.wrapping_add(y) } else { x & y };
+
+    if bit(0) { !out } else { out }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn check_alu() {
+        let (x, y) = (7, 3);
+
+        assert_eq!(alu(x, y, 0b101010), 0);
+        assert_eq!(alu(x, y, 0b111111), 1);
+        assert_eq!(alu(x, y, 0b111010), 0xffff);
+        assert_eq!(alu(x, y, 0b001100), 7);
+        assert_eq!(alu(x, y, 0b001111), (-7i16) as u16);
+        assert_eq!(alu(x, y, 0b000010), 10);
+        assert_eq!(alu(x, y, 0b010011), 4);
+        assert_eq!(alu(x, y, 0b000111), (-4i16) as u16);
+        assert_eq!(alu(x, y, 0b000000), 3);
+        assert_eq!(alu(x, y, 0b010101), 7);
+        assert_eq!(alu(x, y, 0b110111), 4);
+    }
+
+    #[test]
+    fn run_max() {
+        // Max.asm: RAM[2] = max(RAM[0], RAM[1]).
+        let rom = vec![
+            0, 0xfc10, 1, 0xf4d0, 10, 0xe301, 1, 0xfc10, 12, 0xea87, 0, 0xfc10, 2, 0xe308, 14,
+            0xea87,
+        ];
+
+        for (r0, r1) in [(3, 9), (12, 5)] {
+            let mut cpu = Cpu::new(rom.clone());
+
+            cpu.ram[0] = r0;
+            cpu.ram[1] = r1;
+
+            assert_eq!(cpu.run(100).1, Status::Looping);
+            assert_eq!(cpu.ram[2], std::cmp::max(r0, r1));
+        }
+    }
+
+    #[test]
+    fn run_past_end_and_step_limit() {
+        let mut cpu = Cpu::new(vec![5, 0xec10]);  // @5 D=A
+
+        assert_eq!(cpu.run(100), (2, Status::Finished));
+        assert_eq!(cpu.d, 5);
+
+        let mut cpu = Cpu::new(vec![0, 0xfdc8, 0, 0xea87]);  // @0 M=M+1 @0 0;JMP
+
+        assert_eq!(cpu.run(10), (10, Status::Running));
+        assert_eq!(cpu.ram[0], 3);
+    }
+}
@@ -2,6 +2,7 @@ use std::io;
 use std::num::ParseIntError;
 use std::fmt;
 use std::error;
+use std::path::{Path, PathBuf};
 use std::result;
 
 /// A specialised 'Result' type for assembler operations.
@@ -16,9 +17,15 @@ pub type Result<T> = result::Result<T, Error>;
 
 /// The error type for assembler operations.
 ///
+/// Alongside the underlying cause, an error may record the source file and the span within it
+/// that the error relates to.  These are used when reporting diagnostics.
+///
 #[derive(Debug)]
 pub struct Error {
     repr: Repr,
+    file: Option<PathBuf>,
+    span: Option<Span>,
+    help: Option<String>,
 }
 
 impl Error {
@@ -28,7 +35,94 @@ impl Error {
     /// std::io library.
     ///
     pub fn new(error_kind: ErrorKind) -> Error {
-        Error { repr: Repr::Other(error_kind.as_str()) }
+        Error::from_repr(Repr::Other(error_kind, String::from(error_kind.as_str())))
+    }
+
+    /// Creates a new assembler error from a known kind of error, with a message that describes
+    /// the specific problem in place of the general description of the kind.
+    ///
+    pub fn with_message(error_kind: ErrorKind, message: String) -> Error {
+        Error::from_repr(Repr::Other(error_kind, message))
+    }
+
+    fn from_repr(repr: Repr) -> Error {
+        Error { repr, file: None, span: None, help: None }
+    }
+
+    /// Attaches a hint on how the error might be fixed, such as a suggested correction.
+    ///
+    pub fn with_help(mut self, help: String) -> Error {
+        self.help = Some(help);
+        self
+    }
+
+    /// Attaches the path of the source file the error relates to, unless one has already been
+    /// attached.
+    ///
+    pub fn with_file(mut self, file: &Path) -> Error {
+        if self.file.is_none() {
+            self.file = Some(file.to_path_buf());
+        }
+        self
+    }
+
+    /// Attaches the span within the source file that the error relates to.
+    ///
+    /// Spans are attached as the error propagates outwards, so an existing span is kept as it
+    /// will be the more specific of the two.
+    ///
+    pub fn with_span(mut self, span: Option<Span>) -> Error {
+        if self.span.is_none() {
+            self.span = span;
+        }
+        self
+    }
+
+    /// Returns the kind of error, or None if the error originated outside of the assembler.
+    ///
+    pub fn kind(&self) -> Option<ErrorKind> {
+        match self.repr {
+            Repr::Other(kind, _) => Some(kind),
+            _ => None,
+        }
+    }
+
+    /// Returns a stable code identifying the kind of error, for use in diagnostics.
+    ///
+    pub fn code(&self) -> &'static str {
+        match self.repr {
+            Repr::IO(_) => "E0001",
+            Repr::ParseInt(_) => "E0002",
+            Repr::Other(kind, _) => kind.code(),
+        }
+    }
+
+    /// Returns the error message, without any location information.
+    ///
+    pub fn message(&self) -> String {
+        match self.repr {
+            Repr::IO(ref e) => e.to_string(),
+            Repr::ParseInt(ref e) => e.to_string(),
+            Repr::Other(_, ref message) => message.clone(),
+        }
+    }
+
+    /// Returns the path of the source file the error relates to, if known.
+    ///
+    pub fn file(&self) -> Option<&Path> {
+        self.file.as_deref()
+    }
+
+    /// Returns the span within the source file that the error relates to, if known.
+    ///
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    /// Returns the hint on how the error might be fixed, if there is one.
+    ///
+    pub fn help(&self) -> Option<&str> {
+        self.help.as_deref()
     }
 }
 
@@ -36,11 +130,33 @@ impl Error {
 enum Repr {
     IO(io::Error),
     ParseInt(ParseIntError),
-    Other(&'static str),
+    Other(ErrorKind, String),
+}
+
+/// A region of a single line of source text.
+///
+/// Lines and columns are numbered from 1, and columns count characters rather than bytes.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Span {
+    pub line: usize,
+    pub column: usize,
+    pub length: usize,
+}
+
+impl Span {
+    /// Returns a new span covering the given part of this one.  The offset is counted in
+    /// characters from the start of this span.
+    ///
+    pub fn sub_span(&self, offset: usize, length: usize) -> Span {
+        Span { line: self.line, column: self.column + offset, length }
+    }
 }
 
 /// General categories of assembler error.
 ///
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum ErrorKind {
     /// The parser has advanced through all lines of the input BufReader.
     EndOfFile,
@@ -52,30 +168,147 @@ pub enum ErrorKind {
     InvalidOutFileExt,
     /// A syntax error in the Hack assembly instruction has been identified.
     InvalidSyntax,
-    /// An insufficient number of arguments were provided when generating a Config instance,
+    /// The 'dest' mnemonic of a C-command is not recognised.
+    InvalidDest,
+    /// The 'comp' mnemonic of a C-command is not recognised.
+    InvalidComp,
+    /// The 'jump' mnemonic of a C-command is not recognised.
+    InvalidJump,
+    /// An input filename was not provided when generating a Config instance.
     MissingArguments,
-    /// An output filename was not provided when generating a Config instance.
-    MissingOutputFilename,
+    /// An unrecognised diagnostics format was requested.
+    InvalidDiagnosticsFormat,
+    /// A file of machine code could not be read as Hack machine instructions.
+    InvalidMachineCode,
+    /// A relocatable object file is malformed.
+    InvalidObjectFile,
+    /// An unrecognised command line option was provided when generating a Config instance.
+    InvalidOption,
+    /// An unrecognised output format was requested.
+    InvalidOutputFormat,
+    /// A command line option that requires a value was provided without one.
+    MissingOptionValue,
+    /// More positional arguments were provided than a Config instance accepts.
+    UnexpectedArgument,
     /// An attempt to add a new variable to the SymbolTable has failed because there are no more
     /// available RAM addresses.
     RAMFull,
     /// An attempt to add a new variable or label to the SymbolTable has failed because it is
     /// already present.
     SymbolExists,
+    /// A symbol used as a jump target is not defined by any of the modules being linked.
+    UndefinedSymbol,
+    /// The modules being linked hold more instructions than fit in ROM.
+    ROMFull,
+    /// The project configuration file could not be parsed, or contains an invalid setting.
+    InvalidProjectFile,
+    /// The programs requested by a build script cannot be assembled into a Rust module.
+    InvalidBuildConfig,
+    /// A memory map file could not be parsed.
+    InvalidMemoryMap,
+    /// A variable would be allocated at an address that the memory map reserves.
+    ReservedAddress,
+    /// The filesystem could not be monitored for changes to the input files.
+    WatchFailed,
 }
 
+/// Every kind of error, so that a kind can be found from its code.
+///
+const KINDS: [ErrorKind; 25] = [
+    ErrorKind::WatchFailed,
+    ErrorKind::MissingArguments,
+    ErrorKind::InvalidInFileExt,
+    ErrorKind::InvalidOutFileExt,
+    ErrorKind::InvalidOption,
+    ErrorKind::InvalidDiagnosticsFormat,
+    ErrorKind::InvalidOutputFormat,
+    ErrorKind::MissingOptionValue,
+    ErrorKind::UnexpectedArgument,
+    ErrorKind::InvalidProjectFile,
+    ErrorKind::InvalidBuildConfig,
+    ErrorKind::InvalidMemoryMap,
+    ErrorKind::InvalidSyntax,
+    ErrorKind::InvalidCmdType,
+    ErrorKind::EndOfFile,
+    ErrorKind::InvalidDest,
+    ErrorKind::InvalidComp,
+    ErrorKind::InvalidJump,
+    ErrorKind::InvalidMachineCode,
+    ErrorKind::InvalidObjectFile,
+    ErrorKind::SymbolExists,
+    ErrorKind::RAMFull,
+    ErrorKind::UndefinedSymbol,
+    ErrorKind::ROMFull,
+    ErrorKind::ReservedAddress,
+];
+
 impl ErrorKind {
     fn as_str(&self) -> &'static str {
         match *self {
             ErrorKind::EndOfFile => "the end of the file has been reached",
             ErrorKind::InvalidSyntax => "invalid syntax",
+            ErrorKind::InvalidDest => "invalid syntax, unrecognised dest mnemonic",
+            ErrorKind::InvalidComp => "invalid syntax, unrecognised comp mnemonic",
+            ErrorKind::InvalidJump => "invalid syntax, unrecognised jump mnemonic",
             ErrorKind::InvalidCmdType => "this function cannot act on Commands of this type",
-            ErrorKind::MissingArguments => "input and output filenames were not provided",
-            ErrorKind::MissingOutputFilename => "output filename not provided",
+            ErrorKind::MissingArguments => "an input filename was not provided",
             ErrorKind::InvalidInFileExt => "invalid input file extension, only '.asm' accepted",
             ErrorKind::InvalidOutFileExt => "invalid output file extension, only '.hack' accepted",
             ErrorKind::SymbolExists => "this symbol has already been defined",
             ErrorKind::RAMFull => "there are no more free RAM addresses",
+            ErrorKind::UndefinedSymbol => "this symbol is not defined",
+            ErrorKind::ROMFull => "the program does not fit in ROM",
+            ErrorKind::InvalidDiagnosticsFormat =>
+                "invalid diagnostics format, only 'human', 'json' or 'sarif' accepted",
+            ErrorKind::InvalidOption => "unrecognised command line option",
+            ErrorKind::InvalidMachineCode => "invalid machine code",
+            ErrorKind::InvalidObjectFile => "invalid object file",
+            ErrorKind::InvalidOutputFormat =>
+                "invalid output format, only 'hack', 'bin' or 'hex' accepted",
+            ErrorKind::MissingOptionValue => "a value was not provided for an option",
+            ErrorKind::UnexpectedArgument => "unexpected argument",
+            ErrorKind::InvalidProjectFile => "invalid project configuration file",
+            ErrorKind::InvalidBuildConfig => "invalid build script configuration",
+            ErrorKind::InvalidMemoryMap => "invalid memory map",
+            ErrorKind::ReservedAddress => "this address is reserved by the memory map",
+            ErrorKind::WatchFailed => "the input files could not be watched for changes",
+        }
+    }
+
+    /// Returns the kind of error with the given code, as returned by 'Error::code', or None if
+    /// the code is not that of an ErrorKind.
+    ///
+    pub fn from_code(code: &str) -> Option<ErrorKind> {
+        KINDS.iter().copied().find(|kind| kind.code() == code)
+    }
+
+    fn code(&self) -> &'static str {
+        match *self {
+            ErrorKind::WatchFailed => "E0003",
+            ErrorKind::MissingArguments => "E0101",
+            ErrorKind::InvalidInFileExt => "E0103",
+            ErrorKind::InvalidOutFileExt => "E0104",
+            ErrorKind::InvalidOption => "E0105",
+            ErrorKind::InvalidDiagnosticsFormat => "E0106",
+            ErrorKind::InvalidOutputFormat => "E0107",
+            ErrorKind::MissingOptionValue => "E0108",
+            ErrorKind::UnexpectedArgument => "E0109",
+            ErrorKind::InvalidProjectFile => "E0110",
+            ErrorKind::InvalidBuildConfig => "E0111",
+            ErrorKind::InvalidMemoryMap => "E0112",
+            ErrorKind::InvalidSyntax => "E0201",
+            ErrorKind::InvalidCmdType => "E0202",
+            ErrorKind::EndOfFile => "E0203",
+            ErrorKind::InvalidDest => "E0204",
+            ErrorKind::InvalidComp => "E0205",
+            ErrorKind::InvalidJump => "E0206",
+            ErrorKind::InvalidMachineCode => "E0207",
+            ErrorKind::InvalidObjectFile => "E0208",
+            ErrorKind::SymbolExists => "E0301",
+            ErrorKind::RAMFull => "E0302",
+            ErrorKind::UndefinedSymbol => "E0303",
+            ErrorKind::ROMFull => "E0304",
+            ErrorKind::ReservedAddress => "E0305",
         }
     }
 }
@@ -85,8 +318,14 @@ impl fmt::Display for Error {
         match self.repr {
             Repr::IO(ref e) => e.fmt(f),
             Repr::ParseInt(ref e) => e.fmt(f),
-            Repr::Other(ref e) =>
-                write!(f, "Error: {}", e),
+            Repr::Other(_, ref message) => {
+                write!(f, "Error: {}", message)?;
+
+                match self.help {
+                    Some(ref help) => write!(f, "; {}", help),
+                    None => Ok(()),
+                }
+            },
         }
     }
 }
@@ -96,20 +335,20 @@ impl error::Error for Error {
         match self.repr {
             Repr::IO(ref e) => Some(e),
             Repr::ParseInt(ref e) => Some(e),
-            Repr::Other(_) => None,
+            Repr::Other(..) => None,
         }
     }
 }
 
 impl From<io::Error> for Error {
     fn from(err: io::Error) -> Error {
-        Error { repr: Repr::IO(err) }
+        Error::from_repr(Repr::IO(err))
     }
 }
 
 impl From<ParseIntError> for Error {
     fn from(err: ParseIntError) -> Error {
-        Error { repr: Repr::ParseInt(err) }
+        Error::from_repr(Repr::ParseInt(err))
     }
 }
 
@@ -117,12 +356,49 @@ impl From<ParseIntError> for Error {
 mod tests {
     use super::*;
 
+    #[test]
+    fn error_codes() {
+        for kind in KINDS.iter() {
+            assert_eq!(ErrorKind::from_code(kind.code()), Some(*kind));
+            assert_eq!(Error::new(*kind).code(), kind.code());
+        }
+
+        assert_eq!(ErrorKind::from_code("E0001"), None);
+    }
+
     #[test]
     fn check_debug_error() {
         let error = Error::new(ErrorKind::RAMFull);
 
-        let expected = "Error { repr: Other(\"there are no more free RAM addresses\") }";
+        let expected = "Error { repr: Other(RAMFull, \"there are no more free RAM addresses\"), \
+            file: None, span: None, help: None }";
 
         assert_eq!(format!("{:?}", error), expected);
     }
+
+    #[test]
+    fn check_error_location() {
+        let span = Span { line: 3, column: 5, length: 4 };
+
+        let error = Error::new(ErrorKind::InvalidSyntax)
+            .with_span(Some(span.sub_span(2, 1)))
+            .with_span(Some(span))
+            .with_file(Path::new("Test.asm"));
+
+        assert_eq!(error.span(), Some(Span { line: 3, column: 7, length: 1 }));
+        assert_eq!(error.file(), Some(Path::new("Test.asm")));
+        assert_eq!(error.code(), "E0201");
+        assert_eq!(error.message(), "invalid syntax");
+        assert_eq!(error.kind(), Some(ErrorKind::InvalidSyntax));
+    }
+
+    #[test]
+    fn check_display_error() {
+        let error = Error::with_message(ErrorKind::InvalidComp, String::from("comp 'D+Q' is not valid"))
+            .with_help(String::from("did you mean 'D+A'?"));
+
+        assert_eq!(error.message(), "comp 'D+Q' is not valid");
+        assert_eq!(error.help(), Some("did you mean 'D+A'?"));
+        assert_eq!(format!("{}", error), "Error: comp 'D+Q' is not valid; did you mean 'D+A'?");
+    }
 }
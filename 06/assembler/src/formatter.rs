@@ -0,0 +1,112 @@
+/// The indentation given to instructions and to comments within the body of a program.
+///
+const INDENT: &str = "    ";
+
+/// Formats Hack assembly source text in a consistent layout:
+///
+/// - labels start in the first column, and instructions are indented by four spaces;
+/// - comments on their own line stay in the first column if they started there, and are
+///   otherwise indented like instructions;
+/// - comments after an instruction are separated from it by a single space;
+/// - trailing whitespace is removed, runs of blank lines are reduced to one, and the text ends
+///   with a single newline.
+///
+/// Only whitespace outside of commands and comments is changed, so the program assembles to the
+/// same machine code.
+///
+/// # Examples
+///
+/// '''
+/// use assembler::formatter;
+///
+/// assert_eq!(formatter::format_source("(LOOP)\n@LOOP   \n  0;JMP//forever\n"),
+///     "(LOOP)\n    @LOOP\n    0;JMP //forever\n");
+/// '''
+pub fn format_source(text: &str) -> String {
+    let mut output = String::with_capacity(text.len());
+    let mut pending_blank = false;
+
+    for line in text.lines() {
+        let (code, comment) = match line.find("//") {
+            Some(i) => (line[..i].trim(), Some(line[i..].trim_end())),
+            None => (line.trim(), None),
+        };
+
+        if code.is_empty() && comment.is_none() {
+            pending_blank = !output.is_empty();
+            continue;
+        }
+
+        if pending_blank {
+            output.push('\n');
+            pending_blank = false;
+        }
+
+        match (code, comment) {
+            ("", Some(comment)) if line.starts_with("//") => output.push_str(comment),
+            ("", Some(comment)) => {
+                output.push_str(INDENT);
+                output.push_str(comment);
+            },
+            (code, comment) => {
+                if !code.starts_with('(') {
+                    output.push_str(INDENT);
+                }
+                output.push_str(code);
+
+                if let Some(comment) = comment {
+                    output.push(' ');
+                    output.push_str(comment);
+                }
+            },
+        }
+
+        output.push('\n');
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn format_program() {
+        let source = "\n\
+            // Computes R2 = max(R0, R1).\r\n\
+            \n\
+            \n\
+            @R0\n\
+            D=M    // D = R0\n\
+            \t@R1  \n\
+            (OUTPUT_FIRST)\n\
+            \x20 // Output R0.\n\
+            \x20 (END)   \n\
+            @END\n\
+            0;JMP\n\
+            \n";
+
+        assert_eq!(
+            format_source(source),
+            "// Computes R2 = max(R0, R1).\n\
+            \n\
+            \x20   @R0\n\
+            \x20   D=M // D = R0\n\
+            \x20   @R1\n\
+            (OUTPUT_FIRST)\n\
+            \x20   // Output R0.\n\
+            (END)\n\
+            \x20   @END\n\
+            \x20   0;JMP\n",
+        );
+    }
+
+    #[test]
+    fn format_is_idempotent() {
+        let source = include_str!("../testfiles/max/Max.asm");
+        let formatted = format_source(source);
+
+        assert_eq!(format_source(&formatted), formatted);
+    }
+}
@@ -6,13 +6,74 @@
 //!
 //! Some syntax checking of the Hack assembly instructions takes place, but it is not designed to
 //! be exhaustive.  In general the input is assumed to be syntactically correct.
+//!
+//! Errors can be reported as human-readable text, or as structured diagnostics in JSON lines or
+//! SARIF format using the 'diagnostics' module.  The 'stats' module reports how an assembled
+//! program uses the available ROM and RAM.
+//!
+//! Many files can be assembled at once, in parallel, with the 'batch' module, and the 'watch'
+//! module assembles them again whenever they change.  Per-project settings are read from a
+//! 'hackasm.toml' file by the 'project' module, which can also set a directory in which the
+//! 'cache' module keeps the results of assembling each input, to be reused while it is unchanged.
+//!
+//! The crate is split by cargo features so that it can be embedded without the command line
+//! tooling.  Without any features it works over in-memory text, through 'assemble', the 'parser'
+//! and the encoder tables in 'code_translator'.  The 'std-io' feature adds reading source files and
+//! writing output files, including the 'build' module for assembling programs from build scripts,
+//! and 'cli' (enabled by default) adds the 'Config' type, project files, batches, watching and the
+//! binaries.  The 'serde' feature derives serialization for the types that describe a program.
+//!
+//! The predefined symbols and the layout of ROM and RAM are those of the standard Hack platform,
+//! unless a 'memory' map describing another variant of the machine is given.  Likewise the
+//! instructions are those of standard Hack unless another 'code_translator::InstructionSet' is
+//! given to 'runner::assemble_with', the 'parser' or the 'disasm' module.
+//!
+//! Source files can also be assembled separately into relocatable objects with the 'object'
+//! module, and combined into a single program by the 'linker'.  Objects shared by many programs
+//! can be packaged into a static library with the 'archive' module.
+//!
+//! The companion 'assembler-macros' crate provides a 'hack_asm!' macro that assembles a program
+//! at compile time with the in-memory API.
+//!
+//! The 'hack' binary gathers the assembler, the linker and the 'disasm', 'emulator', 'formatter'
+//! and 'lint' modules into a single command line tool, whose subcommands are implemented in 'cli'.
 
+#[cfg(feature = "cli")]
 pub use self::config::Config;
+pub use self::error::{Error, ErrorKind, Result, Span};
+pub use self::runner::assemble;
+#[cfg(feature = "cli")]
 pub use self::runner::run;
+pub use self::symbols::{PredefinedSymbols, SymbolTable};
 
+pub mod archive;
+#[cfg(feature = "cli")]
+pub mod batch;
+#[cfg(feature = "std-io")]
+pub mod build;
+#[cfg(feature = "cli")]
+pub mod cache;
+#[cfg(feature = "cli")]
+pub mod cli;
+pub mod code_translator;
+#[cfg(feature = "cli")]
 pub mod config;
+pub mod diagnostics;
+pub mod disasm;
+pub mod emulator;
+pub mod formatter;
+pub mod linker;
+#[cfg(feature = "cli")]
+pub mod lint;
+pub mod memory;
+pub mod object;
+pub mod output;
+pub mod parser;
+#[cfg(feature = "cli")]
+pub mod project;
 pub mod runner;
-mod code_translator;
+pub mod stats;
+#[cfg(feature = "cli")]
+pub mod watch;
 mod error;
-mod parser;
 mod symbols;
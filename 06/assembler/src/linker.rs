@@ -0,0 +1,796 @@
+use std::collections::{HashMap, HashSet};
+use std::fmt::{self, Write};
+use crate::archive::Archive;
+use crate::error::{Error, ErrorKind, Result};
+use crate::object::Object;
+use crate::symbols::SymbolTable;
+
+/// A program linked from relocatable objects, ready to be loaded into ROM.
+///
+/// The symbol table holds every exported label at its final ROM address, and every variable
+/// allocated whilst linking.  'modules' records where the code of each object was placed.
+///
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Image {
+    pub words: Vec<u16>,
+    pub symbols: SymbolTable,
+    pub modules: Vec<Placement>,
+}
+
+/// The location of one module's code within a linked program, along with its global and private
+/// labels.
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Placement {
+    pub name: String,
+    pub address: u16,
+    pub size: u16,
+    pub labels: Vec<(String, u16)>,
+    pub locals: Vec<(String, u16)>,
+}
+
+/// Links relocatable objects into a single program, starting from the given symbol table, which
+/// should hold the same predefined symbols and constants the objects were assembled with.  Each
+/// object is given with the name of its module, such as the path it was read from, which is used
+/// in messages and the link map.
+///
+/// The code of the modules is placed in ROM one after another, in the order given.  Linking then:
+///
+/// * Defines each label at its address in ROM, by its qualified name, so that private labels of
+///   different modules do not collide.  A label defined by two modules is an error.
+/// * Resolves each external reference to the global label of that name.  Referring to a label
+///   that another module keeps private is an error.  A reference to a symbol that no module
+///   defines is a variable, allocated in RAM by the symbol table as it would be when assembling
+///   a single file, so that every module referring to the same name shares one slot and
+///   different names never do.  Private variables are qualified with the name of their module,
+///   so each module has its own.  A symbol loaded just before a jump must be a label, however,
+///   and is reported as undefined if no module defines it.  So is any symbol that looks like a
+///   label another module forgot to share: a private name that another module declares global,
+///   or a name qualified as 'File.name' where another module defines labels of that file.
+/// * Adds the address each module was placed at to its relocated words.
+///
+/// # Examples
+///
+/// '''
+/// use assembler::{linker, object, SymbolTable};
+///
+/// let main = "@Lib.run\n0;JMP\n";
+/// let lib = ".global Lib.run\n(Lib.run)\n@count\nM=1\n";
+///
+/// let (main, _) = object::assemble(main, "Main", SymbolTable::new()).unwrap();
+/// let (lib, _) = object::assemble(lib, "Lib", SymbolTable::new()).unwrap();
+///
+/// let modules = vec![(String::from("Main"), main), (String::from("Lib"), lib)];
+/// let image = linker::link(&modules, SymbolTable::new()).unwrap();
+///
+/// assert_eq!(image.words, vec![2, 0b1110_1010_1000_0111, 16, 0b1110_1111_1100_1000]);
+/// '''
+pub fn link(modules: &[(String, Object)], mut symbols: SymbolTable) -> Result<Image> {
+    let mut placements = Vec::with_capacity(modules.len());
+    let mut owners: HashMap<&str, (&str, bool)> = HashMap::new();
+    let mut address = 0;
+    let rom_size = symbols.layout().rom_size;
+
+    for (name, object) in modules.iter() {
+        let size = object.words.len();
+
+        if address + size > rom_size {
+            return Err(Error::with_message(
+                ErrorKind::ROMFull,
+                format!("the program does not fit in ROM, as '{}' would end at address {}",
+                    name, address + size),
+            ));
+        }
+
+        let mut labels = Vec::with_capacity(object.exports.len());
+        let mut locals = Vec::with_capacity(object.locals.len());
+
+        let defined = object.exports.iter().map(|label| (label, true))
+            .chain(object.locals.iter().map(|label| (label, false)));
+
+        for ((label, offset), global) in defined {
+            let label_address = address as u16 + offset;
+
+            if let Some((other, _)) = owners.insert(label, (name, global)) {
+                return Err(Error::with_message(
+                    ErrorKind::SymbolExists,
+                    format!("the label '{}' is defined by both '{}' and '{}'", label, other, name),
+                ).with_help(String::from("global labels are shared by every module, so one of \
+                    them must be renamed")));
+            }
+
+            symbols.insert_label_at(label, label_address).map_err(|_| {
+                Error::with_message(
+                    ErrorKind::SymbolExists,
+                    format!("the label '{}' in '{}' is already a predefined symbol or constant",
+                        label, name),
+                )
+            })?;
+
+            match global {
+                true => labels.push((label.clone(), label_address)),
+                false => locals.push((label.clone(), label_address)),
+            }
+        }
+
+        placements.push(Placement {
+            name: name.clone(),
+            address: address as u16,
+            size: size as u16,
+            labels,
+            locals,
+        });
+
+        address += size;
+    }
+
+    // Report private and undefined labels before allocating any variables.
+    for (index, (name, object)) in modules.iter().enumerate() {
+        for (symbol, offset) in object.references.iter() {
+            if let Some(&(owner, false)) = owners.get(symbol.as_str()) {
+                return Err(Error::with_message(
+                    ErrorKind::UndefinedSymbol,
+                    format!("'{}' is used in '{}', but is private to '{}'", symbol, name, owner),
+                ).with_help(format!("declare it with '.global {}' to share it with other modules",
+                    symbol)));
+            }
+
+            if symbols.get_address(symbol).is_some() {
+                continue;
+            }
+
+            let next = object.words.get(usize::from(*offset) + 1).copied();
+            let jump = next.is_some_and(is_jump);
+
+            // A private name may have been meant as a global label of another module.
+            let global = symbol.split_once('.')
+                .map(|(_, unqualified)| unqualified)
+                .filter(|unqualified| matches!(owners.get(unqualified), Some((_, true))));
+
+            // A name qualified as 'File.name' is meant as a label of that file, if another
+            // module defines labels named so and this one does not.
+            let file = symbol.split_once('.')
+                .map(|(file, _)| file)
+                .filter(|file| !defines_labels_of(object, file))
+                .and_then(|file| modules.iter()
+                    .enumerate()
+                    .find(|&(other, (_, object))| other != index && defines_labels_of(object, file))
+                    .map(|(_, (other, _))| (file, other)));
+
+            let help = match (global, file) {
+                (Some(global), _) => format!("to use the global label '{}', declare it with \
+                    '.global {}'", global, global),
+                (None, Some((file, other))) => format!("'{}' defines other labels of '{}', so \
+                    check the name, or declare it with '.global {}' where it is defined",
+                    other, file, symbol),
+                (None, None) if jump => format!("add the module that defines the label '{}'",
+                    symbol),
+                (None, None) => continue,
+            };
+
+            let message = match jump {
+                true => format!("'{}' is used as a jump target in '{}', but no module defines it",
+                    symbol, name),
+                false => format!("'{}' is used in '{}', but no module defines it", symbol, name),
+            };
+
+            return Err(Error::with_message(ErrorKind::UndefinedSymbol, message).with_help(help));
+        }
+    }
+
+    let mut words = Vec::with_capacity(address);
+
+    for ((name, object), placement) in modules.iter().zip(placements.iter()) {
+        let start = words.len();
+
+        words.extend_from_slice(&object.words);
+
+        for offset in object.relocations.iter() {
+            words[start + usize::from(*offset)] += placement.address;
+        }
+
+        for (symbol, offset) in object.references.iter() {
+            let value = match symbols.get_address(symbol) {
+                Some(value) => value,
+                None => {
+                    log::debug!("New variable {:?} referenced by {}.", symbol, name);
+
+                    let value = symbols.insert_variable(symbol)?;
+
+                    symbols.inc_ram_address()?;
+                    value
+                },
+            };
+
+            words[start + usize::from(*offset)] = value;
+        }
+    }
+
+    Ok(Image { words, symbols, modules: placements })
+}
+
+/// Adds to the modules being linked the members of the archives that they need, which are those
+/// exporting a label that is referred to but not yet defined.  Members needed by the members
+/// added are then added in turn, until no more are needed.
+///
+/// Members are appended in the order they are needed, searching the archives in the order
+/// given, and are named after their archive and member name, as 'os.hlib(Math.hobj)'.  A
+/// member that is not needed is left out, so a reference that no member resolves remains for
+/// 'link' to make a variable or report as undefined.
+///
+pub fn add_members(modules: &mut Vec<(String, Object)>, archives: &[(String, Archive)]) {
+    let mut defined: HashSet<String> = HashSet::new();
+    let mut needed: HashSet<String> = HashSet::new();
+
+    for (_, object) in modules.iter() {
+        note_symbols(object, &mut defined, &mut needed);
+    }
+
+    let mut added = HashSet::new();
+
+    loop {
+        let count = added.len();
+
+        for (archive_name, archive) in archives.iter() {
+            for (index, (name, object)) in archive.members.iter().enumerate() {
+                let key = (archive_name, index);
+
+                let wanted = object.exports.iter().any(|(label, _)| needed.contains(label));
+
+                if !wanted || added.contains(&key) {
+                    continue;
+                }
+
+                log::debug!("Adding member {} of {} to the program.", name, archive_name);
+
+                note_symbols(object, &mut defined, &mut needed);
+                added.insert(key);
+                modules.push((format!("{}({})", archive_name, name), object.clone()));
+            }
+        }
+
+        if added.len() == count {
+            break;
+        }
+    }
+}
+
+/// Records the labels an object defines, and the symbols it refers to that are not yet defined.
+///
+fn note_symbols(object: &Object, defined: &mut HashSet<String>, needed: &mut HashSet<String>) {
+    for (label, _) in object.exports.iter() {
+        needed.remove(label);
+        defined.insert(label.clone());
+    }
+
+    for (symbol, _) in object.references.iter() {
+        if !defined.contains(symbol) {
+            needed.insert(symbol.clone());
+        }
+    }
+}
+
+/// A region of code removed by 'strip', which runs from one label, or the start of its module,
+/// up to the next.
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Stripped {
+    pub module: String,
+    pub labels: Vec<String>,
+    pub address: u16,
+    pub size: u16,
+}
+
+/// Removes the code that cannot be reached from the entry point of the program, which is the
+/// start of the first module, and returns the modules that remain along with a report of each
+/// region removed.  The modules are then ready to 'link' as usual.
+///
+/// The code of each module is divided into regions at each label.  A region is reachable if it
+/// is the entry point, if a reachable region refers to one of its labels, or if it follows a
+/// reachable region that can fall through into it, which is any region that does not end with
+/// an unconditional jump.  Every reference to a label counts, not only those followed by a jump,
+/// as the address of a label may also be stored to be jumped to later, such as a return address.
+///
+/// Addresses within the remaining code are moved down to close the gaps, and the labels,
+/// references and relocations of the regions removed are dropped with them.  A module that is
+/// removed entirely remains in the program with no code.
+///
+/// # Examples
+///
+/// '''
+/// use assembler::{linker, object, SymbolTable};
+///
+/// let main = "@END\n0;JMP\n(unused)\n@unused\n0;JMP\n(END)\n@END\n0;JMP\n";
+/// let (main, _) = object::assemble(main, "Main", SymbolTable::new()).unwrap();
+///
+/// let (modules, stripped) = linker::strip(&[(String::from("Main"), main)]);
+/// let image = linker::link(&modules, SymbolTable::new()).unwrap();
+///
+/// assert_eq!(image.words, vec![2, 0b1110_1010_1000_0111, 2, 0b1110_1010_1000_0111]);
+/// assert_eq!(stripped[0].labels, vec![String::from("Main.unused")]);
+/// assert_eq!(stripped[0].size, 2);
+/// '''
+pub fn strip(modules: &[(String, Object)]) -> (Vec<(String, Object)>, Vec<Stripped>) {
+    // The regions of every module in the order they are placed, as module and address range.
+    let mut regions: Vec<(usize, u16, u16)> = Vec::new();
+    let mut firsts = Vec::with_capacity(modules.len());
+    let mut exports = HashMap::new();
+
+    for (index, (_, object)) in modules.iter().enumerate() {
+        let size = object.words.len() as u16;
+        let mut starts: Vec<u16> = object.exports.iter()
+            .chain(object.locals.iter())
+            .map(|&(_, address)| address)
+            .filter(|&address| address < size)
+            .collect();
+
+        starts.push(0);
+        starts.sort_unstable();
+        starts.dedup();
+
+        firsts.push(regions.len());
+
+        if size > 0 {
+            for (i, &start) in starts.iter().enumerate() {
+                regions.push((index, start, starts.get(i + 1).copied().unwrap_or(size)));
+            }
+        }
+
+        for (label, address) in object.exports.iter() {
+            exports.entry(label.as_str()).or_insert((index, *address));
+        }
+    }
+
+    // The address just past the end of a module is the start of whichever region follows it.
+    let region_at = |index: usize, address: u16| {
+        let first = firsts[index];
+        let last = firsts.get(index + 1).copied().unwrap_or(regions.len());
+
+        (first..last)
+            .find(|&region| (regions[region].1..regions[region].2).contains(&address))
+            .or(Some(last))
+            .filter(|&region| region < regions.len())
+    };
+
+    let mut reachable = vec![false; regions.len()];
+    let mut pending: Vec<usize> = (0..regions.len()).take(1).collect();
+
+    while let Some(region) = pending.pop() {
+        if reachable[region] {
+            continue;
+        }
+
+        reachable[region] = true;
+
+        let (index, start, end) = regions[region];
+        let object = &modules[index].1;
+        let within = |offset: u16| (start..end).contains(&offset);
+
+        for &offset in object.relocations.iter().filter(|&&offset| within(offset)) {
+            pending.extend(region_at(index, object.words[usize::from(offset)]));
+        }
+
+        for (symbol, _) in object.references.iter().filter(|(_, offset)| within(*offset)) {
+            if let Some(&(module, address)) = exports.get(symbol.as_str()) {
+                pending.extend(region_at(module, address));
+            }
+        }
+
+        if !is_unconditional_jump(object.words[usize::from(end) - 1]) {
+            pending.extend(Some(region + 1).filter(|&next| next < regions.len()));
+        }
+    }
+
+    let mut remaining = Vec::with_capacity(modules.len());
+    let mut stripped = Vec::new();
+
+    for (index, (name, object)) in modules.iter().enumerate() {
+        let removed: Vec<(u16, u16)> = regions.iter()
+            .zip(reachable.iter())
+            .filter(|&(&(module, _, _), &reachable)| module == index && !reachable)
+            .map(|(&(_, start, end), _)| (start, end))
+            .collect();
+
+        let kept = |address: u16| {
+            !removed.iter().any(|&(start, end)| (start..end).contains(&address))
+        };
+        let moved = |address: u16| {
+            address - removed.iter()
+                .filter(|&&(start, _)| start < address)
+                .map(|&(start, end)| end - start)
+                .sum::<u16>()
+        };
+        let moved_symbols = |symbols: &[(String, u16)]| {
+            symbols.iter()
+                .filter(|&&(_, address)| kept(address))
+                .map(|(symbol, address)| (symbol.clone(), moved(*address)))
+                .collect()
+        };
+
+        let words = object.words.iter()
+            .enumerate()
+            .map(|(offset, &word)| (offset as u16, word))
+            .filter(|&(offset, _)| kept(offset))
+            .map(|(offset, word)| match object.relocations.contains(&offset) {
+                true => moved(word),
+                false => word,
+            })
+            .collect();
+
+        for &(start, end) in removed.iter() {
+            let labels = object.exports.iter()
+                .chain(object.locals.iter())
+                .filter(|&&(_, address)| address == start)
+                .map(|(label, _)| label.clone())
+                .collect();
+
+            log::debug!("Stripping {} words at {} of {}.", end - start, start, name);
+
+            stripped.push(Stripped {
+                module: name.clone(),
+                labels,
+                address: start,
+                size: end - start,
+            });
+        }
+
+        remaining.push((name.clone(), Object {
+            words,
+            exports: moved_symbols(&object.exports),
+            locals: moved_symbols(&object.locals),
+            references: moved_symbols(&object.references),
+            relocations: object.relocations.iter()
+                .filter(|&&offset| kept(offset))
+                .map(|&offset| moved(offset))
+                .collect(),
+        }));
+    }
+
+    (remaining, stripped)
+}
+
+impl fmt::Display for Stripped {
+    /// Describes the region for a report, by its labels, or by its address if it has none.
+    ///
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.labels.is_empty() {
+            true => write!(f, "the code at {} of {}", self.address, self.module)?,
+            false => write!(f, "'{}' in {}", self.labels.join("', '"), self.module)?,
+        }
+
+        write!(f, " ({} words)", self.size)
+    }
+}
+
+impl Image {
+    /// Formats a link map of the program: the address and size of each module, each global and
+    /// private label with its address and the module that defines it, and the address of each
+    /// variable.
+    ///
+    /// # Examples
+    ///
+    /// '''
+    /// module Main.hobj 0 6
+    /// label LOOP 0 Main.hobj
+    /// local Main.end 4 Main.hobj
+    /// module Lib.hobj 6 4
+    /// label Lib.run 6 Lib.hobj
+    /// variable count 16
+    /// '''
+    pub fn map(&self) -> String {
+        let mut map = String::new();
+
+        for module in self.modules.iter() {
+            writeln!(map, "module {} {} {}", module.name, module.address, module.size).unwrap();
+
+            for (label, address) in module.labels.iter() {
+                writeln!(map, "label {} {} {}", label, address, module.name).unwrap();
+            }
+
+            for (label, address) in module.locals.iter() {
+                writeln!(map, "local {} {} {}", label, address, module.name).unwrap();
+            }
+        }
+
+        for (variable, address) in self.symbols.variables() {
+            writeln!(map, "variable {} {}", variable, address).unwrap();
+        }
+
+        map
+    }
+}
+
+/// Returns true if the object defines a label qualified with the given file name, as 'File.name'.
+///
+fn defines_labels_of(object: &Object, file: &str) -> bool {
+    object.exports.iter()
+        .chain(object.locals.iter())
+        .any(|(label, _)| label.split_once('.').is_some_and(|(prefix, _)| prefix == file))
+}
+
+/// Returns true if the word is a C-instruction with a jump.
+///
+fn is_jump(word: u16) -> bool {
+    word & 0b1110_0000_0000_0000 == 0b1110_0000_0000_0000 && word & 0b111 != 0
+}
+
+/// Returns true if the word is a C-instruction that always jumps.
+///
+fn is_unconditional_jump(word: u16) -> bool {
+    is_jump(word) && word & 0b111 == 0b111
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::object;
+    use crate::symbols::ROM_SIZE;
+
+    fn objects(sources: &[(&str, &str)]) -> Vec<(String, Object)> {
+        sources.iter()
+            .map(|(name, source)| {
+                let module = name.trim_end_matches(".hobj");
+                let (object, _) = object::assemble(source, module, SymbolTable::new()).unwrap();
+
+                (String::from(*name), object)
+            })
+            .collect()
+    }
+
+    #[test]
+    fn link_modules() {
+        let modules = objects(&[
+            ("Main.hobj", ".global LOOP count\n(LOOP)\n@count\nM=M+1\n@Lib.reset\n0;JMP\n\
+                @LOOP\n0;JMP\n"),
+            ("Lib.hobj", ".global Lib.reset count\n(Lib.reset)\n@count\nM=0\n@SCREEN\n(end)\n\
+                @end\n0;JMP\n"),
+            ("Other.hobj", ".global count\n@total\nM=0\n@count\n"),
+        ]);
+
+        let image = link(&modules, SymbolTable::new()).unwrap();
+
+        assert_eq!(image.words, vec![
+            16, 0xfdc8, 6, 0xea87, 0, 0xea87,
+            16, 0xea88, 0x4000, 9, 0xea87,
+            17, 0xea88, 16,
+        ]);
+        assert_eq!(image.symbols.get_address("Lib.end"), Some(9));
+        assert_eq!(image.symbols.variables(), vec![("count", 16), ("Other.total", 17)]);
+
+        assert_eq!(image.map(), "\
+            module Main.hobj 0 6\n\
+            label LOOP 0 Main.hobj\n\
+            module Lib.hobj 6 5\n\
+            label Lib.reset 6 Lib.hobj\n\
+            local Lib.end 9 Lib.hobj\n\
+            module Other.hobj 11 3\n\
+            variable count 16\n\
+            variable Other.total 17\n\
+            ");
+    }
+
+    #[test]
+    fn private_symbols() {
+        let source = "(loop)\n@i\n@loop\n";
+        let modules = objects(&[("A.hobj", source), ("B.hobj", source)]);
+
+        let image = link(&modules, SymbolTable::new()).unwrap();
+
+        assert_eq!(image.words, vec![16, 0, 17, 2]);
+        assert_eq!(image.symbols.labels(), vec![("A.loop", 0), ("B.loop", 2)]);
+        assert_eq!(image.symbols.variables(), vec![("A.i", 16), ("B.i", 17)]);
+
+        let modules = objects(&[("Lib.hobj", "(Lib.helper)\n"), ("Main.hobj", "@Lib.helper\n")]);
+
+        let err = link(&modules, SymbolTable::new()).unwrap_err();
+
+        assert_eq!(err.kind(), Some(ErrorKind::UndefinedSymbol));
+        assert_eq!(
+            err.message(),
+            "'Lib.helper' is used in 'Main.hobj', but is private to 'Lib.hobj'",
+        );
+        assert_eq!(
+            err.help(),
+            Some("declare it with '.global Lib.helper' to share it with other modules"),
+        );
+    }
+
+    #[test]
+    fn duplicate_label() {
+        let source = ".global START\n(START)\n@START\n";
+        let modules = objects(&[("A.hobj", source), ("B.hobj", source)]);
+
+        let err = link(&modules, SymbolTable::new()).unwrap_err();
+
+        assert_eq!(err.kind(), Some(ErrorKind::SymbolExists));
+        assert_eq!(err.message(), "the label 'START' is defined by both 'A.hobj' and 'B.hobj'");
+
+        let modules = objects(&[("A.hobj", ".global WIDTH\n(WIDTH)\n")]);
+        let mut symbols = SymbolTable::new();
+
+        symbols.insert_constant("WIDTH", 512).unwrap();
+
+        assert_eq!(
+            link(&modules, symbols).unwrap_err().message(),
+            "the label 'WIDTH' in 'A.hobj' is already a predefined symbol or constant",
+        );
+    }
+
+    #[test]
+    fn undefined_label() {
+        let modules = objects(&[("Main.hobj", "@i\nM=1\n@Sys.init\nD;JGT\n")]);
+
+        let err = link(&modules, SymbolTable::new()).unwrap_err();
+
+        assert_eq!(err.kind(), Some(ErrorKind::UndefinedSymbol));
+        assert_eq!(
+            err.message(),
+            "'Sys.init' is used as a jump target in 'Main.hobj', but no module defines it",
+        );
+
+        let modules = objects(&[
+            ("Main.hobj", "@LOOP\n0;JMP\n"),
+            ("Lib.hobj", ".global LOOP\n(LOOP)\n"),
+        ]);
+
+        let err = link(&modules, SymbolTable::new()).unwrap_err();
+
+        assert_eq!(
+            err.message(),
+            "'Main.LOOP' is used as a jump target in 'Main.hobj', but no module defines it",
+        );
+        assert_eq!(
+            err.help(),
+            Some("to use the global label 'LOOP', declare it with '.global LOOP'"),
+        );
+    }
+
+    #[test]
+    fn undefined_data_label() {
+        let modules = objects(&[
+            ("Main.hobj", "@Lib.tabel
+D=M
+@Main.i
+M=D
+"),
+            ("Lib.hobj", ".global Lib.table
+(Lib.table)
+@1
+"),
+        ]);
+
+        let err = link(&modules, SymbolTable::new()).unwrap_err();
+
+        assert_eq!(err.kind(), Some(ErrorKind::UndefinedSymbol));
+        assert_eq!(err.message(), "'Lib.tabel' is used in 'Main.hobj', but no module defines it");
+        assert_eq!(
+            err.help(),
+            Some("'Lib.hobj' defines other labels of 'Lib', so check the name, or declare it \
+                with '.global Lib.tabel' where it is defined"),
+        );
+
+        let modules = objects(&[
+            ("Main.hobj", "@RETURN
+D=A
+"),
+            ("Lib.hobj", ".global RETURN
+(RETURN)
+"),
+        ]);
+
+        let err = link(&modules, SymbolTable::new()).unwrap_err();
+
+        assert_eq!(err.message(), "'Main.RETURN' is used in 'Main.hobj', but no module defines it");
+        assert_eq!(
+            err.help(),
+            Some("to use the global label 'RETURN', declare it with '.global RETURN'"),
+        );
+
+        // A module's own qualified variables, and those of files with no labels, are variables.
+        let modules = objects(&[
+            ("Lib.hobj", "(Lib.run)
+@Lib.count
+@Other.total
+"),
+            ("Main.hobj", "@Main.i
+"),
+        ]);
+
+        let image = link(&modules, SymbolTable::new()).unwrap();
+
+        assert_eq!(
+            image.symbols.variables(),
+            vec![("Lib.count", 16), ("Other.total", 17), ("Main.i", 18)],
+        );
+    }
+
+    #[test]
+    fn archive_members() {
+        let mut modules = objects(&[("Main.hobj", "@Math.square\n0;JMP\n@count\n")]);
+
+        let archive = Archive::new(objects(&[
+            ("Screen.hobj", "(Screen.clear)\n@SCREEN\n"),
+            ("Square.hobj", ".global Math.square\n(Math.square)\n@Math.multiply\n0;JMP\n"),
+            ("Multiply.hobj", ".global Math.multiply\n(Math.multiply)\n@product\n"),
+        ])).unwrap();
+
+        add_members(&mut modules, &[(String::from("os.hlib"), archive)]);
+
+        let names: Vec<&str> = modules.iter().map(|(name, _)| name.as_str()).collect();
+
+        assert_eq!(names, vec!["Main.hobj", "os.hlib(Square.hobj)", "os.hlib(Multiply.hobj)"]);
+
+        let image = link(&modules, SymbolTable::new()).unwrap();
+
+        assert_eq!(image.words, vec![3, 0xea87, 16, 5, 0xea87, 17]);
+        assert_eq!(
+            image.symbols.variables(),
+            vec![("Main.count", 16), ("Multiply.product", 17)],
+        );
+    }
+
+    #[test]
+    fn program_too_large() {
+        let object = Object { words: vec![0; ROM_SIZE / 2 + 1], ..Object::default() };
+        let modules = vec![(String::from("A"), object.clone()), (String::from("B"), object)];
+
+        let err = link(&modules, SymbolTable::new()).unwrap_err();
+
+        assert_eq!(err.kind(), Some(ErrorKind::ROMFull));
+        assert_eq!(
+            err.message(),
+            "the program does not fit in ROM, as 'B' would end at address 32770",
+        );
+    }
+
+    #[test]
+    fn strip_unreachable_code() {
+        let modules = objects(&[
+            ("Main.hobj", ".global Lib.used\n@RET\nD=A\n@Lib.used\n0;JMP\n(RET)\n@RET\n0;JMP\n\
+                (dead)\n@dead\n0;JMP\n"),
+            ("Lib.hobj", ".global Lib.used Lib.unused\n(Lib.unused)\n@count\nM=0\n(Lib.used)\n\
+                @count\nM=M+1\n@skip\nD;JEQ\n(skip)\n@R15\n0;JMP\n"),
+            ("Unused.hobj", "@SCREEN\nM=0\n"),
+        ]);
+
+        let (modules, stripped) = strip(&modules);
+
+        assert_eq!(stripped, vec![
+            Stripped {
+                module: String::from("Main.hobj"),
+                labels: vec![String::from("Main.dead")],
+                address: 6,
+                size: 2,
+            },
+            Stripped {
+                module: String::from("Lib.hobj"),
+                labels: vec![String::from("Lib.unused")],
+                address: 0,
+                size: 2,
+            },
+            Stripped { module: String::from("Unused.hobj"), labels: vec![], address: 0, size: 2 },
+        ]);
+        assert_eq!(stripped[1].to_string(), "'Lib.unused' in Lib.hobj (2 words)");
+        assert_eq!(stripped[2].to_string(), "the code at 0 of Unused.hobj (2 words)");
+
+        let image = link(&modules, SymbolTable::new()).unwrap();
+
+        assert_eq!(image.words, vec![
+            4, 0xec10, 6, 0xea87, 4, 0xea87,
+            16, 0xfdc8, 10, 0xe302, 15, 0xea87,
+        ]);
+        assert_eq!(image.symbols.get_address("Lib.skip"), Some(10));
+        assert_eq!(image.map(), "\
+            module Main.hobj 0 6\n\
+            local Main.RET 4 Main.hobj\n\
+            module Lib.hobj 6 6\n\
+            label Lib.used 6 Lib.hobj\n\
+            local Lib.skip 10 Lib.hobj\n\
+            module Unused.hobj 12 0\n\
+            variable Lib.count 16\n\
+            ");
+    }
+}
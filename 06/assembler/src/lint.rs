@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use crate::config::Config;
+use crate::diagnostics::{Diagnostic, Severity};
+use crate::error::{Error, ErrorKind, Result, Span};
+use crate::parser::{Command, Parser};
+use crate::project::LintLevel;
+use crate::runner;
+
+/// The lints that can be run over a program, with the code and default level of each.
+///
+pub const LINTS: [(&str, &str, LintLevel); 3] = [
+    ("unused-label", "W0101", LintLevel::Warn),
+    ("unreachable-code", "W0102", LintLevel::Warn),
+    ("single-use-variable", "W0103", LintLevel::Warn),
+];
+
+/// Checks a program for likely mistakes that still assemble.
+///
+/// - 'unused-label': a label that no A-command refers to.
+/// - 'unreachable-code': an instruction straight after an unconditional jump, which can only be
+///   reached by jumping to a label placed before it.
+/// - 'single-use-variable': a variable referred to only once, which is often a misspelling of
+///   another symbol.
+///
+/// The program is assembled first, without writing any output, and an assembly error is
+/// returned as the Err variant.  Otherwise a diagnostic is returned for every problem found by a
+/// lint that is not allowed, as a warning or an error according to the levels in
+/// 'config.lints', which override the defaults in the order given.
+///
+pub fn lint(config: &Config) -> Result<Vec<Diagnostic>> {
+    let levels = levels(config)?;
+
+    runner::run(Config { check: true, ..config.clone() })?;
+
+    let symbols = runner::symbol_table(config)?;
+    let path = config.infile.as_path();
+    let mut parser = Parser::new(path, symbols).map_err(|e| e.with_file(path))?;
+
+    let mut labels = Vec::new();
+    let mut references: HashMap<String, Vec<Span>> = HashMap::new();
+    let mut problems = Vec::new();
+    let mut after_jump = false;
+
+    while parser.advance().map_err(|e| e.with_file(path))? > 0 {
+        let span = match parser.get_command_span() {
+            Some(span) => span,
+            None => continue,
+        };
+
+        match parser.get_command() {
+            Some(Command::LCommand(_)) => {
+                labels.push((parser.symbol()?, span));
+                after_jump = false;
+                continue;
+            },
+            Some(Command::Directive(_)) if parser.instruction_word().is_none() => continue,
+            Some(Command::ACommand(_)) => {
+                let symbol = parser.symbol()?;
+
+                if symbol.parse::<u16>().is_err() {
+                    references.entry(symbol).or_default().push(span.sub_span(1, span.length - 1));
+                }
+            },
+            _ => (),
+        }
+
+        if after_jump {
+            let message = String::from("this instruction is unreachable");
+
+            problems.push(("unreachable-code", message, span));
+        }
+
+        after_jump = matches!(parser.jump(), Ok(Some(ref jump)) if jump == "JMP");
+    }
+
+    for (label, span) in labels.iter() {
+        if !references.contains_key(label) {
+            problems.push(("unused-label", format!("label '{}' is never used", label), *span));
+        }
+    }
+
+    let mut single_uses: Vec<(&String, Span)> = references.iter()
+        .filter(|(symbol, spans)| {
+            spans.len() == 1
+                && parser.get_symbol_address(symbol).is_none()
+                && !labels.iter().any(|(label, _)| label == *symbol)
+        })
+        .map(|(symbol, spans)| (symbol, spans[0]))
+        .collect();
+
+    single_uses.sort_by_key(|&(_, span)| span.line);
+
+    for (symbol, span) in single_uses {
+        let message = format!("variable '{}' is only used once", symbol);
+
+        problems.push(("single-use-variable", message, span));
+    }
+
+    problems.sort_by_key(|&(_, _, span)| (span.line, span.column));
+
+    let mut diagnostics = Vec::new();
+
+    for (name, message, span) in problems {
+        let (code, level) = levels[name];
+
+        let severity = match level {
+            LintLevel::Allow => continue,
+            LintLevel::Warn => Severity::Warning,
+            LintLevel::Deny => Severity::Error,
+        };
+
+        diagnostics.push(Diagnostic {
+            severity,
+            code,
+            message,
+            help: Some(format!("this is reported by the '{}' lint", name)),
+            file: Some(config.infile.clone()),
+            span: Some(span),
+        });
+    }
+
+    Ok(diagnostics)
+}
+
+/// Returns the code and level of every lint, after applying the levels given in the Config.
+///
+fn levels(config: &Config) -> Result<HashMap<&'static str, (&'static str, LintLevel)>> {
+    let mut levels: HashMap<_, _> = LINTS.iter()
+        .map(|&(name, code, level)| (name, (code, level)))
+        .collect();
+
+    for (name, level) in config.lints.iter() {
+        match levels.get_mut(name.as_str()) {
+            Some(entry) => entry.1 = *level,
+            None => {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidOption,
+                    format!("unrecognised lint '{}'", name),
+                ));
+            },
+        }
+    }
+
+    Ok(levels)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    fn lint_source(source: &str, lints: Vec<(String, LintLevel)>) -> Result<Vec<Diagnostic>> {
+        let dir = tempfile::tempdir().unwrap();
+        let infile = dir.path().join("Test.asm");
+
+        fs::write(&infile, source).unwrap();
+
+        lint(&Config { lints, ..Config::for_input(&infile) })
+    }
+
+    #[test]
+    fn find_problems() {
+        let source = "\
+            @count\n\
+            M=0\n\
+            (UNUSED)\n\
+            (LOOP)\n\
+            @count\n\
+            M=M+1\n\
+            @totl\n\
+            @LOOP\n\
+            0;JMP\n\
+            D=0\n\
+            @R0\n\
+        ";
+
+        let diagnostics = lint_source(source, Vec::new()).unwrap();
+
+        let found: Vec<(&str, &str, Severity, usize)> = diagnostics.iter()
+            .map(|d| (d.code, d.message.as_str(), d.severity, d.span.unwrap().line))
+            .collect();
+
+        assert_eq!(
+            found,
+            vec![
+                ("W0101", "label 'UNUSED' is never used", Severity::Warning, 3),
+                ("W0103", "variable 'totl' is only used once", Severity::Warning, 7),
+                ("W0102", "this instruction is unreachable", Severity::Warning, 10),
+            ],
+        );
+        assert_eq!(diagnostics[1].span, Some(Span { line: 7, column: 2, length: 4 }));
+    }
+
+    #[test]
+    fn configure_levels() {
+        let source = "(UNUSED)\n@x\n";
+        let lints = vec![
+            (String::from("unused-label"), LintLevel::Deny),
+            (String::from("single-use-variable"), LintLevel::Allow),
+        ];
+
+        let diagnostics = lint_source(source, lints).unwrap();
+
+        assert_eq!(diagnostics.len(), 1);
+        assert_eq!(diagnostics[0].severity, Severity::Error);
+
+        let lints = vec![(String::from("unused-labels"), LintLevel::Deny)];
+
+        assert_eq!(
+            lint_source(source, lints).unwrap_err().message(),
+            "unrecognised lint 'unused-labels'",
+        );
+    }
+
+    #[test]
+    fn assembly_error() {
+        let err = lint_source("D=D+Q\n", Vec::new()).unwrap_err();
+
+        assert_eq!(err.kind(), Some(ErrorKind::InvalidComp));
+    }
+}
@@ -0,0 +1,331 @@
+use std::collections::HashSet;
+use std::fmt;
+#[cfg(feature = "std-io")]
+use std::fs;
+#[cfg(feature = "std-io")]
+use std::path::Path;
+use std::str::FromStr;
+use crate::error::{Error, ErrorKind, Result, Span};
+use crate::symbols::{PredefinedSymbols, ROM_SIZE, VARIABLE_BASE, VARIABLE_LIMIT};
+
+/// The file extension of memory maps.
+///
+pub const EXTENSION: &str = "hmap";
+
+/// The symbols predefined by the standard Hack platform: the virtual registers, pointers and
+/// memory-mapped I/O.
+///
+pub const HACK_SYMBOLS: [(&str, u16); 23] = [
+    ("SP", 0),
+    ("LCL", 1),
+    ("ARG", 2),
+    ("THIS", 3),
+    ("THAT", 4),
+    ("R0", 0),
+    ("R1", 1),
+    ("R2", 2),
+    ("R3", 3),
+    ("R4", 4),
+    ("R5", 5),
+    ("R6", 6),
+    ("R7", 7),
+    ("R8", 8),
+    ("R9", 9),
+    ("R10", 10),
+    ("R11", 11),
+    ("R12", 12),
+    ("R13", 13),
+    ("R14", 14),
+    ("R15", 15),
+    ("SCREEN", 16384),
+    ("KBD", 24576),
+];
+
+/// The largest value an A-instruction can load, and so the highest address that a symbol can
+/// refer to.
+///
+const ADDRESS_LIMIT: u16 = 0x7fff;
+
+/// The memory layout of a Hack machine: the symbols it predefines, and the layout of its memory
+/// that the assembler and linker allocate within.  The standard Hack platform is the default,
+/// and variants with more RAM or other I/O devices can be described in a file, in the style of
+/// a linker script.
+///
+/// Each line of the file is a record, and '//' starts a comment:
+///
+/// '''
+/// // Hack with a second screen and a larger RAM.
+/// rom 32768
+/// predefined hack
+/// symbol SCREEN2 24577
+/// variables 16 32767
+/// reserve SCREEN 16384 24576
+/// reserve SCREEN2 24577 28672
+/// '''
+///
+/// - 'rom SIZE' sets the number of instruction words in ROM.
+/// - 'predefined hack' adds the symbols of the standard Hack platform.
+/// - 'symbol NAME ADDRESS' adds a predefined symbol.
+/// - 'variables FIRST LAST' sets the range of RAM addresses that variables are allocated from.
+/// - 'reserve NAME FIRST LAST' reserves a range of RAM addresses, such as for a device, so that
+///   allocating a variable within it is an error.
+///
+/// Addresses may be written in decimal or, with a '0x' prefix, in hexadecimal, and ranges
+/// include both of their ends.
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct MemoryMap {
+    pub symbols: Vec<(String, u16)>,
+    pub layout: Layout,
+}
+
+/// The parts of a memory map that allocation must keep within.
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Layout {
+    pub rom_size: usize,
+    pub variable_base: u16,
+    pub variable_limit: u16,
+    pub reserved: Vec<Reserved>,
+}
+
+/// A named range of RAM addresses that variables must not be allocated within.
+///
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Reserved {
+    pub name: String,
+    pub first: u16,
+    pub last: u16,
+}
+
+impl Default for Layout {
+    fn default() -> Layout {
+        Layout {
+            rom_size: ROM_SIZE,
+            variable_base: VARIABLE_BASE,
+            variable_limit: VARIABLE_LIMIT,
+            reserved: Vec::new(),
+        }
+    }
+}
+
+impl Default for MemoryMap {
+    fn default() -> MemoryMap {
+        MemoryMap::with_predefined(PredefinedSymbols::Hack, VARIABLE_BASE)
+    }
+}
+
+impl MemoryMap {
+    /// Returns the memory map of the standard Hack platform, with the given set of predefined
+    /// symbols and allocating variables from the given RAM address.
+    ///
+    pub fn with_predefined(predefined: PredefinedSymbols, variable_base: u16) -> MemoryMap {
+        let symbols = match predefined {
+            PredefinedSymbols::Hack => HACK_SYMBOLS.iter()
+                .map(|&(name, address)| (String::from(name), address))
+                .collect(),
+            PredefinedSymbols::None => Vec::new(),
+        };
+
+        MemoryMap { symbols, layout: Layout { variable_base, ..Layout::default() } }
+    }
+
+    /// Reads a memory map from a file.  Errors are returned with the path of the file.
+    ///
+    #[cfg(feature = "std-io")]
+    pub fn load(path: &Path) -> Result<MemoryMap> {
+        let text = fs::read_to_string(path).map_err(|e| Error::from(e).with_file(path))?;
+
+        text.parse::<MemoryMap>().map_err(|e| e.with_file(path))
+    }
+}
+
+impl Layout {
+    /// Returns the reserved region that holds the address, if any.
+    ///
+    pub fn reserved_at(&self, address: u16) -> Option<&Reserved> {
+        self.reserved.iter().find(|region| (region.first..=region.last).contains(&address))
+    }
+
+    /// Returns the number of RAM addresses that variables can be allocated, which is zero if the
+    /// base address is above the limit.
+    ///
+    pub fn variable_space(&self) -> usize {
+        (usize::from(self.variable_limit) + 1).saturating_sub(usize::from(self.variable_base))
+    }
+}
+
+impl fmt::Display for MemoryMap {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let layout = &self.layout;
+
+        writeln!(f, "rom {}", layout.rom_size)?;
+
+        for (name, address) in self.symbols.iter() {
+            writeln!(f, "symbol {} {}", name, address)?;
+        }
+
+        writeln!(f, "variables {} {}", layout.variable_base, layout.variable_limit)?;
+
+        for region in layout.reserved.iter() {
+            writeln!(f, "reserve {} {} {}", region.name, region.first, region.last)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for MemoryMap {
+    type Err = Error;
+
+    /// Reads a memory map from its text form.  Records that are absent keep their value in the
+    /// standard Hack platform, except that there are no predefined symbols unless they are
+    /// given.  Errors carry the span of the line at fault.
+    ///
+    fn from_str(s: &str) -> Result<MemoryMap> {
+        let mut map = MemoryMap::with_predefined(PredefinedSymbols::None, VARIABLE_BASE);
+        let mut names = HashSet::new();
+
+        for (index, line) in s.lines().enumerate() {
+            let text = line.split("//").next().unwrap_or("");
+            let fields: Vec<&str> = text.split_whitespace().collect();
+            let span = Span { line: index + 1, column: 1, length: line.chars().count() };
+            let error = |message: String| {
+                Error::with_message(ErrorKind::InvalidMemoryMap, message).with_span(Some(span))
+            };
+
+            match fields.as_slice() {
+                [] => (),
+                ["rom", size] => {
+                    map.layout.rom_size = match parse_number(size) {
+                        Some(size) if size > 0 && size <= ROM_SIZE => size,
+                        _ => return Err(error(format!("'{}' is not a ROM size from 1 to {}",
+                            size, ROM_SIZE))),
+                    };
+                },
+                ["predefined", "hack"] => {
+                    for (name, address) in HACK_SYMBOLS.iter() {
+                        if !names.insert(String::from(*name)) {
+                            return Err(error(format!("'{}' is defined more than once", name)));
+                        }
+                        map.symbols.push((String::from(*name), *address));
+                    }
+                },
+                ["symbol", name, address] => {
+                    if !names.insert(String::from(*name)) {
+                        return Err(error(format!("'{}' is defined more than once", name)));
+                    }
+                    map.symbols.push((String::from(*name), parse_address(address).map_err(error)?));
+                },
+                ["variables", first, last] => {
+                    let (first, last) = parse_range(first, last).map_err(error)?;
+
+                    map.layout.variable_base = first;
+                    map.layout.variable_limit = last;
+                },
+                ["reserve", name, first, last] => {
+                    let (first, last) = parse_range(first, last).map_err(error)?;
+
+                    map.layout.reserved.push(Reserved { name: String::from(*name), first, last });
+                },
+                _ => {
+                    return Err(error(format!("unrecognised record '{}'", text.trim()))
+                        .with_help(String::from("expected 'rom', 'predefined', 'symbol', \
+                            'variables' or 'reserve'")));
+                },
+            }
+        }
+
+        Ok(map)
+    }
+}
+
+/// Parses a number written in decimal, or in hexadecimal with a '0x' prefix.
+///
+fn parse_number(number: &str) -> Option<usize> {
+    match number.strip_prefix("0x") {
+        Some(hex) => usize::from_str_radix(hex, 16).ok(),
+        None => number.parse().ok(),
+    }
+}
+
+/// Parses an address that an A-instruction can load.
+///
+fn parse_address(address: &str) -> std::result::Result<u16, String> {
+    match parse_number(address) {
+        Some(value) if value <= usize::from(ADDRESS_LIMIT) => Ok(value as u16),
+        _ => Err(format!("'{}' is not an address from 0 to {}", address, ADDRESS_LIMIT)),
+    }
+}
+
+fn parse_range(first: &str, last: &str) -> std::result::Result<(u16, u16), String> {
+    let (first, last) = (parse_address(first)?, parse_address(last)?);
+
+    match first <= last {
+        true => Ok((first, last)),
+        false => Err(format!("the range {} to {} is empty", first, last)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn standard_memory_map() {
+        let map = MemoryMap::default();
+
+        assert_eq!(map.symbols.len(), 23);
+        assert_eq!(map.layout, Layout::default());
+        assert_eq!(map.layout.variable_space(), 16368);
+        assert!(MemoryMap::with_predefined(PredefinedSymbols::None, 32).symbols.is_empty());
+
+        assert_eq!(map.to_string().parse::<MemoryMap>().unwrap(), map);
+    }
+
+    #[test]
+    fn memory_map_text() {
+        let text = "\
+            // Hack with a second screen.\n\
+            rom 0x4000\n\
+            predefined hack\n\
+            symbol SCREEN2 24577  // The second screen.\n\
+            \n\
+            variables 16 32767\n\
+            reserve SCREEN 16384 24576\n\
+            reserve SCREEN2 0x6001 0x7000\n\
+            ";
+
+        let map = text.parse::<MemoryMap>().unwrap();
+
+        assert_eq!(map.symbols.len(), 24);
+        assert_eq!(map.symbols[23], (String::from("SCREEN2"), 24577));
+        assert_eq!(map.layout.rom_size, 16384);
+        assert_eq!((map.layout.variable_base, map.layout.variable_limit), (16, 32767));
+        assert_eq!(map.layout.reserved_at(24577).map(|r| r.name.as_str()), Some("SCREEN2"));
+        assert_eq!(map.layout.reserved_at(16383), None);
+        assert_eq!(map.to_string().parse::<MemoryMap>().unwrap(), map);
+    }
+
+    #[test]
+    fn invalid_memory_map_text() {
+        let cases = [
+            ("rom 0\n", "'0' is not a ROM size from 1 to 32768", 1),
+            ("\nsymbol SP 32768\n", "'32768' is not an address from 0 to 32767", 2),
+            ("predefined hack\nsymbol SP 0\n", "'SP' is defined more than once", 2),
+            ("variables 100 16\n", "the range 100 to 16 is empty", 1),
+            ("reserve 1 2\n", "unrecognised record 'reserve 1 2'", 1),
+        ];
+
+        for (text, message, line) in cases.iter() {
+            let error = text.parse::<MemoryMap>().unwrap_err();
+
+            assert_eq!(error.kind(), Some(ErrorKind::InvalidMemoryMap));
+            assert_eq!(error.message(), *message);
+            assert_eq!(error.span().map(|span| span.line), Some(*line));
+        }
+    }
+}
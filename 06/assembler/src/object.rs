@@ -0,0 +1,410 @@
+use std::collections::HashSet;
+use std::fmt;
+use std::str::FromStr;
+use crate::error::{Error, ErrorKind, Result, Span};
+use crate::parser::{self, Command, Parser};
+use crate::runner::{self, Assembly};
+use crate::symbols::SymbolTable;
+
+/// The file extension of relocatable object files.
+///
+pub const EXTENSION: &str = "hobj";
+
+/// The first line of every object file, naming the format and its version.
+///
+const HEADER: &str = "hackobj 1";
+
+/// A relocatable object, produced by assembling a source file on its own so that it can later be
+/// linked with others into a complete program.
+///
+/// Symbols are private to the source file they are written in, unless declared with '.global'.
+/// Each symbol is known to the linker by its qualified name: a private symbol written without a
+/// '.' is qualified with the name of its module, the file name without its extension, so that
+/// 'loop' in 'Math.asm' becomes 'Math.loop', as the VM translator names static variables.  Global
+/// symbols, and symbols already written as 'File.name', keep the name they are written with.
+///
+/// The code is assembled as though it were loaded at ROM address 0.  Each ROM address below is
+/// relative to the start of the code:
+///
+/// * 'exports' holds the global labels the object defines, along with their addresses, which
+///   other objects may refer to.
+/// * 'locals' holds the private labels, by their qualified names, which only this object may
+///   refer to.
+/// * 'references' holds the qualified name of each symbol used by an A-command that is neither a
+///   label of the object nor predefined, along with the address of that A-command.  The linker
+///   resolves it to a label exported by another object, or otherwise allocates it as a variable.
+///   Its word is 0 until then.
+/// * 'relocations' holds the address of each A-command that loads the address of one of the
+///   object's own labels, which the linker adjusts by the address the code is placed at.
+///
+/// An object is written as text, one record per line:
+///
+/// '''
+/// hackobj 1
+/// word 0000
+/// word ea87
+/// export Main.loop 0
+/// local Main.end 2
+/// extern Main.i 2
+/// reloc 0
+/// '''
+#[derive(Debug, Clone, Default, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Object {
+    pub words: Vec<u16>,
+    pub exports: Vec<(String, u16)>,
+    pub locals: Vec<(String, u16)>,
+    pub references: Vec<(String, u16)>,
+    pub relocations: Vec<u16>,
+}
+
+/// Assembles source text into a relocatable object for the named module, starting from the given
+/// symbol table, which should hold only predefined symbols and constants.
+///
+/// Returns a Result containing the object, along with the Assembly of its code as it stands
+/// before linking, whose symbol table holds the labels, by the names they are written with, but
+/// no variables.
+///
+/// # Examples
+///
+/// '''
+/// use assembler::{object, SymbolTable};
+///
+/// let source = ".global LOOP\n(LOOP)\n@i\n@LOOP\n";
+/// let (object, _) = object::assemble(source, "Main", SymbolTable::new()).unwrap();
+///
+/// assert_eq!(object.exports, vec![(String::from("LOOP"), 0)]);
+/// assert_eq!(object.references, vec![(String::from("Main.i"), 0)]);
+/// assert_eq!(object.relocations, vec![1]);
+/// '''
+pub fn assemble(source: &str, module: &str, symbols: SymbolTable) -> Result<(Object, Assembly)> {
+    let globals = globals(source)?;
+    let mut parser = Parser::from_source(source, symbols);
+
+    runner::first_pass(&mut parser)?;
+
+    parser.reset();
+
+    let mut references = Vec::new();
+    let mut relocations = Vec::new();
+
+    let (words, lines) = runner::second_pass(&mut parser, |parser, address| {
+        let symbol = parser.symbol()?;
+
+        if let Some(value) = runner::constant(&symbol, parser.instruction_set())? {
+            return Ok(value);
+        }
+
+        match parser.get_symbol_address(&symbol) {
+            Some(value) => {
+                if parser.is_label(&symbol) {
+                    relocations.push(address);
+                }
+                Ok(value)
+            },
+            None => {
+                let symbol = qualified_name(module, &symbol, &globals);
+
+                log::debug!("External reference to {:?} at {}.", symbol, address);
+
+                references.push((symbol, address));
+                Ok(0)
+            },
+        }
+    })?;
+
+    let symbols = parser.into_symbol_table();
+    let mut exports = Vec::new();
+    let mut locals = Vec::new();
+
+    for (label, address) in symbols.labels() {
+        match globals.contains(label) {
+            true => exports.push((String::from(label), address)),
+            false => locals.push((qualified_name(module, label, &globals), address)),
+        }
+    }
+
+    let object = Object { words: words.clone(), exports, locals, references, relocations };
+
+    Ok((object, Assembly { words, lines, symbols }))
+}
+
+/// Returns the name a symbol written in the named module is known by when linking.
+///
+/// # Examples
+///
+/// '''
+/// use std::collections::HashSet;
+/// use assembler::object;
+///
+/// let globals: HashSet<String> = vec![String::from("count")].into_iter().collect();
+///
+/// assert_eq!(object::qualified_name("Math", "loop", &globals), "Math.loop");
+/// assert_eq!(object::qualified_name("Math", "count", &globals), "count");
+/// assert_eq!(object::qualified_name("Math", "Sys.init", &globals), "Sys.init");
+/// '''
+pub fn qualified_name(module: &str, symbol: &str, globals: &HashSet<String>) -> String {
+    match globals.contains(symbol) || symbol.contains('.') {
+        true => String::from(symbol),
+        false => format!("{}.{}", module, symbol),
+    }
+}
+
+/// Returns the symbols declared with '.global' in the source text.
+///
+fn globals(source: &str) -> Result<HashSet<String>> {
+    let mut globals = HashSet::new();
+
+    for (command, _) in parser::commands(source)? {
+        if let Command::Directive(ref directive) = command {
+            let (name, arguments) = parser::directive_fields(directive);
+
+            if name == ".global" {
+                globals.extend(arguments.into_iter().map(String::from));
+            }
+        }
+    }
+
+    Ok(globals)
+}
+
+impl fmt::Display for Object {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{}", HEADER)?;
+
+        for word in self.words.iter() {
+            writeln!(f, "word {:04x}", word)?;
+        }
+
+        for (symbol, address) in self.exports.iter() {
+            writeln!(f, "export {} {}", symbol, address)?;
+        }
+
+        for (symbol, address) in self.locals.iter() {
+            writeln!(f, "local {} {}", symbol, address)?;
+        }
+
+        for (symbol, address) in self.references.iter() {
+            writeln!(f, "extern {} {}", symbol, address)?;
+        }
+
+        for address in self.relocations.iter() {
+            writeln!(f, "reloc {}", address)?;
+        }
+
+        Ok(())
+    }
+}
+
+impl FromStr for Object {
+    type Err = Error;
+
+    /// Reads an object from its text form, as written by 'Display'.  Blank lines are ignored,
+    /// and errors carry the span of the line at fault.
+    ///
+    fn from_str(s: &str) -> Result<Object> {
+        Object::parse(s, 1)
+    }
+}
+
+impl Object {
+    /// Returns the address of an exported label, if the object defines it.
+    ///
+    pub fn export(&self, symbol: &str) -> Option<u16> {
+        self.exports.iter().find(|(name, _)| name == symbol).map(|&(_, address)| address)
+    }
+
+    /// Reads an object from its text form, where the text starts at the given line of the file
+    /// it was read from, so that errors point at the right line when the object is part of an
+    /// archive.
+    ///
+    pub(crate) fn parse(s: &str, first_line: usize) -> Result<Object> {
+        let mut object = Object::default();
+        let mut addresses = Vec::new();
+        let mut defined = HashSet::new();
+        let mut header = false;
+
+        for (index, line) in s.lines().enumerate() {
+            let span = Span { line: first_line + index, column: 1, length: line.chars().count() };
+            let invalid = |message: String| {
+                Error::with_message(ErrorKind::InvalidObjectFile, message).with_span(Some(span))
+            };
+
+            let fields: Vec<&str> = line.split_whitespace().collect();
+
+            if fields.is_empty() {
+                continue;
+            }
+
+            if !header {
+                if fields.join(" ") != HEADER {
+                    return Err(invalid(format!("expected '{}' at the start of the object", HEADER))
+                        .with_help(String::from("object files are written by 'assembler -c'")));
+                }
+                header = true;
+                continue;
+            }
+
+            match fields[..] {
+                ["word", word] => match u16::from_str_radix(word, 16) {
+                    Ok(value) if word.len() == 4 => object.words.push(value),
+                    _ => return Err(invalid(format!("'{}' is not a 4-digit machine word", word))),
+                },
+                [kind @ "export", symbol, address] | [kind @ "local", symbol, address] => {
+                    if !defined.insert(symbol) {
+                        return Err(invalid(format!("'{}' is defined more than once", symbol)));
+                    }
+                    let address = parse_address(address).map_err(invalid)?;
+                    let labels = match kind {
+                        "export" => &mut object.exports,
+                        _ => &mut object.locals,
+                    };
+
+                    labels.push((String::from(symbol), address));
+                    addresses.push((address, span, true));
+                },
+                ["extern", symbol, address] => {
+                    let address = parse_address(address).map_err(invalid)?;
+
+                    object.references.push((String::from(symbol), address));
+                    addresses.push((address, span, false));
+                },
+                ["reloc", address] => {
+                    let address = parse_address(address).map_err(invalid)?;
+
+                    object.relocations.push(address);
+                    addresses.push((address, span, false));
+                },
+                _ => return Err(invalid(format!("unrecognised record '{}'", line.trim()))),
+            }
+        }
+
+        if !header {
+            return Err(Error::with_message(
+                ErrorKind::InvalidObjectFile,
+                String::from("the object is empty"),
+            ));
+        }
+
+        // A label may mark the end of the code, but every other record refers to a word.
+        for (address, span, label) in addresses {
+            if usize::from(address) >= object.words.len() + usize::from(label) {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidObjectFile,
+                    format!("address {} is beyond the end of the code", address),
+                ).with_span(Some(span)));
+            }
+        }
+
+        Ok(object)
+    }
+}
+
+/// Parses a decimal ROM address, returning a message describing the problem if it is invalid.
+///
+fn parse_address(address: &str) -> std::result::Result<u16, String> {
+    address.parse::<u16>().map_err(|_| format!("'{}' is not a valid address", address))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SOURCE: &str = "\
+        .global START count\n\
+        (START)\n\
+        @i\n\
+        M=1\n\
+        @count\n\
+        @SCREEN\n\
+        D=A\n\
+        @END\n\
+        0;JMP\n\
+        (END)\n\
+        @START\n\
+        0;JMP\n\
+        ";
+
+    #[test]
+    fn assemble_object() {
+        let (object, assembly) = assemble(SOURCE, "Main", SymbolTable::new()).unwrap();
+
+        assert_eq!(object.words, vec![0, 0xefc8, 0, 0x4000, 0xec10, 7, 0xea87, 0, 0xea87]);
+        assert_eq!(object.exports, vec![(String::from("START"), 0)]);
+        assert_eq!(object.locals, vec![(String::from("Main.END"), 7)]);
+        assert_eq!(
+            object.references,
+            vec![(String::from("Main.i"), 0), (String::from("count"), 2)],
+        );
+        assert_eq!(object.relocations, vec![5, 7]);
+        assert_eq!(object.export("START"), Some(0));
+        assert_eq!(object.export("END"), None);
+
+        assert_eq!(assembly.words, object.words);
+        assert_eq!(assembly.lines, vec![3, 4, 5, 6, 7, 8, 9, 11, 12]);
+        assert_eq!(assembly.symbols.labels(), vec![("START", 0), ("END", 7)]);
+        assert!(assembly.symbols.variables().is_empty());
+    }
+
+    #[test]
+    fn qualified_names() {
+        let (object, _) = assemble("(loop)\n@Sys.init\n@loop\n(Main.run)\n", "Main",
+            SymbolTable::new()).unwrap();
+
+        assert_eq!(
+            object.locals,
+            vec![(String::from("Main.loop"), 0), (String::from("Main.run"), 2)],
+        );
+        assert_eq!(object.references, vec![(String::from("Sys.init"), 0)]);
+    }
+
+    #[test]
+    fn object_text() {
+        let (object, _) = assemble(SOURCE, "Main", SymbolTable::new()).unwrap();
+        let text = object.to_string();
+
+        assert_eq!(text, "\
+            hackobj 1\n\
+            word 0000\n\
+            word efc8\n\
+            word 0000\n\
+            word 4000\n\
+            word ec10\n\
+            word 0007\n\
+            word ea87\n\
+            word 0000\n\
+            word ea87\n\
+            export START 0\n\
+            local Main.END 7\n\
+            extern Main.i 0\n\
+            extern count 2\n\
+            reloc 5\n\
+            reloc 7\n\
+            ");
+        assert_eq!(text.parse::<Object>().unwrap(), object);
+    }
+
+    #[test]
+    fn invalid_object_text() {
+        let cases = [
+            ("", "the object is empty", None),
+            ("word 0000\n", "expected 'hackobj 1' at the start of the object", Some(1)),
+            ("hackobj 1\nword 10\n", "'10' is not a 4-digit machine word", Some(2)),
+            ("hackobj 1\n\nlabel A 0\n", "unrecognised record 'label A 0'", Some(3)),
+            ("hackobj 1\nword 0000\nreloc x\n", "'x' is not a valid address", Some(3)),
+            ("hackobj 1\nexport A 0\nlocal A 0\n", "'A' is defined more than once", Some(3)),
+            ("hackobj 1\nword 0000\nextern i 2\n", "address 2 is beyond the end of the code",
+                Some(3)),
+            ("hackobj 1\nword 0000\nreloc 1\n", "address 1 is beyond the end of the code", Some(3)),
+            ("hackobj 1\nexport A 1\n", "address 1 is beyond the end of the code", Some(2)),
+        ];
+
+        for (text, message, line) in cases.iter() {
+            let error = text.parse::<Object>().unwrap_err();
+
+            assert_eq!(error.kind(), Some(ErrorKind::InvalidObjectFile));
+            assert_eq!(error.message(), *message);
+            assert_eq!(error.span().map(|span| span.line), *line);
+        }
+    }
+}
@@ -0,0 +1,259 @@
+use std::fmt::Write as FmtWrite;
+use std::io::{BufRead, Write};
+use std::path::Path;
+use std::str::FromStr;
+use crate::error::{Error, ErrorKind, Result, Span};
+use crate::runner::Assembly;
+
+/// The file formats in which assembled machine instructions can be written.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum OutputFormat {
+    /// Text with one 16-digit binary instruction per line, as read by the Hack CPU emulator.
+    Hack,
+    /// Raw 16-bit big-endian words.
+    Binary,
+    /// Text with one 4-digit hexadecimal instruction per line.
+    Hex,
+}
+
+impl OutputFormat {
+    /// Returns the file extension expected for output files of this format.
+    ///
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            OutputFormat::Hack => "hack",
+            OutputFormat::Binary => "bin",
+            OutputFormat::Hex => "hex",
+        }
+    }
+
+    /// Formats machine instructions as the contents of an output file of this format.
+    ///
+    pub fn format(&self, words: &[u16]) -> Vec<u8> {
+        let mut output: Vec<u8> = Vec::with_capacity(words.len() * 17);
+
+        for word in words {
+            match *self {
+                OutputFormat::Hack => writeln!(output, "{:016b}", word).unwrap(),
+                OutputFormat::Binary => output.extend_from_slice(&word.to_be_bytes()),
+                OutputFormat::Hex => writeln!(output, "{:04x}", word).unwrap(),
+            }
+        }
+
+        output
+    }
+
+    /// Returns the format whose extension the given path has, if any.
+    ///
+    pub fn from_path(path: &Path) -> Option<OutputFormat> {
+        let extension = path.extension()?.to_str()?;
+
+        [OutputFormat::Hack, OutputFormat::Binary, OutputFormat::Hex]
+            .iter()
+            .copied()
+            .find(|format| format.extension() == extension)
+    }
+
+    /// Reads machine instructions back from the contents of a file of this format, the reverse
+    /// of 'format'.  Blank lines in the text formats are ignored.
+    ///
+    /// Malformed lines are reported with their location, and a binary file must hold a whole
+    /// number of words.
+    ///
+    pub fn decode(&self, contents: &[u8]) -> Result<Vec<u16>> {
+        let (radix, digits) = match *self {
+            OutputFormat::Binary => {
+                if !contents.len().is_multiple_of(2) {
+                    return Err(Error::with_message(
+                        ErrorKind::InvalidMachineCode,
+                        String::from("binary machine code must hold a whole number of words"),
+                    ));
+                }
+
+                let words = contents.chunks(2).map(|pair| u16::from_be_bytes([pair[0], pair[1]]));
+
+                return Ok(words.collect());
+            },
+            OutputFormat::Hack => (2, 16),
+            OutputFormat::Hex => (16, 4),
+        };
+
+        let text = String::from_utf8_lossy(contents);
+        let mut words = Vec::new();
+
+        for (index, line) in text.lines().enumerate() {
+            let word = line.trim();
+
+            if word.is_empty() {
+                continue;
+            }
+
+            match u16::from_str_radix(word, radix) {
+                Ok(value) if word.len() == digits => words.push(value),
+                _ => {
+                    let indent = line.len() - line.trim_start().len();
+
+                    return Err(Error::with_message(
+                        ErrorKind::InvalidMachineCode,
+                        format!("'{}' is not a {}-digit machine instruction", word, digits),
+                    ).with_span(Some(Span {
+                        line: index + 1,
+                        column: line[..indent].chars().count() + 1,
+                        length: word.chars().count(),
+                    })));
+                },
+            }
+        }
+
+        Ok(words)
+    }
+}
+
+impl FromStr for OutputFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<OutputFormat> {
+        match s {
+            "hack" => Ok(OutputFormat::Hack),
+            "bin" => Ok(OutputFormat::Binary),
+            "hex" => Ok(OutputFormat::Hex),
+            _ => Err(Error::new(ErrorKind::InvalidOutputFormat)),
+        }
+    }
+}
+
+/// Formats a listing of the assembled program.  Every line of the source is reproduced, and
+/// those that produced a machine instruction are prefixed with its ROM address and binary
+/// representation.
+///
+/// The source is read from the given reader, which must yield the same text that was assembled.
+///
+pub fn listing<R>(assembly: &Assembly, source: R) -> Result<Vec<u8>>
+    where R: BufRead
+{
+    let mut output = String::new();
+    let mut instructions = assembly.lines.iter().zip(assembly.words.iter()).enumerate().peekable();
+
+    for (index, line) in source.lines().enumerate() {
+        let line = line?;
+        let line_number = index + 1;
+
+        match instructions.next_if(|&(_, (&l, _))| l == line_number) {
+            Some((address, (_, word))) => {
+                writeln!(output, "{:05}  {:016b}  {}", address, word, line).unwrap();
+            },
+            None => writeln!(output, "{:5}  {:16}  {}", "", "", line).unwrap(),
+        }
+    }
+
+    Ok(output.into_bytes())
+}
+
+/// Formats the label and variable symbols of an assembled program, one per line as the kind of
+/// symbol, its name and its address.
+///
+pub fn symbols(assembly: &Assembly) -> Vec<u8> {
+    let mut output = String::new();
+
+    for (symbol, address) in assembly.symbols.labels() {
+        writeln!(output, "label {} {}", symbol, address).unwrap();
+    }
+
+    for (symbol, address) in assembly.symbols.variables() {
+        writeln!(output, "variable {} {}", symbol, address).unwrap();
+    }
+
+    output.into_bytes()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::SymbolTable;
+
+    fn test_assembly() -> Assembly {
+        let mut symbols = SymbolTable::new();
+
+        symbols.insert_variable("i").unwrap();
+        symbols.inc_rom_address().unwrap();
+        symbols.insert_label("LOOP").unwrap();
+
+        Assembly {
+            words: vec![0b0000_0000_0001_0000, 0b1110_1010_1000_0111],
+            lines: vec![2, 4],
+            symbols,
+        }
+    }
+
+    #[test]
+    fn format_words() {
+        let words = [0b0000_0000_0001_0000, 0b1110_1010_1000_0111];
+
+        assert_eq!(
+            OutputFormat::Hack.format(&words),
+            b"0000000000010000\n1110101010000111\n".to_vec(),
+        );
+        assert_eq!(OutputFormat::Binary.format(&words), vec![0x00, 0x10, 0xea, 0x87]);
+        assert_eq!(OutputFormat::Hex.format(&words), b"0010\nea87\n".to_vec());
+    }
+
+    #[test]
+    fn decode_words() {
+        let words = vec![0b0000_0000_0001_0000, 0b1110_1010_1000_0111];
+
+        for format in [OutputFormat::Hack, OutputFormat::Binary, OutputFormat::Hex] {
+            assert_eq!(format.decode(&format.format(&words)).unwrap(), words);
+        }
+
+        assert_eq!(OutputFormat::Hex.decode(b"0010\n\n  ea87  \n").unwrap(), words);
+        assert!(OutputFormat::Binary.decode(&[0x00, 0x10, 0xea]).is_err());
+
+        let err = OutputFormat::Hack.decode(b"0000000000010000\n  10101\n").unwrap_err();
+
+        assert_eq!(err.message(), "'10101' is not a 16-digit machine instruction");
+        assert_eq!(err.span(), Some(Span { line: 2, column: 3, length: 5 }));
+
+        assert_eq!(OutputFormat::from_path(Path::new("a/Prog.hex")), Some(OutputFormat::Hex));
+        assert_eq!(OutputFormat::from_path(Path::new("a/Prog.asm")), None);
+    }
+
+    #[test]
+    fn parse_output_format() {
+        assert_eq!("hack".parse::<OutputFormat>().unwrap(), OutputFormat::Hack);
+        assert_eq!("bin".parse::<OutputFormat>().unwrap(), OutputFormat::Binary);
+        assert_eq!("hex".parse::<OutputFormat>().unwrap(), OutputFormat::Hex);
+        assert!("elf".parse::<OutputFormat>().is_err());
+    }
+
+    #[test]
+    fn format_listing() {
+        let source = "// Comment.\n@i\n(LOOP)\n0;JMP\n";
+
+        let listing = listing(&test_assembly(), source.as_bytes()).unwrap();
+
+        let blank = " ".repeat(25);
+
+        assert_eq!(
+            String::from_utf8(listing).unwrap(),
+            format!("\
+                {0}// Comment.\n\
+                00000  0000000000010000  @i\n\
+                {0}(LOOP)\n\
+                00001  1110101010000111  0;JMP\n\
+                ",
+                blank,
+            ),
+        );
+    }
+
+    #[test]
+    fn format_symbols() {
+        assert_eq!(
+            String::from_utf8(symbols(&test_assembly())).unwrap(),
+            "label LOOP 1\nvariable i 16\n",
+        );
+    }
+}
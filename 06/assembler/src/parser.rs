@@ -1,112 +1,172 @@
-use std::io::{BufReader, BufRead, Seek, SeekFrom};
-use std::fs::File;
+use std::fmt;
+#[cfg(feature = "std-io")]
+use std::fs;
+#[cfg(feature = "std-io")]
 use std::path::Path;
-use regex::{Regex, RegexSet};
-use crate::error::{Error, ErrorKind, Result};
+use crate::code_translator::{Hack, InstructionSet, Word};
+use crate::error::{Error, ErrorKind, Result, Span};
 use crate::symbols::SymbolTable;
 
-/// Different types of Command; A- or C-instructions, or L-pseudocommands along with their String
-/// representation.
+/// Different types of Command; A- or C-instructions, L-pseudocommands, or directives such as
+/// '.global NAME', along with their String representation.
 ///
+#[allow(clippy::enum_variant_names)]
 #[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
 pub enum Command {
     ACommand(String),
     CCommand(String),
     LCommand(String),
+    Directive(String),
 }
 
-/// A struct that encapsulates the current state of the parser.  It holds a BufReader for the input
-/// file, as well as the last raw line read and any command contained within that line.  A
-/// SymbolTable tracks variable and label symbols along with their allocated RAM/ROM addresses.
+/// The directives that may appear in a program.
 ///
-#[derive(Debug)]
-pub struct Parser {
-    reader: std::io::BufReader<File>,
+/// - '.global NAME...': the named symbols are shared with the other modules a program is linked
+///   from, rather than being private to the source file.  It has no effect when a source file is
+///   assembled on its own.
+/// - '.inst WORD': the word is emitted as an instruction, so that words with no mnemonic can be
+///   included in a program.  It is written in decimal, or in binary or hexadecimal with a '0b' or
+///   '0x' prefix.
+///
+pub const DIRECTIVES: [&str; 2] = [".global", ".inst"];
+
+/// A struct that encapsulates the current state of the parser.  It holds the source text and the
+/// position of the next line to be read, as well as the last raw line read and any command
+/// contained within that line, along with their position in the source.  A SymbolTable tracks
+/// variable and label symbols along with their allocated RAM/ROM addresses.
+///
+/// C-commands are recognised by the syntax of the instruction set that the Parser is created
+/// with, which is standard Hack unless another is given.
+///
+pub struct Parser<I = Hack> {
+    source: String,
+    position: usize,
     raw_line: String,
+    line_number: usize,
     command: Option<Command>,
+    command_span: Option<Span>,
     symbol_table: SymbolTable,
+    isa: I,
 }
 
 impl Parser {
     /// Takes a reference to the Path of an input file and returns a Result containing a new Parser
-    /// instance.
+    /// instance, which will populate the given SymbolTable.
     ///
-    /// An error will be returned if opening the file identified by the given Path returns an
+    /// An error will be returned if reading the file identified by the given Path returns an
     /// error.
-    /// 
-    pub fn new(filename: &Path) -> Result<Parser> {
-        let file = File::open(filename)?;
+    ///
+    #[cfg(feature = "std-io")]
+    pub fn new(filename: &Path, symbol_table: SymbolTable) -> Result<Parser> {
+        let source = fs::read_to_string(filename)?;
+
+        Ok(Parser::with_source(source, symbol_table, Hack))
+    }
+
+    /// Returns a new Parser instance that reads commands from the given source text, and which
+    /// will populate the given SymbolTable.
+    ///
+    pub fn from_source(source: &str, symbol_table: SymbolTable) -> Parser {
+        Parser::with_source(String::from(source), symbol_table, Hack)
+    }
+}
 
-        Ok(Parser {
-            reader: BufReader::new(file),
+impl<I: InstructionSet> Parser<I> {
+    /// Returns a new Parser instance that reads commands of the given instruction set from the
+    /// source text, and which will populate the given SymbolTable.
+    ///
+    pub fn with_instruction_set(source: &str, symbol_table: SymbolTable, isa: I) -> Parser<I> {
+        Parser::with_source(String::from(source), symbol_table, isa)
+    }
+
+    fn with_source(source: String, symbol_table: SymbolTable, isa: I) -> Parser<I> {
+        Parser {
+            source,
+            position: 0,
             raw_line: String::new(),
+            line_number: 0,
             command: None,
-            symbol_table: SymbolTable::new(),
-        })
+            command_span: None,
+            symbol_table,
+            isa,
+        }
     }
 
     /// Reads the next line and extracts a command string if present, updating the 'command' option
     /// field of the Parser instance appropriately.
     /// 
     /// Returns a result containing the number of bytes present in the original line read from the
-    /// source file.
+    /// source.
+    ///
+    /// Ok(0) will be returned when the end of the source is reached.
     ///
-    /// Ok(0) will be returned when EOF is reached.
+    /// Syntax errors are returned with the span of the offending command attached.
     ///
     pub fn advance(&mut self) -> Result<usize> {
+        let rest = &self.source[self.position..];
+        let bytes = rest.find('\n').map_or(rest.len(), |i| i + 1);
+
         self.raw_line.clear();
+        self.raw_line.push_str(&rest[..bytes]);
+        self.position += bytes;
 
-        let bytes = self.reader.read_line(&mut self.raw_line)?;
+        if bytes > 0 {
+            self.line_number += 1;
+        }
 
-        self.set_command()?;
+        self.set_command().map_err(|e| e.with_span(self.command_span))?;
 
         Ok(bytes)
     }
 
-    /// Takes the currently loaded raw line from the source file, strips it of any comments and
-    /// trims any remaining leading or trailing whitespace.
+    /// Takes the currently loaded raw line from the source, strips it of any comments and trims
+    /// any remaining leading or trailing whitespace.
     ///
     /// If the remaining line content is not empty, then it is used to set the 'command' field of
     /// the Parser instance.  Otherwise the 'command' field is set to None.
     ///
     fn set_command(&mut self) -> Result<usize> {
         self.command = None;
+        self.command_span = None;
 
         let mut cmd = self.raw_line.clone();
 
         let comment_offset = cmd.find("//").unwrap_or(cmd.len());
 
         cmd.replace_range(comment_offset.., "");
+        let indent = cmd.len() - cmd.trim_start().len();
         let cmd = cmd.trim();
         
         if cmd.is_empty() {
             return Ok(0);
         } else {
+            self.command_span = Some(Span {
+                line: self.line_number,
+                column: self.raw_line[..indent].chars().count() + 1,
+                length: cmd.chars().count(),
+            });
+
             self.set_command_type(cmd)?;
         }
         Ok(0)
     }
 
-    /// Takes an input &str and determines whether it is an A-, C- or L-command, setting the
-    /// 'command' field fo the Parser instance appropriately.
+    /// Takes an input &str and determines whether it is an A-, C- or L-command or a directive,
+    /// setting the 'command' field fo the Parser instance appropriately.
     ///
     /// Returns Ok(0) upon successful execution.
     ///
     fn set_command_type(&mut self, cmd: &str) -> Result<usize> {
-        let re_a = Regex::new(r"^@").unwrap();
-        let re_c = RegexSet::new(&[
-            r"^[[:alpha:]]+=[[:alpha:]01\-!+&|]+$",  // dest=comp
-            r"^[[:alpha:]01\-!+&|]+;[[:alpha:]]+$",  // comp;jump
-            r"^[[:alpha:]]+=[[:alpha:]01\-!+&|]+;[[:alpha:]]+$",  // dest=comp;jump
-        ]).unwrap();
-        let re_l = Regex::new(r"^\([[:word:].$]+\)$").unwrap();
-
-        if re_a.is_match(cmd) {
+        if cmd.starts_with('@') {
             self.command = Some(Command::ACommand(String::from(cmd)));
-        } else if re_c.is_match(cmd) {
+        } else if is_c_command(cmd, self.isa.comp_operators()) {
             self.command = Some(Command::CCommand(String::from(cmd)));
-        } else if re_l.is_match(cmd) {
+        } else if label_symbol(cmd).is_some() {
             self.command = Some(Command::LCommand(String::from(cmd)));
+        } else if cmd.starts_with('.') {
+            check_directive::<I::Word>(cmd)?;
+            self.command = Some(Command::Directive(String::from(cmd)));
         } else {
             return Err(Error::new(ErrorKind::InvalidSyntax));
         }
@@ -120,19 +180,45 @@ impl Parser {
     /// This method can only be called on A- or L-commands and will otherwise return an error.
     ///
     pub fn symbol(&self) -> Result<String> {
-        let (raw_symbol, re) = match self.command {
+        let symbol = match self.command {
             Some(Command::ACommand(ref cmd)) => {
-                (cmd.clone(), Regex::new(r"^@(?P<symbol>[[:word:].$]+)$").unwrap())
-            },
-            Some(Command::LCommand(ref cmd)) => {
-                (cmd.clone(), Regex::new(r"^[\(](?P<symbol>[[:word:].$]+)[\)]$").unwrap())
+                // An A-command that is not followed by a valid symbol is returned whole.
+                Some(&cmd[1..]).filter(|symbol| is_symbol(symbol)).unwrap_or(cmd)
             },
+            Some(Command::LCommand(ref cmd)) => label_symbol(cmd).unwrap_or(cmd),
             _ => return Err(Error::new(ErrorKind::InvalidCmdType)),
         };
 
-        let symbol = String::from(re.replace(&raw_symbol[..], "$symbol"));
+        Ok(String::from(symbol))
+    }
+
+    /// Returns a Result containing the name of the current directive, such as '.global', along
+    /// with its arguments.
+    ///
+    /// This method can only be called on directives and will otherwise return an error.
+    ///
+    pub fn directive(&self) -> Result<(String, Vec<String>)> {
+        match self.command {
+            Some(Command::Directive(ref cmd)) => {
+                let (name, arguments) = directive_fields(cmd);
+
+                Ok((String::from(name), arguments.into_iter().map(String::from).collect()))
+            },
+            _ => Err(Error::new(ErrorKind::InvalidCmdType)),
+        }
+    }
 
-        Ok(symbol)
+    /// Returns an Option containing the word emitted by the current command if it is an '.inst'
+    /// directive, or None otherwise.
+    ///
+    pub fn instruction_word(&self) -> Option<I::Word> {
+        match self.command {
+            Some(Command::Directive(ref cmd)) => match directive_fields(cmd) {
+                (".inst", arguments) => arguments.first().and_then(|word| parse_word::<I::Word>(word)),
+                _ => None,
+            },
+            _ => None,
+        }
     }
 
     /// Returns an Option containing the 'dest' component of the current C-command string, within
@@ -141,23 +227,9 @@ impl Parser {
     /// This method can only be called on C-commands and will otherwise return an error.
     ///
     pub fn dest(&self) -> Result<Option<String>> {
-        let (command, re) = match self.command {
-            Some(Command::CCommand(ref cmd)) => {
-                (cmd.clone(),
-                 Regex::new(r"(^(?P<dest>[[:alpha:]]+)=)").unwrap()
-                )
-            },
-            _ => return Err(Error::new(ErrorKind::InvalidCmdType)),
-        };
+        let (dest, _, _) = self.c_fields()?;
 
-        let caps = match re.captures(&command[..]) {
-            Some(c) => c,
-            None => return Ok(None),
-        };
-
-        let dest = String::from(caps.name("dest").unwrap().as_str());
-
-        Ok(Some(dest))
+        Ok(dest.map(String::from))
     }
 
     /// Returns an Option containing the 'comp' component of the current C-command string, within
@@ -166,26 +238,9 @@ impl Parser {
     /// This method can only be called on C-commands and will otherwise return an error.
     ///
     pub fn comp(&self) -> Result<Option<String>> {
-        let (command, re) = match self.command {
-            Some(Command::CCommand(ref cmd)) => {
-                (cmd.clone(),
-                 Regex::new(r"(?x)
-                    (^(?P<comp_0>[[:alpha:]01\-!+&|]+);) |
-                    (=(?P<comp_1>[[:alpha:]01\-!+&|]+);?)"
-                 ).unwrap()
-                )
-            },
-            _ => return Err(Error::new(ErrorKind::InvalidCmdType)),
-        };
+        let (_, comp, _) = self.c_fields()?;
 
-        let caps = match re.captures(&command[..]) {
-            Some(c) => c,
-            None => return Ok(None),
-        };
-
-        let comp = String::from(caps.name("comp_0").or_else(|| caps.name("comp_1")).unwrap().as_str());
-
-        Ok(Some(comp))
+        Ok(Some(String::from(comp)))
     }
 
     /// Returns an Option containing the 'jump' component of the current C-command string, within
@@ -194,23 +249,18 @@ impl Parser {
     /// This method can only be called on C-commands and will otherwise return an error.
     ///
     pub fn jump(&self) -> Result<Option<String>> {
-        let (command, re) = match self.command {
-            Some(Command::CCommand(ref cmd)) => {
-                (cmd.clone(),
-                 Regex::new(r"(;(?P<jump>[[:alpha:]]+)$)").unwrap()
-                )
-            },
-            _ => return Err(Error::new(ErrorKind::InvalidCmdType)),
-        };
-
-        let caps = match re.captures(&command[..]) {
-            Some(c) => c,
-            None => return Ok(None),
-        };
+        let (_, _, jump) = self.c_fields()?;
 
-        let jump = String::from(caps.name("jump").unwrap().as_str());
+        Ok(jump.map(String::from))
+    }
 
-        Ok(Some(jump))
+    /// Splits the current C-command into its 'dest', 'comp' and 'jump' fields.
+    ///
+    fn c_fields(&self) -> Result<(Option<&str>, &str, Option<&str>)> {
+        match self.command {
+            Some(Command::CCommand(ref cmd)) => Ok(c_fields(cmd)),
+            _ => Err(Error::new(ErrorKind::InvalidCmdType)),
+        }
     }
 
     /// Returns a reference to the last raw line from the input file read by the Parser.
@@ -219,12 +269,25 @@ impl Parser {
         &self.raw_line
     }
 
+    /// Returns the line number, counting from 1, of the last raw line read by the Parser.
+    ///
+    pub fn get_line_number(&self) -> usize {
+        self.line_number
+    }
+
     /// Returns a reference to an Option containing the current Command loaded into the Parser.
     ///
     pub fn get_command(&self) -> &Option<Command> {
         &self.command
     }
 
+    /// Returns an Option containing the span of the current command within the source file, or
+    /// None if no command is loaded.
+    ///
+    pub fn get_command_span(&self) -> Option<Span> {
+        self.command_span
+    }
+
     /// Increments the next available RAM address used when adding a new variable to the symbol
     /// table.
     ///
@@ -235,8 +298,8 @@ impl Parser {
     /// Increments the next available ROM address used when adding a new label to the symbol
     /// table.
     ///
-    pub fn inc_rom_address(&mut self) {
-        self.symbol_table.inc_rom_address();
+    pub fn inc_rom_address(&mut self) -> Result<u8> {
+        self.symbol_table.inc_rom_address()
     }
 
     /// Adds a new label to the symbol table and returns a Result containing the allocated ROM
@@ -260,30 +323,235 @@ impl Parser {
         self.symbol_table.get_address(symbol)
     }
 
+    /// Returns true if the symbol is a label in the symbol table.
+    ///
+    pub fn is_label(&self, symbol: &str) -> bool {
+        self.symbol_table.is_label(symbol)
+    }
+
+    /// Returns the instruction set that the Parser reads commands of.
+    ///
+    pub fn instruction_set(&self) -> &I {
+        &self.isa
+    }
+
+    /// Consumes the Parser and returns the symbol table built up whilst parsing.
+    ///
+    pub fn into_symbol_table(self) -> SymbolTable {
+        self.symbol_table
+    }
+
     /// Clears the current raw line and command loaded into the Parser instance and resets it back
-    /// to reading from the beginning of the source file.
+    /// to reading from the beginning of the source.
     ///
     pub fn reset(&mut self) {
-        self.reader.get_mut().seek(SeekFrom::Start(0)).unwrap();
+        self.position = 0;
         self.raw_line.clear();
+        self.line_number = 0;
         self.command = None;
+        self.command_span = None;
+    }
+}
+
+/// Parses the source text into its commands, along with the span of each, without translating
+/// them.  Label and variable symbols are left unresolved.
+///
+/// Returns the first syntax error found, with its span attached.
+///
+/// # Examples
+///
+/// '''
+/// use assembler::parser::{self, Command};
+///
+/// let commands = parser::commands("(LOOP)\n  @LOOP // Forever.\n").unwrap();
+///
+/// assert_eq!(commands[1].0, Command::ACommand(String::from("@LOOP")));
+/// assert_eq!(commands[1].1.column, 3);
+/// '''
+pub fn commands(source: &str) -> Result<Vec<(Command, Span)>> {
+    let mut parser = Parser::from_source(source, SymbolTable::new());
+    let mut commands = Vec::new();
+
+    while parser.advance()? > 0 {
+        if let (Some(command), Some(span)) = (parser.command.take(), parser.command_span) {
+            commands.push((command, span));
+        }
+    }
+
+    Ok(commands)
+}
+
+impl<I> fmt::Debug for Parser<I> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        // The source is left out, as it would repeat the whole program in every debug log.
+        f.debug_struct("Parser")
+            .field("position", &self.position)
+            .field("raw_line", &self.raw_line)
+            .field("line_number", &self.line_number)
+            .field("command", &self.command)
+            .field("command_span", &self.command_span)
+            .field("symbol_table", &self.symbol_table)
+            .finish()
+    }
+}
+
+/// Splits a C-command into its optional 'dest', 'comp' and optional 'jump' fields, which are
+/// separated by '=' and ';' respectively.  A comp such as 'alu(a=1,zx)' may itself contain '=',
+/// so the separators are only looked for outside of parentheses.
+///
+fn c_fields(cmd: &str) -> (Option<&str>, &str, Option<&str>) {
+    let open = cmd.find('(').unwrap_or(cmd.len());
+    let (dest, rest) = match cmd[..open].find('=') {
+        Some(i) => (Some(&cmd[..i]), &cmd[i + 1..]),
+        None => (None, cmd),
+    };
+
+    let close = rest.rfind(')').map_or(0, |i| i + 1);
+
+    match rest[close..].find(';') {
+        Some(i) => (dest, &rest[..close + i], Some(&rest[close + i + 1..])),
+        None => (dest, rest, None),
+    }
+}
+
+/// Returns true if the command has the form of a C-command: 'dest=comp', 'comp;jump' or
+/// 'dest=comp;jump', where the dest and jump are alphabetic and the comp is made up of letters
+/// and the given operators, which for standard Hack are '0', '1', '-', '!', '+', '&' and '|'.
+///
+/// The comp may instead list the ALU's control bits, as in 'alu(a=0,zx,nx,f,no)', which the
+/// instruction set checks when the command is translated.
+///
+fn is_c_command(cmd: &str, operators: &str) -> bool {
+    let is_mnemonic = |s: &str| !s.is_empty() && s.chars().all(|c| c.is_ascii_alphabetic());
+    let is_controls = |s: &str| s.chars().all(|c| c.is_ascii_alphanumeric() || ",=".contains(c));
+    let is_comp = |s: &str| match s.strip_prefix("alu(").and_then(|s| s.strip_suffix(')')) {
+        Some(controls) => is_controls(controls),
+        None => {
+            !s.is_empty() && s.chars().all(|c| c.is_ascii_alphabetic() || operators.contains(c))
+        },
+    };
+
+    match c_fields(cmd) {
+        (None, _, None) => false,
+        (dest, comp, jump) => {
+            dest.is_none_or(is_mnemonic) && is_comp(comp) && jump.is_none_or(is_mnemonic)
+        },
     }
 }
 
+/// Splits a directive into its name and the arguments that follow it, separated by whitespace.
+///
+pub fn directive_fields(cmd: &str) -> (&str, Vec<&str>) {
+    let mut fields = cmd.split_whitespace();
+    let name = fields.next().unwrap_or("");
+
+    (name, fields.collect())
+}
+
+/// Checks that a directive is one of those recognised, with valid arguments.
+///
+fn check_directive<W: Word>(cmd: &str) -> Result<()> {
+    let (name, arguments) = directive_fields(cmd);
+
+    if !DIRECTIVES.contains(&name) {
+        return Err(Error::with_message(
+            ErrorKind::InvalidSyntax,
+            format!("unrecognised directive '{}'", name),
+        ).with_help(format!("the directives are {}", DIRECTIVES.join(", "))));
+    }
+
+    if name == ".inst" {
+        return match arguments.as_slice() {
+            [word] if parse_word::<W>(word).is_some() => Ok(()),
+            [word] => Err(Error::with_message(
+                ErrorKind::InvalidSyntax,
+                format!("'{}' is not a {}-bit word", word, W::BITS),
+            ).with_help(String::from("write the word in decimal, or with a '0b' or '0x' prefix"))),
+            _ => Err(Error::with_message(
+                ErrorKind::InvalidSyntax,
+                String::from("'.inst' must be followed by a single word"),
+            )),
+        };
+    }
+
+    if arguments.is_empty() {
+        return Err(Error::with_message(
+            ErrorKind::InvalidSyntax,
+            format!("'{}' must be followed by at least one symbol", name),
+        ));
+    }
+
+    for argument in arguments {
+        if !is_symbol(argument) || argument.starts_with(|c: char| c.is_ascii_digit()) {
+            return Err(Error::with_message(
+                ErrorKind::InvalidSyntax,
+                format!("'{}' is not a valid symbol", argument),
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Parses the word of an '.inst' directive, written in decimal, or in binary or hexadecimal with a
+/// '0b' or '0x' prefix.  Underscores may separate the digits of binary and hexadecimal words.
+///
+/// Returns None if the word is not a number or does not fit in the machine word W.
+///
+fn parse_word<W: Word>(word: &str) -> Option<W> {
+    let digits = |digits: &str| digits.replace('_', "");
+
+    let value = if let Some(binary) = word.strip_prefix("0b") {
+        u64::from_str_radix(&digits(binary), 2).ok()
+    } else if let Some(hex) = word.strip_prefix("0x") {
+        u64::from_str_radix(&digits(hex), 16).ok()
+    } else {
+        word.parse().ok()
+    };
+
+    value.and_then(|value| W::try_from(value).ok())
+}
+
+/// Returns the symbol within an L-command such as '(LOOP)', or None if the command is not a
+/// valid L-command.
+///
+fn label_symbol(cmd: &str) -> Option<&str> {
+    cmd.strip_prefix('(')
+        .and_then(|rest| rest.strip_suffix(')'))
+        .filter(|symbol| is_symbol(symbol))
+}
+
+/// Returns true if the text is a valid symbol or decimal constant, made up of letters, digits and
+/// the characters '_', '.' and '$'.
+///
+fn is_symbol(text: &str) -> bool {
+    !text.is_empty() && text.chars().all(|c| c.is_ascii_alphanumeric() || "_.$".contains(c))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use std::io::Write;
-    use tempfile::NamedTempFile;
 
     fn temp_parser(text: &str) -> Parser {
+        Parser::from_source(text, SymbolTable::new())
+    }
+
+    #[cfg(feature = "std-io")]
+    #[test]
+    fn read_from_file() {
+        use std::io::Write;
+        use tempfile::NamedTempFile;
+
         let mut file = NamedTempFile::new().unwrap();
 
-        file.write_all(text.as_bytes()).unwrap();
+        file.write_all(b"@1\nD=A\n").unwrap();
 
-        let parser = Parser::new(file.path()).unwrap();
+        let mut parser = Parser::new(file.path(), SymbolTable::new()).unwrap();
 
-        parser
+        assert_eq!(parser.advance().unwrap(), 3);
+        assert_eq!(*parser.get_command(), Some(Command::ACommand(String::from("@1"))));
+        assert_eq!(parser.advance().unwrap(), 4);
+        assert_eq!(parser.advance().unwrap(), 0);
     }
 
     #[test]
@@ -329,6 +597,110 @@ mod tests {
         parser.advance().unwrap();
     }
 
+    #[test]
+    fn command_syntax_forms() {
+        let valid = [
+            "@", "@R0", "@a b", "M=1", "0;JMP", "AM=M-1;JNE", "(a_b.$1)", "D=alu(a=1,zx,no)",
+            "alu();JMP", "AM=alu(f);JNE",
+        ];
+        let invalid = [
+            "D", "=D", "M=", "D;", "A=M=1", "D;J;J", "M=D+2", "(LOOP", "(A B)", "()", "D=alu(zx",
+            "D=alu(z+x)", "alu(zx)",
+        ];
+
+        for cmd in valid.iter() {
+            assert!(temp_parser(cmd).advance().is_ok(), "{}", cmd);
+        }
+
+        for cmd in invalid.iter() {
+            assert!(temp_parser(cmd).advance().is_err(), "{}", cmd);
+        }
+    }
+
+    #[test]
+    fn directives() {
+        let mut parser = temp_parser("  .global  Main.run  count // Shared.\n@1\n");
+
+        parser.advance().unwrap();
+
+        assert_eq!(
+            *parser.get_command(),
+            Some(Command::Directive(String::from(".global  Main.run  count"))),
+        );
+        assert_eq!(
+            parser.directive().unwrap(),
+            (String::from(".global"), vec![String::from("Main.run"), String::from("count")]),
+        );
+        assert_eq!(parser.get_command_span(), Some(Span { line: 1, column: 3, length: 24 }));
+
+        parser.advance().unwrap();
+
+        assert_eq!(parser.directive().unwrap_err().kind(), Some(ErrorKind::InvalidCmdType));
+
+        let invalid = [
+            (".globl x", "unrecognised directive '.globl'"),
+            (".global", "'.global' must be followed by at least one symbol"),
+            (".global 1x", "'1x' is not a valid symbol"),
+            (".global a,b", "'a,b' is not a valid symbol"),
+            (".inst", "'.inst' must be followed by a single word"),
+            (".inst 1 2", "'.inst' must be followed by a single word"),
+            (".inst 0b12", "'0b12' is not a 16-bit word"),
+            (".inst 65536", "'65536' is not a 16-bit word"),
+        ];
+
+        for (cmd, message) in invalid.iter() {
+            let err = temp_parser(cmd).advance().unwrap_err();
+
+            assert_eq!(err.kind(), Some(ErrorKind::InvalidSyntax));
+            assert_eq!(err.message(), *message);
+        }
+
+        let words = [("0b1110_1111_1000_0000", 0xef80), ("0xEF80", 0xef80), ("61312", 0xef80)];
+
+        for (word, value) in words.iter() {
+            let mut parser = temp_parser(&format!(".inst {}\n", word));
+
+            parser.advance().unwrap();
+
+            assert_eq!(parser.instruction_word(), Some(*value));
+        }
+
+        assert_eq!(parser.instruction_word(), None);
+    }
+
+    #[test]
+    fn parse_commands() {
+        let commands = commands("// Comment.\n@i\n\n(LOOP)\nD;JGT\n").unwrap();
+
+        assert_eq!(
+            commands,
+            vec![
+                (Command::ACommand(String::from("@i")), Span { line: 2, column: 1, length: 2 }),
+                (Command::LCommand(String::from("(LOOP)")), Span { line: 4, column: 1, length: 6 }),
+                (Command::CCommand(String::from("D;JGT")), Span { line: 5, column: 1, length: 5 }),
+            ],
+        );
+
+        let err = super::commands("@1\nD=\n").unwrap_err();
+
+        assert_eq!(err.span(), Some(Span { line: 2, column: 1, length: 2 }));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_commands() {
+        let commands = commands("@2\n(END)\nM=D\n").unwrap();
+        let json = serde_json::to_string(&commands).unwrap();
+
+        assert_eq!(
+            json,
+            "[[{\"ACommand\":\"@2\"},{\"line\":1,\"column\":1,\"length\":2}],\
+                [{\"LCommand\":\"(END)\"},{\"line\":2,\"column\":1,\"length\":5}],\
+                [{\"CCommand\":\"M=D\"},{\"line\":3,\"column\":1,\"length\":3}]]",
+        );
+        assert_eq!(serde_json::from_str::<Vec<(Command, Span)>>(&json).unwrap(), commands);
+    }
+
     #[test]
     fn retrieve_symbol() {
         let mut parser = temp_parser("\
@@ -467,7 +839,7 @@ mod tests {
             ($TEST.LOOP_1)  // Example L-command with label symbol.\n\
             ");
         
-        let expected = vec![
+        let expected = [
             "@VAR_1.$TEST    // Example A-command with variable symbol.\n",
             "($TEST.LOOP_1)  // Example L-command with label symbol.\n",
         ];
@@ -480,9 +852,52 @@ mod tests {
 
         parser.reset();
         assert_eq!(parser.get_raw_line(), "");
+        assert_eq!(parser.get_line_number(), 0);
         assert_eq!(*parser.get_command(), None);
 
         parser.advance().unwrap();
         assert_eq!(parser.get_raw_line(), expected[0]);
     }
+
+    #[test]
+    fn command_spans() {
+        let mut parser = temp_parser("\
+            // Comment line.\n\
+            \n\
+            \t  D=M+1 // Indented C-command.\n\
+            (LOOP)\n\
+            ");
+
+        parser.advance().unwrap();
+        assert_eq!(parser.get_line_number(), 1);
+        assert_eq!(parser.get_command_span(), None);
+
+        parser.advance().unwrap();
+        parser.advance().unwrap();
+        assert_eq!(parser.get_line_number(), 3);
+        assert_eq!(
+            parser.get_command_span(),
+            Some(Span { line: 3, column: 4, length: 5 }),
+        );
+
+        parser.advance().unwrap();
+        assert_eq!(
+            parser.get_command_span(),
+            Some(Span { line: 4, column: 1, length: 6 }),
+        );
+    }
+
+    #[test]
+    fn syntax_error_span() {
+        let mut parser = temp_parser("\
+            @1\n\
+            \x20\x20notacommand\n\
+            ");
+
+        parser.advance().unwrap();
+
+        let error = parser.advance().unwrap_err();
+
+        assert_eq!(error.span(), Some(Span { line: 2, column: 3, length: 11 }));
+    }
 }
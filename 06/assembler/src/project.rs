@@ -0,0 +1,312 @@
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use toml::{Table, Value};
+use crate::error::{Error, ErrorKind, Result, Span};
+use crate::output::OutputFormat;
+use crate::symbols::{PredefinedSymbols, VARIABLE_LIMIT};
+
+/// The name of the project configuration file, which is searched for in the directory of each
+/// input file and then in each of its ancestors.
+///
+pub const FILE_NAME: &str = "hackasm.toml";
+
+/// How the assembler responds when a lint finds a problem.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum LintLevel {
+    /// The problem is not reported.
+    Allow,
+    /// The problem is reported as a warning.
+    Warn,
+    /// The problem is reported as an error.
+    Deny,
+}
+
+impl FromStr for LintLevel {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<LintLevel> {
+        match s {
+            "allow" => Ok(LintLevel::Allow),
+            "warn" => Ok(LintLevel::Warn),
+            "deny" => Ok(LintLevel::Deny),
+            _ => Err(Error::with_message(
+                ErrorKind::InvalidProjectFile,
+                format!("invalid lint level '{}', only 'allow', 'warn' or 'deny' accepted", s),
+            )),
+        }
+    }
+}
+
+/// Per-project settings read from a 'hackasm.toml' file.  Settings that are absent are None or
+/// empty, so that they can be merged with command line options and defaults.
+///
+/// Relative paths in the file are resolved from the directory containing it.
+///
+/// # Examples
+///
+/// '''
+/// format = "hex"
+/// out-dir = "build"
+/// cache-dir = ".hackcache"
+/// predefined-symbols = "hack"
+/// variable-base = 32
+/// memory-map = "machine.hmap"
+///
+/// [defines]
+/// WIDTH = 32
+///
+/// [lints]
+/// unused-label = "deny"
+/// '''
+#[derive(Debug, Default, PartialEq)]
+pub struct Project {
+    pub format: Option<OutputFormat>,
+    pub out_dir: Option<PathBuf>,
+    pub cache_dir: Option<PathBuf>,
+    pub defines: Vec<(String, u16)>,
+    pub predefined_symbols: Option<PredefinedSymbols>,
+    pub variable_base: Option<u16>,
+    pub memory_map: Option<PathBuf>,
+    pub lints: Vec<(String, LintLevel)>,
+}
+
+impl Project {
+    /// Searches for a project file in the given directory and each of its ancestors, returning
+    /// the settings from the nearest one found.
+    ///
+    /// The directory need not exist, in which case its ancestors are still searched.  Returns
+    /// Ok(None) if there is no project file.
+    ///
+    pub fn discover(dir: &Path) -> Result<Option<Project>> {
+        let dir = match dir {
+            dir if dir == Path::new("") => Path::new("."),
+            dir => dir,
+        };
+        let dir = std::path::absolute(dir).map_err(|e| Error::from(e).with_file(dir))?;
+
+        for ancestor in dir.ancestors() {
+            let path = ancestor.join(FILE_NAME);
+
+            if path.is_file() {
+                return Project::load(&path).map(Some);
+            }
+        }
+
+        Ok(None)
+    }
+
+    /// Reads the settings from a project file.  Errors are returned with the path of the file,
+    /// and the location of the problem where it is known.
+    ///
+    pub fn load(path: &Path) -> Result<Project> {
+        let text = fs::read_to_string(path).map_err(|e| Error::from(e).with_file(path))?;
+        let dir = path.parent().unwrap_or_else(|| Path::new(""));
+
+        Project::parse(&text, dir).map_err(|e| e.with_file(path))
+    }
+
+    /// Parses the settings from the text of a project file, resolving relative paths from the
+    /// given directory.
+    ///
+    pub fn parse(text: &str, dir: &Path) -> Result<Project> {
+        let table = text.parse::<Table>().map_err(|e| {
+            let span = e.span().map(|range| span_of(text, range.start, range.end));
+
+            Error::with_message(ErrorKind::InvalidProjectFile, String::from(e.message().trim()))
+                .with_span(span)
+        })?;
+
+        let mut project = Project::default();
+
+        for (key, value) in table.iter() {
+            match key.as_str() {
+                "format" => project.format = Some(string(key, value)?.parse()?),
+                "out-dir" => project.out_dir = Some(dir.join(string(key, value)?)),
+                "cache-dir" => project.cache_dir = Some(dir.join(string(key, value)?)),
+                "predefined-symbols" => {
+                    project.predefined_symbols = Some(string(key, value)?.parse()?);
+                },
+                "variable-base" => {
+                    let base = integer(key, value, VARIABLE_LIMIT)?;
+
+                    project.variable_base = Some(base);
+                },
+                "memory-map" => project.memory_map = Some(dir.join(string(key, value)?)),
+                "defines" => {
+                    for (name, value) in table_of(key, value)?.iter() {
+                        project.defines.push((name.clone(), integer(name, value, 0x7fff)?));
+                    }
+                },
+                "lints" => {
+                    for (name, level) in table_of(key, value)?.iter() {
+                        project.lints.push((name.clone(), string(name, level)?.parse()?));
+                    }
+                },
+                _ => {
+                    return Err(Error::with_message(
+                        ErrorKind::InvalidProjectFile,
+                        format!("unrecognised setting '{}'", key),
+                    ));
+                },
+            }
+        }
+
+        if project.memory_map.is_some()
+            && (project.predefined_symbols.is_some() || project.variable_base.is_some())
+        {
+            return Err(Error::with_message(
+                ErrorKind::InvalidProjectFile,
+                String::from("'memory-map' cannot be used with 'predefined-symbols' or \
+                    'variable-base'"),
+            ).with_help(String::from("set the predefined symbols and variables in the memory \
+                map")));
+        }
+
+        Ok(project)
+    }
+}
+
+fn string<'a>(key: &str, value: &'a Value) -> Result<&'a str> {
+    value.as_str().ok_or_else(|| invalid_type(key, "a string"))
+}
+
+fn table_of<'a>(key: &str, value: &'a Value) -> Result<&'a Table> {
+    value.as_table().ok_or_else(|| invalid_type(key, "a table"))
+}
+
+/// Returns the value of an integer setting, which must lie between 0 and 'max' inclusive.
+///
+fn integer(key: &str, value: &Value, max: u16) -> Result<u16> {
+    match value.as_integer() {
+        Some(n) if (0..=max as i64).contains(&n) => Ok(n as u16),
+        Some(n) => Err(Error::with_message(
+            ErrorKind::InvalidProjectFile,
+            format!("the value {} for '{}' is out of range, it must be from 0 to {}", n, key, max),
+        )),
+        None => Err(invalid_type(key, "an integer")),
+    }
+}
+
+fn invalid_type(key: &str, expected: &str) -> Error {
+    Error::with_message(
+        ErrorKind::InvalidProjectFile,
+        format!("the value for '{}' must be {}", key, expected),
+    )
+}
+
+/// Converts a range of byte offsets within the text into a Span, which counts in characters.
+///
+fn span_of(text: &str, start: usize, end: usize) -> Span {
+    let before = &text[..start];
+    let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+    let line_end = text[start..].find('\n').map_or(text.len(), |i| start + i);
+
+    Span {
+        line: before.matches('\n').count() + 1,
+        column: text[line_start..start].chars().count() + 1,
+        length: std::cmp::max(1, text[start..end.min(line_end)].chars().count()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parse_project() {
+        let text = "\
+            format = \"hex\"\n\
+            out-dir = \"build\"\n\
+            cache-dir = \".hackcache\"\n\
+            predefined-symbols = \"none\"\n\
+            variable-base = 32\n\
+            \n\
+            [defines]\n\
+            WIDTH = 32\n\
+            \n\
+            [lints]\n\
+            unused-label = \"deny\"\n\
+        ";
+
+        assert_eq!(
+            Project::parse(text, Path::new("proj")).unwrap(),
+            Project {
+                format: Some(OutputFormat::Hex),
+                out_dir: Some(PathBuf::from("proj/build")),
+                cache_dir: Some(PathBuf::from("proj/.hackcache")),
+                defines: vec![(String::from("WIDTH"), 32)],
+                predefined_symbols: Some(PredefinedSymbols::None),
+                variable_base: Some(32),
+                memory_map: None,
+                lints: vec![(String::from("unused-label"), LintLevel::Deny)],
+            },
+        );
+    }
+
+    #[test]
+    fn parse_invalid_project() {
+        let err = Project::parse("format = \"hex\"\nout-dir = 3\n", Path::new("")).unwrap_err();
+
+        assert_eq!(err.message(), "the value for 'out-dir' must be a string");
+
+        let err = Project::parse("format = \"elf\"\n", Path::new("")).unwrap_err();
+
+        assert_eq!(err.kind(), Some(ErrorKind::InvalidOutputFormat));
+
+        let err = Project::parse("variable-base = 20000\n", Path::new("")).unwrap_err();
+
+        assert_eq!(
+            err.message(),
+            "the value 20000 for 'variable-base' is out of range, it must be from 0 to 16383",
+        );
+
+        let err = Project::parse("[lints]\nx = \"loud\"\n", Path::new("")).unwrap_err();
+
+        assert_eq!(
+            err.message(),
+            "invalid lint level 'loud', only 'allow', 'warn' or 'deny' accepted",
+        );
+
+        let err = Project::parse("formats = \"hex\"\n", Path::new("")).unwrap_err();
+
+        assert_eq!(err.message(), "unrecognised setting 'formats'");
+
+        let err = Project::parse("include-paths = [\"lib\"]\n", Path::new("")).unwrap_err();
+
+        assert_eq!(err.message(), "unrecognised setting 'include-paths'");
+
+        let text = "memory-map = \"machine.hmap\"\nvariable-base = 32\n";
+        let err = Project::parse(text, Path::new("")).unwrap_err();
+
+        assert_eq!(
+            err.message(),
+            "'memory-map' cannot be used with 'predefined-symbols' or 'variable-base'",
+        );
+    }
+
+    #[test]
+    fn syntax_error_span() {
+        let err = Project::parse("format = \"hex\"\nout-dir = \n", Path::new("")).unwrap_err();
+
+        assert_eq!(err.kind(), Some(ErrorKind::InvalidProjectFile));
+        assert_eq!(err.span().unwrap().line, 2);
+    }
+
+    #[test]
+    fn discover_project() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("src/deeper");
+
+        fs::create_dir_all(&nested).unwrap();
+
+        assert_eq!(Project::discover(&nested).unwrap(), None);
+
+        fs::write(dir.path().join(FILE_NAME), "format = \"bin\"\n").unwrap();
+
+        let project = Project::discover(&nested).unwrap().unwrap();
+
+        assert_eq!(project.format, Some(OutputFormat::Binary));
+    }
+}
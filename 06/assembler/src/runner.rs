@@ -1,29 +1,161 @@
-use std::fs::File;
+use std::convert::TryFrom;
+#[cfg(feature = "std-io")]
+use std::fs::{self, File};
+#[cfg(feature = "std-io")]
 use std::io::{BufWriter, Write};
-use std::path::Path;
+#[cfg(feature = "std-io")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "std-io")]
+use std::process;
+#[cfg(feature = "std-io")]
+use std::sync::atomic::{AtomicUsize, Ordering};
 use log::{self, Level, log_enabled};
-use crate::code_translator;
+use crate::code_translator::{Hack, InstructionSet};
+#[cfg(feature = "cli")]
 use crate::config::Config;
-use crate::error::Result;
+use crate::error::{Error, ErrorKind, Result};
+#[cfg(feature = "cli")]
+use crate::{cache, object, output};
 use crate::parser::{Command, Parser};
+use crate::symbols::SymbolTable;
 
-/// Makes two passes through the input file.  First the symbol table is populated with entries
-/// from L-pseudocommands.  In the second pass, A- and C-commands are translated into binary
-/// machine instructions and written out to an output file.
+/// The machine instructions produced by assembling a source file, along with the symbol table
+/// built up whilst doing so.
+///
+/// 'lines' holds the source line number, counting from 1, of each instruction in 'words'.  The
+/// words are 16-bit, as in standard Hack, unless the program was assembled in an instruction set
+/// with another Word.
+///
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct Assembly<W = u16> {
+    pub words: Vec<W>,
+    pub lines: Vec<usize>,
+    pub symbols: SymbolTable,
+}
+
+/// Assembles the input file and writes the output files requested by the Config.
+///
+/// When 'config.object' is set, the output file is a relocatable object rather than machine code,
+/// whose module is named after the input file, and the Assembly returned is that of the unlinked
+/// code.
+///
+/// The output file, and any listing or symbol file requested, are only written once both passes
+/// have succeeded, so a failed assembly never leaves a partial or truncated file behind.  When
+/// 'config.check' is set no files are written at all.
+///
+/// Returns a Result containing the Assembly if execution completes without error.
+///
+#[cfg(feature = "cli")]
+pub fn run(config: Config) -> Result<Assembly> {
+    let path = config.infile.as_path();
+    let source = fs::read_to_string(path).map_err(|e| Error::from(e).with_file(path))?;
+
+    let translated = match config.cache_dir {
+        Some(ref dir) if !config.check => {
+            cache::assemble(dir, &config, &source, || translate(&config, &source))
+        },
+        _ => translate(&config, &source),
+    };
+
+    let (assembly, output) = translated.map_err(|e| e.with_file(path))?;
+
+    if config.check {
+        return Ok(assembly);
+    }
+
+    if config.out_dir.is_some() {
+        create_parent_dirs(&config)?;
+    }
+
+    write_output(&config.outfile, &output).map_err(|e| e.with_file(&config.outfile))?;
+
+    if let Some(ref listing_path) = config.listing {
+        let listing = output::listing(&assembly, source.as_bytes()).map_err(|e| e.with_file(path))?;
+
+        write_output(listing_path, &listing).map_err(|e| e.with_file(listing_path))?;
+    }
+
+    if let Some(ref symbols_path) = config.symbols {
+        write_output(symbols_path, &output::symbols(&assembly))
+            .map_err(|e| e.with_file(symbols_path))?;
+    }
+
+    Ok(assembly)
+}
+
+/// Assembles the source text as the Config requests, returning the Assembly along with the
+/// contents of the output file.
+///
+#[cfg(feature = "cli")]
+fn translate(config: &Config, source: &str) -> Result<(Assembly, Vec<u8>)> {
+    let symbols = symbol_table(config)?;
+
+    match config.object {
+        true => {
+            let module = cache::module_name(&config.infile);
+            let (object, assembly) = object::assemble(source, &module, symbols)?;
+
+            Ok((assembly, object.to_string().into_bytes()))
+        },
+        false => {
+            let assembly = assemble(source, symbols)?;
+            let output = config.format.format(&assembly.words);
+
+            Ok((assembly, output))
+        },
+    }
+}
+
+/// Returns the symbol table that the Config starts assembling from, holding the predefined
+/// symbols of its memory map, or otherwise those it selects, and its defines.
+///
+#[cfg(feature = "cli")]
+pub(crate) fn symbol_table(config: &Config) -> Result<SymbolTable> {
+    let mut symbols = match config.memory_map {
+        Some(ref map) => SymbolTable::with_memory_map(map)?,
+        None => SymbolTable::with_predefined(config.predefined_symbols, config.variable_base),
+    };
+
+    for (name, value) in config.defines.iter() {
+        symbols.insert_constant(name, *value)?;
+    }
+
+    Ok(symbols)
+}
+
+/// Makes two passes through the source text, starting from the given symbol table.  First the
+/// symbol table is populated with entries from L-pseudocommands.  In the second pass, A- and
+/// C-commands are translated into binary machine instructions and collected in memory.
 ///
 /// Any symbolic A-commands encountered during the second pass are looked up in the symbol table
 /// and added if not already present.
 ///
-/// Returns Ok(()) if execution completes without error.
+/// Returns a Result containing the translated machine instructions and the resulting symbol
+/// table.
+///
+/// # Examples
 ///
-pub fn run(config: Config) -> Result<()> {
-    let path = Path::new(&config.infile);
-    let mut parser = Parser::new(path)?;
+/// '''
+/// use assembler::{runner, SymbolTable};
+///
+/// let assembly = runner::assemble("@2\nD=A\n", SymbolTable::new()).unwrap();
+///
+/// assert_eq!(assembly.words, vec![2, 0b1110_1100_0001_0000]);
+/// '''
+pub fn assemble(source: &str, symbols: SymbolTable) -> Result<Assembly> {
+    assemble_with(source, symbols, Hack)
+}
 
-    log::debug!("Parser initialised from input file path\n{:#?}", parser);
+/// Assembles the source text as 'assemble' does, encoding C-commands in the given instruction
+/// set rather than standard Hack.  The Assembly holds words of the instruction set.
+///
+pub fn assemble_with<I>(source: &str, symbols: SymbolTable, isa: I) -> Result<Assembly<I::Word>>
+    where I: InstructionSet
+{
+    let mut parser = Parser::with_instruction_set(source, symbols, isa);
 
-    let output_file = File::create(config.outfile).unwrap();
-    let mut output_writer = BufWriter::new(&output_file);
+    log::debug!("Parser initialised from source text\n{:#?}", parser);
 
     first_pass(&mut parser)?;
 
@@ -31,22 +163,95 @@ pub fn run(config: Config) -> Result<()> {
 
     log::debug!("Parser reset.\n{:#?}", parser);
 
-    second_pass(&mut parser, &mut output_writer)?;
+    let (words, lines) = second_pass(&mut parser, |parser, _| translate_a_cmd(parser))?;
 
     log::debug!("Parser after both passes completed\n{:#?}", parser);
 
-    output_writer.flush()?;
+    Ok(Assembly { words, lines, symbols: parser.into_symbol_table() })
+}
+
+/// Reads the input file and assembles it as 'assemble' does, without writing any output.
+///
+/// Errors are returned with the path of the input file attached.
+///
+#[cfg(feature = "std-io")]
+pub fn assemble_file(path: &Path, symbols: SymbolTable) -> Result<Assembly> {
+    let source = fs::read_to_string(path).map_err(|e| Error::from(e).with_file(path))?;
+
+    assemble(&source, symbols).map_err(|e| e.with_file(path))
+}
+
+/// Creates any missing directories in the paths of the output files.  This is only done when an
+/// output directory is configured, as the layout below it is then chosen by the assembler.
+///
+#[cfg(feature = "cli")]
+fn create_parent_dirs(config: &Config) -> Result<()> {
+    let outputs = [Some(&config.outfile), config.listing.as_ref(), config.symbols.as_ref()];
+
+    for path in outputs.iter().flatten() {
+        if let Some(dir) = path.parent() {
+            fs::create_dir_all(dir).map_err(|e| Error::from(e).with_file(dir))?;
+        }
+    }
 
     Ok(())
 }
 
+/// Writes the assembled output to a temporary file in the same directory as the output path and
+/// then renames it into place.
+///
+/// The rename is atomic on the same filesystem, so readers of the output path see either the
+/// previous file or the complete new one.  The temporary file is removed if any step fails.
+///
+/// Returns Ok(()) if execution completes without error.
+///
+#[cfg(feature = "std-io")]
+pub fn write_output(outfile: &Path, contents: &[u8]) -> Result<()> {
+    let temp_path = temp_output_path(outfile);
+
+    log::debug!("Writing output to temporary file {:?}", temp_path);
+
+    let written = File::create(&temp_path).and_then(|file| {
+        let mut writer = BufWriter::new(file);
+
+        writer.write_all(contents)?;
+        writer.flush()?;
+        writer.get_ref().sync_all()
+    });
+
+    if let Err(e) = written.and_then(|_| fs::rename(&temp_path, outfile)) {
+        let _ = fs::remove_file(&temp_path);
+        return Err(e.into());
+    }
+
+    Ok(())
+}
+
+/// Returns the path of the hidden temporary file used while writing the given output path.
+///
+/// The name holds the process id and a count of the calls made by this process, so that threads
+/// writing the same output path at once never share a temporary file.
+///
+#[cfg(feature = "std-io")]
+fn temp_output_path(outfile: &Path) -> PathBuf {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    let file_name = outfile
+        .file_name()
+        .map(|name| name.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    let count = COUNT.fetch_add(1, Ordering::Relaxed);
+
+    outfile.with_file_name(format!(".{}.{}.{}.tmp", file_name, process::id(), count))
+}
+
 /// Takes a Parser object and advances line-by-line through the input file buffered within it.
 ///
 /// Each line is processed for present L-pseudocommands,
 ///
 /// Returns Ok(0) if execution completes without error.
 ///
-fn first_pass(parser: &mut Parser) -> Result<u8> {
+pub(crate) fn first_pass<I: InstructionSet>(parser: &mut Parser<I>) -> Result<u8> {
     loop {
         match parser.advance()? {
             0 => {
@@ -57,15 +262,17 @@ fn first_pass(parser: &mut Parser) -> Result<u8> {
                 if log_enabled!(Level::Debug) {
                     log::debug!("\
                         >>> First pass: Parser advanced to next line. <<<\n\
+                        LINE NUMBER: {}\n\
                         RAW LINE READ: {:?}\n\
                         COMMAND: {:?}\
                         ",
+                        parser.get_line_number(),
                         parser.get_raw_line().trim(),
                         parser.get_command(),
                     );
                 }
 
-                process_l_cmd(parser)?;
+                process_l_cmd(parser).map_err(|e| e.with_span(parser.get_command_span()))?;
             },
         }
     }
@@ -75,11 +282,11 @@ fn first_pass(parser: &mut Parser) -> Result<u8> {
 
 /// Adds a new label symbol to the symbol table with the current ROM address upon finding an
 /// L-pseudocommand.  Increments the ROM address when an A- or C-command is found, or does nothing
-/// if no command is present.
+/// if a directive or no command is present.
 ///
 /// Returns Ok(0) if execution completes without error.
 ///
-fn process_l_cmd(parser: &mut Parser) -> Result<u8> {
+fn process_l_cmd<I: InstructionSet>(parser: &mut Parser<I>) -> Result<u8> {
     match parser.get_command() {
         Some(Command::LCommand(_)) => {
             let symbol = parser.symbol().unwrap();
@@ -93,10 +300,13 @@ fn process_l_cmd(parser: &mut Parser) -> Result<u8> {
 
             parser.insert_label(&symbol[..])?;
         },
+        Some(Command::Directive(_)) if parser.instruction_word().is_none() => {
+            log::debug!("Directive. Does not occupy ROM.");
+        },
         Some(_) => {
-            parser.inc_rom_address();
+            parser.inc_rom_address()?;
 
-            log::debug!("Not an L-commmand (A or C-Command). Increment ROM address.");
+            log::debug!("Not an L-commmand (A-, C-Command or '.inst'). Increment ROM address.");
         },
         None => {
             log::debug!("Not a command. Continue to next line.");
@@ -109,13 +319,22 @@ fn process_l_cmd(parser: &mut Parser) -> Result<u8> {
 /// Takes a Parser object and advances line-by-line through the input file buffered within it.
 ///
 /// An attempt is made to translate each line into a binary machine instruction,  If successful
-/// the instruction is written to the output writer.
+/// the instruction is appended to the output words.
 ///
-/// Returns Ok(0) if execution completes without error.
+/// C-commands are encoded in the instruction set of the parser.  A-commands are translated by the
+/// given function, which is passed the parser and the ROM address, relative to the start of the
+/// program, of the instruction being translated.
+///
+/// Returns a Result containing the machine instructions along with the source line number of
+/// each.
 ///
-fn second_pass<W>(parser: &mut Parser, output_writer: &mut W) -> Result<u8>
-    where W: Write
+pub(crate) fn second_pass<I, F>(parser: &mut Parser<I>, mut translate_a: F)
+    -> Result<(Vec<I::Word>, Vec<usize>)>
+    where I: InstructionSet, F: FnMut(&mut Parser<I>, u16) -> Result<I::Word>
 {
+    let mut words = Vec::new();
+    let mut lines = Vec::new();
+
     loop {
         match parser.advance()? {
             0 => {
@@ -126,15 +345,21 @@ fn second_pass<W>(parser: &mut Parser, output_writer: &mut W) -> Result<u8>
                 if log_enabled!(Level::Debug) {
                     log::debug!("\
                         >>> Second pass: Parser advanced to next line. <<<\n\
+                        LINE NUMBER: {}\n\
                         RAW LINE READ: {:?}\n\
                         COMMAND: {:?}\
                         ",
+                        parser.get_line_number(),
                         parser.get_raw_line().trim(),
                         parser.get_command(),
                     );
                 }
 
-                let line = match translate_line(parser)? {
+                let address = words.len() as u16;
+                let translated = translate_line(parser, |parser| translate_a(parser, address))
+                    .map_err(|e| e.with_span(parser.get_command_span()))?;
+
+                let line = match translated {
                     Some(b) => b,
                     None => continue,
                 };
@@ -144,24 +369,36 @@ fn second_pass<W>(parser: &mut Parser, output_writer: &mut W) -> Result<u8>
                     MACHINE INSTRUCTION: {:016b}\
                     ", line);
 
-                writeln!(output_writer, "{:016b}", line)?;
+                words.push(line);
+                lines.push(parser.get_line_number());
             },
         };
     }
 
-    Ok(0)
+    Ok((words, lines))
 }
 
 /// Takes the current command and, if it is an A- or C-command, translates it into a binary machine
-/// instruction.
+/// instruction.  The word of an '.inst' directive is taken as it is.
+///
+/// C-commands are encoded in the instruction set of the parser, and A-commands are translated by
+/// the given function.
 ///
 /// Returns a result with an option that contains the instruction, or None if an A- or C-command
-/// was not present.
+/// or '.inst' directive was not present.
 ///
-fn translate_line(parser: &mut Parser) -> Result<Option<u16>> {
+fn translate_line<I, F>(parser: &mut Parser<I>, translate_a: F) -> Result<Option<I::Word>>
+    where I: InstructionSet, F: FnOnce(&mut Parser<I>) -> Result<I::Word>
+{
+    if let Some(word) = parser.instruction_word() {
+        log::debug!("'.inst' directive. Emit its word unchanged.");
+
+        return Ok(Some(word));
+    }
+
     let instruction = match parser.get_command() {
         Some(Command::ACommand(_)) => {
-            translate_a_cmd(parser)?
+            translate_a(parser)?
         },
         Some(Command::CCommand(_)) => {
             translate_c_cmd(parser)?
@@ -179,9 +416,9 @@ fn translate_line(parser: &mut Parser) -> Result<Option<u16>> {
 ///
 /// The supplied SymbolTable is referenced or updated as needed when symbolic A-Commands are found.
 ///
-/// Returns a result containing the 16-bit machine instruction.
+/// Returns a result containing the machine instruction.
 ///
-fn translate_a_cmd(parser: &mut Parser) -> Result<u16> {
+fn translate_a_cmd<I: InstructionSet>(parser: &mut Parser<I>) -> Result<I::Word> {
     if log_enabled!(Level::Debug) {
         log::debug!("\
             A-Command\n\
@@ -193,32 +430,54 @@ fn translate_a_cmd(parser: &mut Parser) -> Result<u16> {
 
     let symbol = parser.symbol()?;
 
-    // Check for u16, a label/variable symbol that needs to be looked up, or a variable symbol
-    // that needs to be added.
-    match symbol.parse::<u16>() {
-        Ok(b) => return Ok(b),
-        Err(_) => {
-            match parser.get_symbol_address(&symbol) {
-                Some(b) => return Ok(b),
-                None => {
-                    log::debug!("New variable. Adding to symbol table.");
+    // Check for a constant, a label/variable symbol that needs to be looked up, or a variable
+    // symbol that needs to be added.
+    if let Some(b) = constant(&symbol, parser.instruction_set())? {
+        return Ok(b);
+    }
 
-                    let b = parser.insert_variable(&symbol[..])?;
+    match parser.get_symbol_address(&symbol) {
+        Some(b) => Ok(I::Word::from(b)),
+        None => {
+            log::debug!("New variable. Adding to symbol table.");
 
-                    parser.inc_ram_address()?;
+            let b = parser.insert_variable(&symbol[..])?;
 
-                    return Ok(b);
-                },
-            }
+            parser.inc_ram_address()?;
+
+            Ok(I::Word::from(b))
         },
     }
 }
 
-/// Translates a C-command into a binary machine instruction.
+/// Returns the instruction that loads the constant of an A-command, or None if the A-command
+/// refers to a symbol instead.
 ///
-/// Returns a result containing the 16-bit machine instruction.
+/// Returns an error if the constant is larger than an A-instruction of the instruction set can
+/// load.
 ///
-fn translate_c_cmd(parser: &mut Parser) -> Result<u16> {
+pub(crate) fn constant<I: InstructionSet>(symbol: &str, isa: &I) -> Result<Option<I::Word>> {
+    if !symbol.chars().all(|c| c.is_ascii_digit()) {
+        return Ok(None);
+    }
+
+    let limit = isa.a_limit();
+
+    match symbol.parse::<u64>().ok().filter(|&value| value <= limit) {
+        Some(value) => Ok(I::Word::try_from(value).ok()),
+        None => Err(Error::with_message(
+            ErrorKind::InvalidSyntax,
+            format!("the constant {} is too large, an A-instruction can load up to {}",
+                symbol, limit),
+        )),
+    }
+}
+
+/// Translates a C-command into a binary machine instruction of the parser's instruction set.
+///
+/// Returns a result containing the machine instruction.
+///
+fn translate_c_cmd<I: InstructionSet>(parser: &mut Parser<I>) -> Result<I::Word> {
     if log_enabled!(Level::Debug) {
         log::debug!("\
             C-Command\n\
@@ -232,42 +491,50 @@ fn translate_c_cmd(parser: &mut Parser) -> Result<u16> {
         );
     }
 
-    let mut instruction = 0b1110_0000_0000_0000;
-    
-    let dest = match parser.dest()? {
-        Some(ref s) => code_translator::dest(&s[..])?,
-        None => 0b0000_0000_0000_0000,
-    };
+    // Locate each field within the command so that errors point at the offending mnemonic.
+    let span = parser.get_command_span();
+    let field_span = |offset: usize, s: &str| span.map(|sp| sp.sub_span(offset, s.len()));
+
+    let dest = parser.dest()?;
+    let comp = parser.comp()?;
+    let jump = parser.jump()?;
 
-    let comp = match parser.comp()? {
-        Some(ref s) => code_translator::comp(&s[..])?,
-        None => 0b0000_0000_0000_0000,
+    let comp_offset = dest.as_ref().map_or(0, |s| s.len() + 1);
+    let isa = parser.instruction_set();
+    let none = I::Word::from(0u16);
+
+    let dest = match dest {
+        Some(ref s) => isa.dest(&s[..]).map_err(|e| e.with_span(field_span(0, s)))?,
+        None => none,
     };
 
-    let jump = match parser.jump()? {
-        Some(ref s) => code_translator::jump(&s[..])?,
-        None => 0b0000_0000_0000_0000,
+    let comp = match comp {
+        Some(ref s) => {
+            isa.comp(&s[..]).map_err(|e| e.with_span(field_span(comp_offset, s)))?
+        },
+        None => none,
     };
 
-    instruction += dest + comp + jump;
+    let jump = match jump {
+        Some(ref s) => {
+            let offset = span.map_or(0, |sp| sp.length - s.len());
 
-    return Ok(instruction);
+            isa.jump(&s[..]).map_err(|e| e.with_span(field_span(offset, s)))?
+        },
+        None => none,
+    };
+
+    Ok(comp | dest | jump)
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
-    //use std::io::Write;
-    use tempfile::NamedTempFile;
+    use crate::error::{ErrorKind, Span};
+    use crate::output::OutputFormat;
 
     fn temp_parser(text: &str) -> Parser {
-        let mut file = NamedTempFile::new().unwrap();
-
-        file.write_all(text.as_bytes()).unwrap();
-
-        let parser = Parser::new(file.path()).unwrap();
-
-        parser
+        Parser::from_source(text, SymbolTable::new())
     }
 
     #[test]
@@ -317,14 +584,15 @@ mod tests {
 
         // Mimic action of 'first_pass' function.
         for _ in 0..6 {
-            parser.inc_rom_address();
+            parser.inc_rom_address().unwrap();
         };
         parser.insert_label("LOOP_1").unwrap();
 
-        let mut output_buf: Vec<u8> = Vec::new();
+        let (words, lines) = second_pass(&mut parser, |parser, _| translate_a_cmd(parser)).unwrap();
 
-        second_pass(&mut parser, &mut output_buf).unwrap();
+        assert_eq!(lines, vec![1, 2, 3, 4, 5, 6]);
 
+        let output_buf = OutputFormat::Hack.format(&words);
         let output = String::from_utf8_lossy(&output_buf);
 
         println!("{:?}", output);
@@ -341,4 +609,147 @@ mod tests {
             output,
         );
     }
+
+    #[test]
+    fn second_pass_error_spans() {
+        let commands = vec![
+            ("MA=D+1", Span { line: 2, column: 1, length: 2 }),
+            ("  D=D+Q;JMP", Span { line: 2, column: 5, length: 3 }),
+            ("0;JMPX", Span { line: 2, column: 3, length: 4 }),
+        ];
+
+        for (cmd, expected) in commands {
+            let mut parser = temp_parser(&format!("@1\n{}\n", cmd));
+
+            let error = second_pass(&mut parser, |parser, _| translate_a_cmd(parser)).unwrap_err();
+
+            assert_eq!(error.span(), Some(expected));
+        }
+    }
+
+    #[test]
+    fn program_too_large() {
+        let map = "rom 2\n".parse().unwrap();
+
+        assert!(assemble("@1\n@2\n", SymbolTable::with_memory_map(&map).unwrap()).is_ok());
+
+        let err = assemble("@1\n@2\n(END)\n@END\n", SymbolTable::with_memory_map(&map).unwrap())
+            .unwrap_err();
+
+        assert_eq!(err.kind(), Some(ErrorKind::ROMFull));
+        assert_eq!(err.message(), "the program does not fit in ROM, which holds 2 words");
+        assert_eq!(err.span().map(|span| span.line), Some(4));
+    }
+
+    #[test]
+    fn assemble_source() {
+        let assembly = assemble("(LOOP)\n@i\n@LOOP\n0;JMP\n", SymbolTable::new()).unwrap();
+
+        assert_eq!(assembly.words, vec![16, 0, 0b1110_1010_1000_0111]);
+        assert_eq!(assembly.lines, vec![2, 3, 4]);
+        assert_eq!(assembly.symbols.get_address("i"), Some(16));
+
+        // Directives occupy no ROM, and '.global' has no effect on a single file.
+        let global = assemble(".global LOOP i\n(LOOP)\n@i\n@LOOP\n0;JMP\n", SymbolTable::new());
+
+        assert_eq!(global.unwrap().words, assembly.words);
+    }
+
+    #[test]
+    fn assemble_raw_instructions() {
+        let source = "\
+            .inst 0b1110_1111_1000_0000 // Undocumented: 1 + A.\n\
+            (END)\n\
+            D=alu(a=1,zx,nx,zy,ny,f);JGT\n\
+            @END\n\
+            ";
+
+        let assembly = assemble(source, SymbolTable::new()).unwrap();
+
+        assert_eq!(assembly.words, vec![0xef80, 0b1111_1111_1001_0001, 1]);
+        assert_eq!(assembly.lines, vec![1, 3, 4]);
+        assert_eq!(assembly.symbols.get_address("END"), Some(1));
+
+        let error = assemble("D=alu(a=1,zx,xz)\n", SymbolTable::new()).unwrap_err();
+
+        assert_eq!(error.kind(), Some(ErrorKind::InvalidComp));
+        assert_eq!(error.span(), Some(Span { line: 1, column: 3, length: 14 }));
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_assembly() {
+        let assembly = assemble("@x\nM=1\n", SymbolTable::new()).unwrap();
+        let json = serde_json::to_value(&assembly).unwrap();
+
+        assert_eq!(json["words"], serde_json::json!([16, 0b1110_1111_1100_1000]));
+        assert_eq!(json["lines"], serde_json::json!([1, 2]));
+        assert_eq!(json["symbols"]["variables"], serde_json::json!(["x"]));
+        assert_eq!(serde_json::from_value::<Assembly>(json).unwrap(), assembly);
+    }
+
+    #[cfg(feature = "cli")]
+    #[test]
+    fn check_writes_no_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let infile = dir.path().join("Test.asm");
+
+        fs::write(&infile, "@i\nM=1\n").unwrap();
+
+        let config = Config {
+            check: true,
+            listing: Some(dir.path().join("Test.lst")),
+            ..Config::for_input(&infile)
+        };
+
+        assert_eq!(run(config).unwrap().words, vec![16, 0b1110_1111_1100_1000]);
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(feature = "std-io")]
+    #[test]
+    fn write_output_replaces_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let outfile = dir.path().join("Test.hack");
+
+        fs::write(&outfile, "stale\n").unwrap();
+
+        write_output(&outfile, b"0000000000000000\n").unwrap();
+
+        assert_eq!(fs::read_to_string(&outfile).unwrap(), "0000000000000000\n");
+
+        // Only the output file should remain; the temporary file is renamed into place.
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(feature = "std-io")]
+    #[test]
+    fn write_output_from_many_threads() {
+        let dir = tempfile::tempdir().unwrap();
+        let outfile = dir.path().join("Test.hack");
+        let contents: Vec<String> = (0..8).map(|i| format!("{:016b}\n", i).repeat(512)).collect();
+
+        assert_ne!(temp_output_path(&outfile), temp_output_path(&outfile));
+
+        std::thread::scope(|scope| {
+            for contents in contents.iter() {
+                let outfile = &outfile;
+
+                scope.spawn(move || write_output(outfile, contents.as_bytes()).unwrap());
+            }
+        });
+
+        assert!(contents.contains(&fs::read_to_string(&outfile).unwrap()));
+        assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+    }
+
+    #[cfg(feature = "std-io")]
+    #[test]
+    fn write_output_to_missing_directory() {
+        let dir = tempfile::tempdir().unwrap();
+        let outfile = dir.path().join("missing").join("Test.hack");
+
+        assert!(write_output(&outfile, b"0000000000000000\n").is_err());
+        assert!(!outfile.exists());
+    }
 }
@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+use std::fmt;
+use crate::code_translator;
+use crate::runner::Assembly;
+
+/// The width, in characters, of the longest bar drawn in a histogram.
+///
+const BAR_WIDTH: usize = 40;
+
+/// A region of ROM that begins at a label and runs up to the next label, or to the end of the
+/// program.
+///
+#[derive(Debug, PartialEq)]
+pub struct Region {
+    pub label: String,
+    pub address: u16,
+    pub size: usize,
+}
+
+/// Statistics describing how an assembled program uses the Hack ROM and RAM.
+///
+/// The instruction mix is decoded from the assembled machine instructions, so it reflects
+/// exactly what was written to the output file.  Usage is measured against the ROM size and the
+/// range of variable addresses in the memory layout of the symbol table.
+///
+#[derive(Debug, PartialEq)]
+pub struct Stats {
+    pub rom_words: usize,
+    pub rom_size: usize,
+    pub variables: usize,
+    pub variable_space: usize,
+    pub a_instructions: usize,
+    pub c_instructions: usize,
+    pub comp: Vec<(String, usize)>,
+    pub dest: Vec<(String, usize)>,
+    pub jump: Vec<(String, usize)>,
+    pub regions: Vec<Region>,
+}
+
+impl Stats {
+    /// Gathers statistics from the machine instructions and symbol table of an Assembly.
+    ///
+    pub fn new(assembly: &Assembly) -> Stats {
+        let mut comp = HashMap::new();
+        let mut dest = HashMap::new();
+        let mut jump = HashMap::new();
+        let mut a_instructions = 0;
+
+        for &word in assembly.words.iter() {
+            if word & 0b1000_0000_0000_0000 == 0 {
+                a_instructions += 1;
+                continue;
+            }
+
+            let comp_mnemonic = code_translator::decode_comp(word).unwrap_or("?");
+
+            *comp.entry(comp_mnemonic).or_insert(0) += 1;
+            *dest.entry(code_translator::decode_dest(word)).or_insert(0) += 1;
+            *jump.entry(code_translator::decode_jump(word)).or_insert(0) += 1;
+        }
+
+        Stats {
+            rom_words: assembly.words.len(),
+            rom_size: assembly.symbols.layout().rom_size,
+            variables: assembly.symbols.variables().len(),
+            variable_space: assembly.symbols.layout().variable_space(),
+            a_instructions,
+            c_instructions: assembly.words.len() - a_instructions,
+            comp: sorted_counts(comp),
+            dest: sorted_counts(dest),
+            jump: sorted_counts(jump),
+            regions: regions(assembly),
+        }
+    }
+}
+
+/// Returns the counts sorted from most to least frequent, breaking ties alphabetically.
+///
+fn sorted_counts(counts: HashMap<&str, usize>) -> Vec<(String, usize)> {
+    let mut counts: Vec<(String, usize)> = counts
+        .into_iter()
+        .map(|(mnemonic, count)| (String::from(mnemonic), count))
+        .collect();
+
+    counts.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    counts
+}
+
+/// Divides the program into labelled regions.
+///
+/// Any instructions before the first label are grouped into a region named '(start)'.  Labels
+/// that share an address each describe the same region, so all but the last have a size of 0.
+///
+fn regions(assembly: &Assembly) -> Vec<Region> {
+    let mut labels = assembly.symbols.labels();
+
+    labels.sort_by_key(|&(_, address)| address);
+
+    let mut regions = Vec::new();
+
+    if labels.first().map_or(!assembly.words.is_empty(), |&(_, address)| address > 0) {
+        regions.push(Region { label: String::from("(start)"), address: 0, size: 0 });
+    }
+
+    for (label, address) in labels {
+        regions.push(Region { label: String::from(label), address, size: 0 });
+    }
+
+    let ends: Vec<usize> = regions.iter()
+        .skip(1)
+        .map(|r| r.address as usize)
+        .chain(std::iter::once(assembly.words.len()))
+        .collect();
+
+    for (region, end) in regions.iter_mut().zip(ends) {
+        region.size = end.saturating_sub(region.address as usize);
+    }
+
+    regions
+}
+
+impl fmt::Display for Stats {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "ROM: {} / {} words ({})",
+            self.rom_words, self.rom_size, percentage(self.rom_words, self.rom_size))?;
+        writeln!(f, "RAM: {} / {} variables ({})",
+            self.variables, self.variable_space,
+            percentage(self.variables, self.variable_space))?;
+        writeln!(f, "Instructions: {} A, {} C", self.a_instructions, self.c_instructions)?;
+
+        write_histogram(f, "comp", &self.comp)?;
+        write_histogram(f, "dest", &self.dest)?;
+        write_histogram(f, "jump", &self.jump)?;
+
+        writeln!(f, "\nLabelled regions:")?;
+
+        let width = self.regions.iter().map(|r| r.label.len()).max().unwrap_or(0);
+
+        for region in self.regions.iter() {
+            writeln!(f, "  {:<width$}  {:>5}  {:>5} words",
+                region.label, region.address, region.size, width = width)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Writes a titled histogram, with one bar per mnemonic scaled to the most frequent.
+///
+fn write_histogram(f: &mut fmt::Formatter, title: &str, counts: &[(String, usize)])
+    -> fmt::Result
+{
+    writeln!(f, "\n{}:", title)?;
+
+    let max = counts.iter().map(|&(_, count)| count).max().unwrap_or(0);
+    let width = counts.iter().map(|(mnemonic, _)| mnemonic.len()).max().unwrap_or(0);
+
+    for (mnemonic, count) in counts {
+        let bar = std::cmp::max(1, count * BAR_WIDTH / max);
+
+        writeln!(f, "  {:<width$}  {:>5}  {}",
+            mnemonic, count, "#".repeat(bar), width = width)?;
+    }
+
+    Ok(())
+}
+
+fn percentage(used: usize, total: usize) -> String {
+    match total {
+        0 => String::from("0.0%"),
+        _ => format!("{:.1}%", used as f64 * 100.0 / total as f64),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::symbols::{PredefinedSymbols, SymbolTable};
+
+    fn test_assembly() -> Assembly {
+        let mut symbols = SymbolTable::new();
+
+        symbols.insert_variable("i").unwrap();
+
+        for _ in 0..2 {
+            symbols.inc_rom_address().unwrap();
+        }
+        symbols.insert_label("LOOP").unwrap();
+
+        for _ in 0..3 {
+            symbols.inc_rom_address().unwrap();
+        }
+        symbols.insert_label("END").unwrap();
+
+        Assembly {
+            words: vec![
+                0b0000_0000_0001_0000,  // @i
+                0b1110_1111_1100_1000,  // M=1
+                0b0000_0000_0001_0000,  // @i
+                0b1111_1101_1100_1000,  // M=M+1
+                0b1110_1010_1000_0111,  // 0;JMP
+                0b1110_1010_1000_0111,  // 0;JMP
+            ],
+            lines: vec![1, 2, 4, 5, 7, 9],
+            symbols,
+        }
+    }
+
+    #[test]
+    fn gather_stats() {
+        let stats = Stats::new(&test_assembly());
+
+        assert_eq!(stats.rom_words, 6);
+        assert_eq!(stats.variables, 1);
+        assert_eq!(stats.a_instructions, 2);
+        assert_eq!(stats.c_instructions, 4);
+        assert_eq!(
+            stats.comp,
+            vec![(String::from("0"), 2), (String::from("1"), 1), (String::from("M+1"), 1)],
+        );
+        assert_eq!(stats.dest, vec![(String::from("M"), 2), (String::from("null"), 2)]);
+        assert_eq!(stats.jump, vec![(String::from("JMP"), 2), (String::from("null"), 2)]);
+        assert_eq!(
+            stats.regions,
+            vec![
+                Region { label: String::from("(start)"), address: 0, size: 2 },
+                Region { label: String::from("LOOP"), address: 2, size: 3 },
+                Region { label: String::from("END"), address: 5, size: 1 },
+            ],
+        );
+    }
+
+    #[test]
+    fn variable_base_above_limit() {
+        let assembly = Assembly {
+            words: vec![0b0000_0000_0000_0010],
+            lines: vec![1],
+            symbols: SymbolTable::with_predefined(PredefinedSymbols::Hack, 20000),
+        };
+        let stats = Stats::new(&assembly);
+
+        assert_eq!(stats.variable_space, 0);
+        assert!(stats.to_string().contains("RAM: 0 / 0 variables (0.0%)\n"));
+    }
+
+    #[test]
+    fn display_stats() {
+        let report = format!("{}", Stats::new(&test_assembly()));
+
+        assert!(report.starts_with("\
+            ROM: 6 / 32768 words (0.0%)\n\
+            RAM: 1 / 16368 variables (0.0%)\n\
+            Instructions: 2 A, 4 C\n\
+            "));
+        assert!(report.contains("\ncomp:\n  0        2  ########################################\n"));
+        assert!(report.contains("  M+1      1  ####################\n"));
+        assert!(report.contains("\nLabelled regions:\n  (start)      0      2 words\n"));
+        assert!(report.contains("  LOOP         2      3 words\n"));
+    }
+}
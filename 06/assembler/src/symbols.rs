@@ -1,17 +1,81 @@
+#[cfg(feature = "serde")]
+use std::collections::BTreeMap;
 use std::collections::HashMap;
+#[cfg(feature = "serde")]
+use std::convert::TryFrom;
+#[cfg(feature = "serde")]
+use std::result;
+use std::str::FromStr;
 use crate::error::{Error, ErrorKind, Result};
+use crate::memory::{Layout, MemoryMap};
+
+/// The number of instruction words in the Hack ROM.
+///
+pub const ROM_SIZE: usize = 32768;
+
+/// The RAM address allocated to the first variable symbol.
+///
+pub const VARIABLE_BASE: u16 = 16;
+
+/// The highest RAM address that can be allocated to a variable symbol, immediately before the
+/// screen memory map.
+///
+pub const VARIABLE_LIMIT: u16 = 16383;
+
+/// The sets of symbols that a SymbolTable can be initialised with.
+///
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(rename_all = "lowercase"))]
+pub enum PredefinedSymbols {
+    /// The virtual registers, pointers and memory-mapped I/O symbols of the Hack platform.
+    Hack,
+    /// No predefined symbols.
+    None,
+}
+
+impl FromStr for PredefinedSymbols {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<PredefinedSymbols> {
+        match s {
+            "hack" => Ok(PredefinedSymbols::Hack),
+            "none" => Ok(PredefinedSymbols::None),
+            _ => Err(Error::with_message(
+                ErrorKind::InvalidProjectFile,
+                format!("invalid predefined symbol set '{}', only 'hack' or 'none' accepted", s),
+            )),
+        }
+    }
+}
 
 /// The SymbolTable is a hashmap that holds both label and variable symbols along with their
 /// associated ROM or RAM address respectively.
 ///
 /// It also tracks the next available ROM and RAM addresses which are used when inserting a new
-/// symbol.
+/// symbol, and the order in which labels and variables were inserted.
 ///
-#[derive(Debug)]
+/// With the 'serde' feature the table can be serialized, with its symbols in alphabetical order
+/// so that snapshots of it are stable.  Deserializing fails if a label or variable has no
+/// address in the table.
+///
+#[derive(Debug, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(try_from = "SymbolTableFields"))]
 pub struct SymbolTable {
+    #[cfg_attr(feature = "serde", serde(serialize_with = "serialize_sorted"))]
     table: HashMap<String, u16>,
+    labels: Vec<String>,
+    variables: Vec<String>,
     ram_address: u16,
     rom_address: u16,
+    layout: Layout,
+}
+
+impl Default for SymbolTable {
+    fn default() -> SymbolTable {
+        SymbolTable::new()
+    }
 }
 
 impl SymbolTable {
@@ -22,46 +86,51 @@ impl SymbolTable {
     /// address is set to 0.
     ///
     pub fn new() -> SymbolTable {
+        SymbolTable::with_predefined(PredefinedSymbols::Hack, VARIABLE_BASE)
+    }
+
+    /// Creates a new SymbolTable instance, initialised with the given set of predefined symbols
+    /// and allocating variables from the given RAM address.
+    ///
+    pub fn with_predefined(predefined: PredefinedSymbols, variable_base: u16) -> SymbolTable {
+        let map = MemoryMap::with_predefined(predefined, variable_base);
+
+        // The symbols of the Hack platform are distinct, so need no checking.
+        SymbolTable::from_parts(map.symbols.into_iter().collect(), map.layout)
+    }
+
+    /// Creates a new SymbolTable instance, initialised with the predefined symbols of the memory
+    /// map, and allocating variables within its layout.
+    ///
+    /// Returns an 'InvalidMemoryMap' error if the memory map defines a symbol more than once.
+    ///
+    pub fn with_memory_map(map: &MemoryMap) -> Result<SymbolTable> {
         let mut table = HashMap::new();
-        
-        let predefined_symbols =
-            vec![(String::from("SP"), 0),
-                 (String::from("LCL"), 1),
-                 (String::from("ARG"), 2),
-                 (String::from("THIS"), 3),
-                 (String::from("THAT"), 4),
-                 (String::from("R0"), 0),
-                 (String::from("R1"), 1),
-                 (String::from("R2"), 2),
-                 (String::from("R3"), 3),
-                 (String::from("R4"), 4),
-                 (String::from("R5"), 5),
-                 (String::from("R6"), 6),
-                 (String::from("R7"), 7),
-                 (String::from("R8"), 8),
-                 (String::from("R9"), 9),
-                 (String::from("R10"), 10),
-                 (String::from("R11"), 11),
-                 (String::from("R12"), 12),
-                 (String::from("R13"), 13),
-                 (String::from("R14"), 14),
-                 (String::from("R15"), 15),
-                 (String::from("SCREEN"), 16384),
-                 (String::from("KBD"), 24576),
-            ];
 
         // Check for duplication.
-        for item in predefined_symbols {
-            match table.insert(item.0, item.1) {
-                Some(x) => panic!("Cannot add predefined symbol '{}' twice!", x),
-                None => continue,
+        for (name, address) in map.symbols.iter() {
+            if table.insert(name.clone(), *address).is_some() {
+                return Err(Error::with_message(
+                    ErrorKind::InvalidMemoryMap,
+                    format!("'{}' is defined more than once", name),
+                ));
             }
         }
 
+        Ok(SymbolTable::from_parts(table, map.layout.clone()))
+    }
+
+    /// Creates a new SymbolTable instance holding the given predefined symbols, with no labels
+    /// or variables yet.
+    ///
+    fn from_parts(table: HashMap<String, u16>, layout: Layout) -> SymbolTable {
         SymbolTable {
             table,
-            ram_address: 16, // Next available.
+            labels: Vec::new(),
+            variables: Vec::new(),
+            ram_address: layout.variable_base, // Next available.
             rom_address: 0,
+            layout,
         }
     }
 
@@ -78,7 +147,7 @@ impl SymbolTable {
     /// If all the RAM addresses have already been used, a 'RAMFull' error will be returned.
     ///
     pub fn inc_ram_address(&mut self) -> Result<u8> {
-        if self.ram_address == 16383 {
+        if self.ram_address == self.layout.variable_limit {
             return Err(Error::new(ErrorKind::RAMFull));
         }
 
@@ -87,20 +156,43 @@ impl SymbolTable {
         Ok(0)
     }
 
-    /// Increment next ROM address by 1.
+    /// Increments the next available ROM address by 1 and returns Ok(0).
+    ///
+    /// If the ROM address is already beyond the end of ROM, a 'ROMFull' error will be returned.
     ///
-    pub fn inc_rom_address(&mut self) {
+    pub fn inc_rom_address(&mut self) -> Result<u8> {
+        if usize::from(self.rom_address) >= self.layout.rom_size {
+            return Err(Error::with_message(
+                ErrorKind::ROMFull,
+                format!("the program does not fit in ROM, which holds {} words",
+                    self.layout.rom_size),
+            ));
+        }
+
         self.rom_address += 1;
+
+        Ok(0)
     }
 
     /// Takes an &str variable symbol as an argument and inserts it into the SymbolTable with the
     /// next available RAM address.
     ///
     /// Returns a result containing the RAM address.  It will return an error if the symbol already
-    /// exists in the SymbolTable.
+    /// exists in the SymbolTable, or if the address is reserved by the memory map.
     ///
     pub fn insert_variable(&mut self, symbol: &str) -> Result<u16> {
-        self.insert(symbol, self.ram_address)
+        if let Some(region) = self.layout.reserved_at(self.ram_address) {
+            return Err(Error::with_message(
+                ErrorKind::ReservedAddress,
+                format!("the variable '{}' would be allocated at {}, which is reserved for '{}'",
+                    symbol, self.ram_address, region.name),
+            ).with_help(format!("the memory map reserves addresses {} to {} for '{}', so fewer \
+                variables can be allocated", region.first, region.last, region.name)));
+        }
+
+        let address = self.insert(symbol, self.ram_address)?;
+        self.variables.push(String::from(symbol));
+        Ok(address)
     }
 
     /// Takes an &str label symbol as an argument and inserts it into the SymbolTable with the
@@ -110,19 +202,125 @@ impl SymbolTable {
     /// exists in the SymbolTable.
     ///
     pub fn insert_label(&mut self, symbol: &str) -> Result<u16> {
-        self.insert(symbol, self.rom_address)
+        let address = self.insert(symbol, self.rom_address)?;
+        self.labels.push(String::from(symbol));
+        Ok(address)
+    }
+
+    /// Takes an &str label symbol as an argument and inserts it into the SymbolTable with the
+    /// given ROM address, as when placing the labels of a linked module.
+    ///
+    /// Returns a result containing the ROM address.  It will return an error if the symbol already
+    /// exists in the SymbolTable.
+    ///
+    pub fn insert_label_at(&mut self, symbol: &str, address: u16) -> Result<u16> {
+        let address = self.insert(symbol, address)?;
+        self.labels.push(String::from(symbol));
+        Ok(address)
+    }
+
+    /// Inserts a symbol with a fixed value, such as one defined in the project configuration.
+    ///
+    /// Returns a result containing the value.  It will return an error if the symbol already
+    /// exists in the SymbolTable.
+    ///
+    pub fn insert_constant(&mut self, symbol: &str, value: u16) -> Result<u16> {
+        self.insert(symbol, value).map_err(|_| {
+            Error::with_message(
+                ErrorKind::SymbolExists,
+                format!("cannot define '{}', it is already a predefined symbol", symbol),
+            )
+        })
+    }
+
+    /// Returns true if the symbol was inserted as a label.
+    ///
+    pub fn is_label(&self, symbol: &str) -> bool {
+        self.labels.iter().any(|label| label == symbol)
+    }
+
+    /// Returns the layout of memory that the table allocates within.
+    ///
+    pub fn layout(&self) -> &Layout {
+        &self.layout
+    }
+
+    /// Returns the label symbols along with their ROM addresses, in the order they were
+    /// inserted.
+    ///
+    pub fn labels(&self) -> Vec<(&str, u16)> {
+        self.entries(&self.labels)
+    }
+
+    /// Returns the variable symbols along with their RAM addresses, in the order they were
+    /// inserted.
+    ///
+    pub fn variables(&self) -> Vec<(&str, u16)> {
+        self.entries(&self.variables)
+    }
+
+    fn entries<'a>(&'a self, symbols: &'a [String]) -> Vec<(&'a str, u16)> {
+        symbols.iter().map(|s| (&s[..], self.table[s])).collect()
     }
 
     fn insert(&mut self, symbol: &str, address: u16) -> Result<u16> {
         if self.table.contains_key(symbol) {
-            return Err(Error::new(ErrorKind::SymbolExists));
+            Err(Error::new(ErrorKind::SymbolExists))
         } else {
             self.table.insert(String::from(symbol), address);
-            return Ok(address);
+            Ok(address)
         }
     }
 }
 
+/// The fields of a SymbolTable as they are deserialized, before the labels and variables are
+/// checked against the table.
+///
+#[cfg(feature = "serde")]
+#[derive(serde::Deserialize)]
+struct SymbolTableFields {
+    table: HashMap<String, u16>,
+    labels: Vec<String>,
+    variables: Vec<String>,
+    ram_address: u16,
+    rom_address: u16,
+    layout: Layout,
+}
+
+#[cfg(feature = "serde")]
+impl TryFrom<SymbolTableFields> for SymbolTable {
+    type Error = String;
+
+    fn try_from(fields: SymbolTableFields) -> result::Result<SymbolTable, String> {
+        let missing = fields.labels.iter()
+            .chain(fields.variables.iter())
+            .find(|symbol| !fields.table.contains_key(*symbol));
+
+        if let Some(symbol) = missing {
+            return Err(format!("the symbol '{}' has no address in the table", symbol));
+        }
+
+        Ok(SymbolTable {
+            table: fields.table,
+            labels: fields.labels,
+            variables: fields.variables,
+            ram_address: fields.ram_address,
+            rom_address: fields.rom_address,
+            layout: fields.layout,
+        })
+    }
+}
+
+/// Serializes the symbol table's map with its symbols in alphabetical order.
+///
+#[cfg(feature = "serde")]
+fn serialize_sorted<S>(table: &HashMap<String, u16>, serializer: S)
+    -> result::Result<S::Ok, S::Error>
+    where S: serde::Serializer
+{
+    serializer.collect_map(table.iter().collect::<BTreeMap<_, _>>())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -175,7 +373,7 @@ mod tests {
 
         assert_eq!(ram_address, 17);
 
-        sym_table.inc_rom_address();
+        sym_table.inc_rom_address().unwrap();
 
         let rom_address = sym_table.insert_label("TEST_LABEL").unwrap();
 
@@ -195,6 +393,47 @@ mod tests {
         }
     }
 
+    #[test]
+    fn allocate_within_memory_map() {
+        let map = "rom 2\nvariables 100 103\nreserve DEVICE 101 101\n".parse().unwrap();
+        let mut sym_table = SymbolTable::with_memory_map(&map).unwrap();
+
+        assert_eq!(sym_table.get_address("SP"), None);
+        assert_eq!(sym_table.insert_variable("i").unwrap(), 100);
+        sym_table.inc_ram_address().unwrap();
+
+        let err = sym_table.insert_variable("j").unwrap_err();
+
+        assert_eq!(err.kind(), Some(ErrorKind::ReservedAddress));
+        assert_eq!(
+            err.message(),
+            "the variable 'j' would be allocated at 101, which is reserved for 'DEVICE'",
+        );
+
+        for _ in 0..2 {
+            sym_table.inc_ram_address().unwrap();
+        }
+        assert_eq!(sym_table.inc_ram_address().unwrap_err().kind(), Some(ErrorKind::RAMFull));
+
+        for _ in 0..2 {
+            sym_table.inc_rom_address().unwrap();
+        }
+        assert_eq!(sym_table.inc_rom_address().unwrap_err().kind(), Some(ErrorKind::ROMFull));
+    }
+
+    #[test]
+    fn duplicate_predefined_symbols() {
+        let map = MemoryMap {
+            symbols: vec![(String::from("SP"), 0), (String::from("SP"), 1)],
+            ..MemoryMap::default()
+        };
+
+        let err = SymbolTable::with_memory_map(&map).unwrap_err();
+
+        assert_eq!(err.kind(), Some(ErrorKind::InvalidMemoryMap));
+        assert_eq!(err.message(), "'SP' is defined more than once");
+    }
+
     #[test]
     fn verify_insert_variable() {
         let mut sym_table = SymbolTable::new();
@@ -226,4 +465,93 @@ mod tests {
             address,
         );
     }
+
+    #[test]
+    fn predefined_symbol_sets() {
+        let mut sym_table = SymbolTable::with_predefined(PredefinedSymbols::None, 32);
+
+        assert_eq!(sym_table.get_address("SCREEN"), None);
+        assert_eq!(sym_table.insert_variable("SCREEN").unwrap(), 32);
+        assert_eq!(sym_table.insert_constant("WIDTH", 512).unwrap(), 512);
+        assert!(sym_table.insert_constant("WIDTH", 256).is_err());
+        assert_eq!(sym_table.variables(), vec![("SCREEN", 32)]);
+
+        assert!(SymbolTable::new().insert_constant("KBD", 0).is_err());
+        assert_eq!("none".parse::<PredefinedSymbols>().unwrap(), PredefinedSymbols::None);
+        assert!("all".parse::<PredefinedSymbols>().is_err());
+    }
+
+    #[test]
+    fn list_labels_and_variables() {
+        let mut sym_table = SymbolTable::new();
+
+        sym_table.insert_label("START").unwrap();
+        sym_table.insert_variable("i").unwrap();
+        sym_table.inc_ram_address().unwrap();
+        sym_table.inc_rom_address().unwrap();
+        sym_table.insert_label("LOOP").unwrap();
+        sym_table.insert_variable("sum").unwrap();
+
+        assert!(sym_table.insert_label("i").is_err());
+
+        assert_eq!(sym_table.labels(), vec![("START", 0), ("LOOP", 1)]);
+        assert_eq!(sym_table.variables(), vec![("i", 16), ("sum", 17)]);
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_symbol_table() {
+        let mut sym_table = SymbolTable::with_predefined(PredefinedSymbols::None, 16);
+
+        sym_table.insert_variable("sum").unwrap();
+        sym_table.inc_ram_address().unwrap();
+        sym_table.insert_label("LOOP").unwrap();
+        sym_table.insert_variable("i").unwrap();
+
+        let json = serde_json::to_string(&sym_table).unwrap();
+
+        assert_eq!(
+            json,
+            "{\"table\":{\"LOOP\":0,\"i\":17,\"sum\":16},\"labels\":[\"LOOP\"],\
+                \"variables\":[\"sum\",\"i\"],\"ram_address\":17,\"rom_address\":0,\
+                \"layout\":{\"rom_size\":32768,\"variable_base\":16,\"variable_limit\":16383,\
+                \"reserved\":[]}}",
+        );
+        assert_eq!(serde_json::from_str::<SymbolTable>(&json).unwrap(), sym_table);
+        assert_eq!(serde_json::to_string(&PredefinedSymbols::Hack).unwrap(), "\"hack\"");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn deserialize_symbol_missing_from_table() {
+        let json = "{\"table\":{\"i\":16},\"labels\":[\"LOOP\"],\"variables\":[\"i\"],\
+            \"ram_address\":17,\"rom_address\":0,\"layout\":{\"rom_size\":32768,\"variable_base\":16,\
+            \"variable_limit\":16383,\"reserved\":[]}}";
+
+        let err = serde_json::from_str::<SymbolTable>(json).unwrap_err();
+
+        assert_eq!(err.to_string(), "the symbol 'LOOP' has no address in the table");
+    }
+
+    #[cfg(feature = "serde")]
+    #[test]
+    fn serialize_memory_map_layout() {
+        let map = "variables 100 103\nreserve DEVICE 102 102\n".parse().unwrap();
+        let mut sym_table = SymbolTable::with_memory_map(&map).unwrap();
+
+        sym_table.insert_variable("i").unwrap();
+        sym_table.inc_ram_address().unwrap();
+
+        let json = serde_json::to_string(&sym_table).unwrap();
+        let mut restored = serde_json::from_str::<SymbolTable>(&json).unwrap();
+
+        assert_eq!(restored, sym_table);
+        assert_eq!(restored.layout(), &map.layout);
+        assert_eq!(restored.insert_variable("j").unwrap(), 101);
+        restored.inc_ram_address().unwrap();
+        assert_eq!(
+            restored.insert_variable("k").unwrap_err().kind(),
+            Some(ErrorKind::ReservedAddress),
+        );
+    }
 }
@@ -0,0 +1,161 @@
+use std::collections::HashSet;
+use std::fs;
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::sync::mpsc;
+use std::time::Duration;
+use notify::{Event, EventKind, RecursiveMode, Watcher};
+use crate::config::Config;
+use crate::error::{Error, ErrorKind, Result};
+use crate::runner::{self, Assembly};
+
+/// How long to wait for further changes before re-assembling.  Editors often save a file as
+/// several separate writes, or by writing a temporary file and renaming it over the original.
+///
+const SETTLE_TIME: Duration = Duration::from_millis(100);
+
+/// Assembles each Config, then re-assembles it every time its input file changes.
+///
+/// The result of every assembly is passed to 'report', which returns whether to keep watching.
+/// Errors in the programs themselves are only reported, so watching continues until 'report'
+/// breaks or the filesystem can no longer be watched.
+///
+/// The directory containing each input file is watched, rather than the file itself, so that
+/// changes are still seen after an editor replaces the file.
+///
+/// # Examples
+///
+/// '''
+/// use std::ops::ControlFlow;
+/// use assembler::config::Config;
+/// use assembler::watch;
+///
+/// watch::watch(&[Config::for_input("Fill.asm")], |config, result| {
+///     match result {
+///         Ok(assembly) => println!("{} words", assembly.words.len()),
+///         Err(e) => println!("{}", e),
+///     }
+///     ControlFlow::Continue(())
+/// }).unwrap();
+/// '''
+pub fn watch<F>(configs: &[Config], mut report: F) -> Result<()>
+    where F: FnMut(&Config, Result<Assembly>) -> ControlFlow<()>
+{
+    let paths = configs.iter()
+        .map(|config| absolute_path(&config.infile))
+        .collect::<Result<Vec<PathBuf>>>()?;
+
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender).map_err(watch_error)?;
+
+    let dirs: HashSet<&Path> = paths.iter().filter_map(|path| path.parent()).collect();
+
+    for dir in dirs {
+        watcher.watch(dir, RecursiveMode::NonRecursive).map_err(watch_error)?;
+    }
+
+    for config in configs {
+        if report(config, runner::run(config.clone())).is_break() {
+            return Ok(());
+        }
+    }
+
+    while let Ok(event) = receiver.recv() {
+        let mut changed = HashSet::new();
+
+        record_change(event.map_err(watch_error)?, &mut changed);
+
+        while let Ok(event) = receiver.recv_timeout(SETTLE_TIME) {
+            record_change(event.map_err(watch_error)?, &mut changed);
+        }
+
+        for (config, path) in configs.iter().zip(paths.iter()) {
+            if changed.contains(path) && report(config, runner::run(config.clone())).is_break() {
+                return Ok(());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Adds the paths affected by an event to the set of changed paths.  Events that only read a
+/// file are ignored.
+///
+fn record_change(event: Event, changed: &mut HashSet<PathBuf>) {
+    if !matches!(event.kind, EventKind::Access(_)) {
+        changed.extend(event.paths);
+    }
+}
+
+/// Returns the absolute path of a file in the same form as the paths of watch events, which
+/// are resolved from the canonical path of the watched directory.  The file itself need not
+/// exist.
+///
+fn absolute_path(path: &Path) -> Result<PathBuf> {
+    let dir = match path.parent() {
+        Some(dir) if dir != Path::new("") => dir,
+        _ => Path::new("."),
+    };
+
+    let dir = fs::canonicalize(dir).map_err(|e| Error::from(e).with_file(dir))?;
+
+    match path.file_name() {
+        Some(name) => Ok(dir.join(name)),
+        None => Err(Error::new(ErrorKind::MissingArguments)),
+    }
+}
+
+fn watch_error(err: notify::Error) -> Error {
+    Error::with_message(
+        ErrorKind::WatchFailed,
+        format!("the input files could not be watched for changes: {}", err),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::thread;
+
+    #[test]
+    fn check_absolute_path() {
+        let dir = fs::canonicalize(".").unwrap();
+
+        assert_eq!(absolute_path(Path::new("Prog.asm")).unwrap(), dir.join("Prog.asm"));
+        assert_eq!(absolute_path(Path::new("./src/x.asm")).unwrap(), dir.join("src/x.asm"));
+        assert!(absolute_path(Path::new("missing/x.asm")).is_err());
+    }
+
+    #[test]
+    fn reassemble_on_change() {
+        let dir = tempfile::tempdir().unwrap();
+
+        let infile = dir.path().join("Prog.asm");
+        fs::write(&infile, "@1\n").unwrap();
+
+        let config = Config::for_input(&infile);
+        let mut results = Vec::new();
+
+        watch(&[config], |_, result| {
+            results.push(result.map(|assembly| assembly.words));
+
+            match results.len() {
+                1 => {
+                    // Change the file from another thread, while the events are being watched.
+                    let infile = infile.clone();
+
+                    thread::spawn(move || {
+                        thread::sleep(Duration::from_millis(200));
+                        fs::write(infile, "@1\nD=D+Q\n").unwrap();
+                    });
+                    ControlFlow::Continue(())
+                },
+                _ => ControlFlow::Break(()),
+            }
+        }).unwrap();
+
+        assert_eq!(results[0].as_ref().unwrap(), &vec![1]);
+        assert_eq!(results[1].as_ref().unwrap_err().kind(), Some(ErrorKind::InvalidComp));
+    }
+}
@@ -1,4 +1,5 @@
-use std::fs::File;
+use std::ffi::OsStr;
+use std::fs::{self, File};
 use std::io::{BufReader, BufRead};
 use std::path::PathBuf;
 use std::process::Command;
@@ -32,7 +33,11 @@ fn check_output_against_file(infile_relative_path: &str, compfile_relative_path:
     compfile_path.push(compfile_relative_path);
     let compfile = File::open(compfile_path).unwrap();
 
-    let mut outfile_buff = BufReader::new(temp_outfile);
+    // The output is renamed into place, so reopen it by path rather than reading through the
+    // handle to the original temporary file.
+    let outfile = File::open(temp_outfile.path()).unwrap();
+
+    let mut outfile_buff = BufReader::new(outfile);
     let mut compfile_buff = BufReader::new(compfile);
 
     let mut outfile_line = String::new();
@@ -128,6 +133,7 @@ fn test_invalid_args(infile_relative_path: &str, temp_outfile: NamedTempFile, er
     infile_path.push(infile_relative_path);
 
     cmd.arg(infile_path)
+        .arg("-o")
         .arg(temp_outfile.path())
         .assert()
         .failure()
@@ -168,22 +174,86 @@ fn invalid_outfile_extension() {
 
 #[test]
 fn missing_arguments() {
-    let mut cmd_0 = Command::cargo_bin("assembler").unwrap();
-    let mut cmd_1 = Command::cargo_bin("assembler").unwrap();
-
-    let mut infile_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
-    infile_path.push("testfiles/add/Add.asm");
+    let mut cmd = Command::cargo_bin("assembler").unwrap();
 
     // No arguments.
-    cmd_0.assert()
+    cmd.assert()
         .failure()
-        .stderr(predicate::str::contains("input and output filenames were not provided"));
+        .stderr(predicate::str::contains("an input filename was not provided"));
+}
+
+#[test]
+fn default_outfile() {
+    let mut cmd = Command::cargo_bin("assembler").unwrap();
 
-    // 1 of 2 arguments provided.
-    cmd_1.arg(infile_path)
+    let dir = tempfile::tempdir().unwrap();
+    let infile_path = dir.path().join("Add.asm");
+
+    let mut source_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    source_path.push("testfiles/add/Add.asm");
+    fs::copy(source_path, &infile_path).unwrap();
+
+    // Only the input file is provided, so the output is written alongside it.
+    cmd.arg(&infile_path)
         .assert()
-        .failure()
-        .stderr(predicate::str::contains("output filename not provided"));
+        .success()
+        .stderr(predicate::str::contains("(6 words)"));
+
+    let mut compfile_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    compfile_path.push("testfiles/add/Add_comp.hack");
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("Add.hack")).unwrap(),
+        fs::read_to_string(compfile_path).unwrap(),
+    );
+}
+
+#[test]
+fn listing_symbols_and_format() {
+    let mut cmd = Command::cargo_bin("assembler").unwrap();
+
+    let mut infile_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    infile_path.push("testfiles/max/Max.asm");
+
+    let dir = tempfile::tempdir().unwrap();
+    let outfile_path = dir.path().join("Max.hex");
+    let listing_path = dir.path().join("Max.lst");
+    let symbols_path = dir.path().join("Max.sym");
+
+    cmd.arg("--quiet")
+        .arg("--format=hex")
+        .arg(format!("--listing={}", listing_path.display()))
+        .arg(format!("--symbols={}", symbols_path.display()))
+        .arg("-o")
+        .arg(&outfile_path)
+        .arg(infile_path)
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+
+    let output = fs::read_to_string(outfile_path).unwrap();
+    assert!(output.starts_with("0000\nfc10\n0001\nf4d0\n"));
+
+    let listing = fs::read_to_string(listing_path).unwrap();
+    assert!(listing.contains("00010  0000000000000000     @R0             \n"));
+
+    let symbols = fs::read_to_string(symbols_path).unwrap();
+    assert!(symbols.contains("label OUTPUT_FIRST 10\n"));
+}
+
+#[test]
+fn help_and_version() {
+    Command::cargo_bin("assembler").unwrap()
+        .arg("--help")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Usage: assembler [OPTIONS] <INPUT.asm> [OUTPUT]"));
+
+    Command::cargo_bin("assembler").unwrap()
+        .arg("--version")
+        .assert()
+        .success()
+        .stdout(predicate::str::contains(env!("CARGO_PKG_VERSION")));
 }
 
 #[test]
@@ -204,3 +274,634 @@ fn infile_does_not_exist() {
         .failure()
         .stderr(predicate::str::contains("No such file or directory"));
 }
+
+/// The below integration tests verify that a failed assembly never leaves a partial or stale
+/// output file behind, and that output file errors are reported rather than causing a panic.
+///
+#[test]
+fn syntax_error_leaves_outfile_untouched() {
+    let mut cmd = Command::cargo_bin("assembler").unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let infile_path = dir.path().join("Invalid.asm");
+    let outfile_path = dir.path().join("Invalid.hack");
+
+    fs::write(&infile_path, "@2\nD=A\nnotacommand\n").unwrap();
+    fs::write(&outfile_path, "previous output\n").unwrap();
+
+    cmd.arg(&infile_path)
+        .arg(&outfile_path)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid syntax"));
+
+    assert_eq!(fs::read_to_string(&outfile_path).unwrap(), "previous output\n");
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn syntax_error_creates_no_outfile() {
+    let mut cmd = Command::cargo_bin("assembler").unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let infile_path = dir.path().join("Invalid.asm");
+    let outfile_path = dir.path().join("Invalid.hack");
+
+    fs::write(&infile_path, "@2\nD=A\nnotacommand\n").unwrap();
+
+    cmd.arg(&infile_path)
+        .arg(&outfile_path)
+        .assert()
+        .failure();
+
+    assert!(!outfile_path.exists());
+}
+
+#[test]
+fn outfile_directory_does_not_exist() {
+    let mut cmd = Command::cargo_bin("assembler").unwrap();
+
+    let mut infile_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    infile_path.push("testfiles/add/Add.asm");
+
+    let dir = tempfile::tempdir().unwrap();
+    let outfile_path = dir.path().join("missing").join("Add.hack");
+
+    cmd.arg(infile_path)
+        .arg(&outfile_path)
+        .assert()
+        .failure()
+        .code(1)
+        .stderr(predicate::str::contains("No such file or directory"));
+}
+
+/// The below integration tests verify that errors are reported as structured records when a
+/// machine-readable diagnostics format is requested.
+///
+fn run_with_diagnostics_format(text: &str, format: &str) -> assert_cmd::assert::Assert {
+    let mut cmd = Command::cargo_bin("assembler").unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let infile_path = dir.path().join("Test.asm");
+    let outfile_path = dir.path().join("Test.hack");
+
+    fs::write(&infile_path, text).unwrap();
+
+    cmd.arg(format!("--diagnostics-format={}", format))
+        .arg(&infile_path)
+        .arg(&outfile_path)
+        .assert()
+}
+
+#[test]
+fn json_diagnostics() {
+    run_with_diagnostics_format("@2\nD=A\n  D=D+Q\n", "json")
+        .failure()
+        .stdout(predicate::str::contains("\"severity\":\"error\",\"code\":\"E0205\""))
+        .stdout(predicate::str::contains("\"help\":\"did you mean 'D+A'?\""))
+        .stdout(predicate::str::contains("Test.asm\""))
+        .stdout(predicate::str::contains("\"span\":{\"line\":3,\"column\":5,\"end_column\":8}"));
+}
+
+#[test]
+fn sarif_diagnostics() {
+    run_with_diagnostics_format("@2\nD=A\n  D=D+Q\n", "sarif")
+        .failure()
+        .stdout(predicate::str::contains("\"version\":\"2.1.0\""))
+        .stdout(predicate::str::contains("\"ruleId\":\"E0205\",\"level\":\"error\""))
+        .stdout(predicate::str::contains("\"startLine\":3,\"startColumn\":5,\"endColumn\":8"));
+
+    run_with_diagnostics_format("@2\nD=A\n", "sarif")
+        .success()
+        .stdout(predicate::str::contains("\"results\":[]"));
+}
+
+#[test]
+fn human_diagnostics() {
+    run_with_diagnostics_format("@2\nD=A\n  D=D+Q\n", "human")
+        .failure()
+        .stderr(predicate::str::contains("error[E0205]: comp 'D+Q' is not valid\n"))
+        .stderr(predicate::str::contains("Test.asm:3:5\n"))
+        .stderr(predicate::str::contains("3 |   D=D+Q\n"))
+        .stderr(predicate::str::contains("  |     ^^^ did you mean 'D+A'?\n"));
+}
+
+#[test]
+fn stats_report() {
+    let mut cmd = Command::cargo_bin("assembler").unwrap();
+
+    let mut infile_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    infile_path.push("testfiles/max/Max.asm");
+
+    let temp_outfile = Builder::new()
+        .suffix(".hack")
+        .tempfile()
+        .unwrap();
+
+    cmd.arg("--stats")
+        .arg(infile_path)
+        .arg(temp_outfile.path())
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ROM: 16 / 32768 words (0.0%)\n"))
+        .stdout(predicate::str::contains("RAM: 0 / 16368 variables (0.0%)\n"))
+        .stdout(predicate::str::contains("\njump:\n"))
+        .stdout(predicate::str::contains("  OUTPUT_FIRST      10      2 words\n"));
+}
+
+#[test]
+fn stats_report_with_structured_diagnostics() {
+    let mut cmd = Command::cargo_bin("assembler").unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    let infile_path = dir.path().join("Test.asm");
+    let outfile_path = dir.path().join("Test.hack");
+
+    fs::write(&infile_path, "@2\nD=A\n").unwrap();
+
+    cmd.arg("--stats")
+        .arg("--diagnostics-format=sarif")
+        .arg(&infile_path)
+        .arg(&outfile_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ROM:").not())
+        .stdout(predicate::str::contains("\"results\":[]"))
+        .stderr(predicate::str::contains("ROM: 2 / 32768 words (0.0%)\n"));
+}
+
+#[test]
+fn batch_directory() {
+    let mut cmd = Command::cargo_bin("assembler").unwrap();
+
+    let dir = tempfile::tempdir().unwrap();
+    fs::create_dir(dir.path().join("sub")).unwrap();
+
+    let manifest_dir = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    fs::copy(manifest_dir.join("testfiles/add/Add.asm"), dir.path().join("Add.asm")).unwrap();
+    fs::copy(manifest_dir.join("testfiles/max/Max.asm"), dir.path().join("sub/Max.asm")).unwrap();
+    fs::write(dir.path().join("sub/Bad.asm"), "@1\nD=D+Q\n").unwrap();
+
+    cmd.arg("--jobs=2")
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("Bad.asm:2:3\n"))
+        .stderr(predicate::str::contains("Add.hack (6 words)\n"))
+        .stderr(predicate::str::contains("Max.hack (16 words)\n"))
+        .stderr(predicate::str::contains("Assembled 2 of 3 files, 1 failed\n"));
+
+    assert!(dir.path().join("Add.hack").exists());
+    assert!(dir.path().join("sub/Max.hack").exists());
+    assert!(!dir.path().join("sub/Bad.hack").exists());
+}
+
+#[test]
+fn check_only() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut source_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    source_path.push("testfiles/add/Add.asm");
+    fs::copy(source_path, dir.path().join("Add.asm")).unwrap();
+    fs::write(dir.path().join("Bad.asm"), "@1\nD=D+Q\n").unwrap();
+
+    Command::cargo_bin("assembler").unwrap()
+        .arg("--check")
+        .arg(dir.path().join("Add.asm"))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Add.asm (6 words)\n"));
+
+    Command::cargo_bin("assembler").unwrap()
+        .arg("--check")
+        .arg(dir.path())
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[E0205]: comp 'D+Q' is not valid\n"))
+        .stderr(predicate::str::contains("Checked 1 of 2 files, 1 failed\n"));
+
+    // Only the two source files should exist.
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 2);
+}
+
+#[test]
+fn project_file() {
+    let dir = tempfile::tempdir().unwrap();
+    let src = dir.path().join("src");
+    fs::create_dir(&src).unwrap();
+
+    fs::write(
+        dir.path().join("hackasm.toml"),
+        "format = \"hex\"\nout-dir = \"build\"\nvariable-base = 32\n\n[defines]\nWIDTH = 512\n",
+    ).unwrap();
+    fs::write(src.join("Prog.asm"), "@WIDTH\n@i\n").unwrap();
+
+    Command::cargo_bin("assembler").unwrap()
+        .arg(src.join("Prog.asm"))
+        .assert()
+        .success();
+
+    assert_eq!(fs::read_to_string(dir.path().join("build/Prog.hex")).unwrap(), "0200\n0020\n");
+
+    // Options on the command line take precedence over the project file.
+    Command::cargo_bin("assembler").unwrap()
+        .args(["--format", "hack", "-D", "WIDTH=256"])
+        .arg(src.join("Prog.asm"))
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("build/Prog.hack")).unwrap(),
+        "0000000100000000\n0000000000100000\n",
+    );
+
+    fs::write(dir.path().join("hackasm.toml"), "format = \"hex\"\nout-dir = \n").unwrap();
+
+    Command::cargo_bin("assembler").unwrap()
+        .arg(src.join("Prog.asm"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[E0110]"))
+        .stderr(predicate::str::contains("hackasm.toml:2:"));
+}
+
+#[test]
+fn memory_map_option() {
+    let dir = tempfile::tempdir().unwrap();
+    let map = dir.path().join("machine.hmap");
+    let infile = dir.path().join("Prog.asm");
+
+    fs::write(&map, "predefined hack\nsymbol LED 24577\nvariables 1024 1025\n\
+        reserve LEDS 1025 1025\n").unwrap();
+    fs::write(&infile, "@i\nM=1\n@LED\nM=1\n").unwrap();
+
+    Command::cargo_bin("assembler").unwrap()
+        .args(["-q", "--memory-map"])
+        .arg(&map)
+        .arg(&infile)
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("Prog.hack")).unwrap(),
+        "0000010000000000\n1110111111001000\n0110000000000001\n1110111111001000\n",
+    );
+
+    fs::write(&infile, "@i\n@j\n").unwrap();
+
+    Command::cargo_bin("assembler").unwrap()
+        .arg("--memory-map")
+        .arg(&map)
+        .arg(&infile)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[E0305]: the variable 'j' would be allocated at \
+            1025, which is reserved for 'LEDS'"));
+
+    fs::write(&map, "rom 65536\n").unwrap();
+
+    Command::cargo_bin("assembler").unwrap()
+        .arg("--memory-map")
+        .arg(&map)
+        .arg(&infile)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[E0112]: '65536' is not a ROM size from 1 to 32768"))
+        .stderr(predicate::str::contains("machine.hmap:1:1"));
+}
+
+#[test]
+fn reuse_cached_results() {
+    let dir = tempfile::tempdir().unwrap();
+    let infile = dir.path().join("Prog.asm");
+    let outfile = dir.path().join("Prog.hack");
+
+    fs::write(&infile, "@i\nM=1\n").unwrap();
+    fs::write(dir.path().join("hackasm.toml"), "cache-dir = \"cache\"\n").unwrap();
+
+    Command::cargo_bin("assembler").unwrap().arg("-q").arg(&infile).assert().success();
+
+    let entries: Vec<PathBuf> = fs::read_dir(dir.path().join("cache")).unwrap()
+        .map(|entry| entry.unwrap().path())
+        .collect();
+
+    assert_eq!(entries.len(), 1);
+    assert_eq!(entries[0].extension(), Some(OsStr::new("hcache")));
+
+    // The output is made from the cached entry while the source is unchanged.
+    let entry = fs::read_to_string(&entries[0]).unwrap();
+    fs::write(&entries[0], entry.replace("word 0010 1", "word 0011 1")).unwrap();
+
+    Command::cargo_bin("assembler").unwrap().arg("-q").arg(&infile).assert().success();
+
+    assert!(fs::read_to_string(&outfile).unwrap().starts_with("0000000000010001\n"));
+
+    Command::cargo_bin("assembler").unwrap()
+        .args(["-q", "--no-cache"])
+        .arg(&infile)
+        .assert()
+        .success();
+
+    assert!(fs::read_to_string(&outfile).unwrap().starts_with("0000000000010000\n"));
+
+    fs::write(&infile, "@j\n@i\n").unwrap();
+
+    Command::cargo_bin("assembler").unwrap().arg("-q").arg(&infile).assert().success();
+
+    assert_eq!(
+        fs::read_to_string(&outfile).unwrap(),
+        "0000000000010000\n0000000000010001\n",
+    );
+    assert_eq!(fs::read_dir(dir.path().join("cache")).unwrap().count(), 2);
+}
+
+#[test]
+fn compile_object() {
+    let dir = tempfile::tempdir().unwrap();
+    let infile_path = dir.path().join("Main.asm");
+
+    fs::write(&infile_path, "(LOOP)\n@count\nM=M+1\n@SCREEN\n@LOOP\n0;JMP\n").unwrap();
+
+    Command::cargo_bin("assembler").unwrap()
+        .arg("--quiet")
+        .arg("-c")
+        .arg(&infile_path)
+        .assert()
+        .success()
+        .stderr(predicate::str::is_empty());
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("Main.hobj")).unwrap(),
+        "hackobj 1\nword 0000\nword fdc8\nword 4000\nword 0000\nword ea87\n\
+            local Main.LOOP 0\nextern Main.count 0\nreloc 3\n",
+    );
+}
+
+#[test]
+fn hack_link() {
+    let dir = tempfile::tempdir().unwrap();
+    let main = ".global LOOP count\n(LOOP)\n@count\nM=M+1\n@Lib.reset\n0;JMP\n";
+    let lib = ".global Lib.reset LOOP count\n(Lib.reset)\n@count\nM=0\n@LOOP\n0;JMP\n";
+
+    fs::write(dir.path().join("Main.asm"), main).unwrap();
+    fs::write(dir.path().join("Lib.asm"), lib).unwrap();
+    fs::write(dir.path().join("Whole.asm"), format!("{}{}", main, lib)).unwrap();
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["asm", "-q", "-c"])
+        .arg(dir.path().join("Main.asm"))
+        .arg(dir.path().join("Lib.asm"))
+        .assert()
+        .success();
+
+    Command::cargo_bin("hack").unwrap()
+        .arg("link")
+        .arg(dir.path().join("Main.hobj"))
+        .arg(dir.path().join("Lib.hobj"))
+        .arg("-o")
+        .arg(dir.path().join("Prog.hack"))
+        .arg(format!("--map={}", dir.path().join("Prog.map").display()))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Linked 2 objects into "))
+        .stderr(predicate::str::contains("Prog.hack (8 words)\n"));
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["asm", "-q"])
+        .arg(dir.path().join("Whole.asm"))
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("Prog.hack")).unwrap(),
+        fs::read_to_string(dir.path().join("Whole.hack")).unwrap(),
+    );
+
+    let map = fs::read_to_string(dir.path().join("Prog.map")).unwrap();
+    assert!(map.contains("Lib.hobj 4 4\nlabel Lib.reset 4 "));
+    assert!(map.ends_with("variable count 16\n"));
+
+    Command::cargo_bin("hack").unwrap()
+        .arg("link")
+        .arg(dir.path().join("Main.hobj"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[E0303]: 'Lib.reset' is used as a jump target in"));
+}
+
+#[test]
+fn hack_link_strip() {
+    let dir = tempfile::tempdir().unwrap();
+
+    fs::write(dir.path().join("Main.asm"), ".global Math.inc\n@Math.inc\n0;JMP\n").unwrap();
+    fs::write(dir.path().join("Math.asm"), ".global Math.inc Math.dec\n(Math.dec)\nD=D-1\n\
+        (Math.inc)\nD=D+1\n(END)\n@END\n0;JMP\n").unwrap();
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["asm", "-q", "-c"])
+        .arg(dir.path())
+        .assert()
+        .success();
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["link", "--strip"])
+        .arg(dir.path().join("Main.hobj"))
+        .arg(dir.path().join("Math.hobj"))
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("Stripped 'Math.dec' in "))
+        .stderr(predicate::str::contains("Math.hobj (1 words)\nSaved 1 words\n"))
+        .stderr(predicate::str::contains("Main.hack (5 words)\n"));
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("Main.hack")).unwrap(),
+        "0000000000000010\n1110101010000111\n1110011111010000\n\
+            0000000000000011\n1110101010000111\n",
+    );
+}
+
+#[test]
+fn hack_archive() {
+    let dir = tempfile::tempdir().unwrap();
+
+    fs::write(dir.path().join("Main.asm"), "@Math.inc\n0;JMP\n").unwrap();
+    fs::write(dir.path().join("Math.asm"), ".global Math.inc\n(Math.inc)\nD=D+1\n").unwrap();
+    fs::write(dir.path().join("Screen.asm"), "(Screen.clear)\n@SCREEN\n").unwrap();
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["asm", "-q", "-c"])
+        .arg(dir.path())
+        .assert()
+        .success();
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["archive", "-o"])
+        .arg(dir.path().join("os.hlib"))
+        .arg(dir.path().join("Math.hobj"))
+        .arg(dir.path().join("Screen.hobj"))
+        .assert()
+        .success();
+
+    let library = fs::read_to_string(dir.path().join("os.hlib")).unwrap();
+    assert!(library.starts_with("hacklib 1\nmember Math.hobj\nhackobj 1\n"));
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["link", "-q", "--map"])
+        .arg(dir.path().join("Main.map"))
+        .arg(dir.path().join("os.hlib"))
+        .arg(dir.path().join("Main.hobj"))
+        .assert()
+        .success();
+
+    assert_eq!(
+        fs::read_to_string(dir.path().join("Main.hack")).unwrap(),
+        "0000000000000010\n1110101010000111\n1110011111010000\n",
+    );
+
+    let map = fs::read_to_string(dir.path().join("Main.map")).unwrap();
+    assert!(map.contains("os.hlib(Math.hobj) 2 1\n"));
+    assert!(!map.contains("Screen"));
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["archive", "-o"])
+        .arg(dir.path().join("bad.hlib"))
+        .arg(dir.path().join("Main.asm"))
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("only '.hobj' accepted"));
+}
+
+#[test]
+fn hack_asm_and_disasm() {
+    let dir = tempfile::tempdir().unwrap();
+
+    let mut source_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    source_path.push("testfiles/add/Add.asm");
+    fs::copy(source_path, dir.path().join("Add.asm")).unwrap();
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["asm", "--format", "hex"])
+        .arg(dir.path().join("Add.asm"))
+        .assert()
+        .success();
+
+    Command::cargo_bin("hack").unwrap()
+        .arg("disasm")
+        .arg(dir.path().join("Add.hex"))
+        .assert()
+        .success()
+        .stdout("@2\nD=A\n@3\nD=D+A\n@0\nM=D\n");
+
+    fs::write(dir.path().join("Odd.hack"), "0000000000000010\n1110111110000000\n").unwrap();
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["disasm", "--addresses", "--bits"])
+        .arg(dir.path().join("Odd.hack"))
+        .assert()
+        .success()
+        .stdout("00000  0000000000000010  @2\n\
+            00001  1110111110000000  null=alu(a=0,zx,nx,zy,ny,f) // undocumented comp\n");
+}
+
+#[test]
+fn hack_run() {
+    let mut source_path = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+    source_path.push("testfiles/max/Max.asm");
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["run", "--set", "0=3", "--set", "1=-9", "--ram", "0..3"])
+        .arg(source_path)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("Reached an endless loop after "))
+        .stdout(predicate::str::ends_with("RAM[0] = 3\nRAM[1] = -9\nRAM[2] = 3\n"));
+}
+
+#[test]
+fn hack_fmt() {
+    let dir = tempfile::tempdir().unwrap();
+    let infile = dir.path().join("Prog.asm");
+
+    fs::write(&infile, "(LOOP)\n@LOOP   \n  0;JMP//forever\n").unwrap();
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["fmt", "--check"])
+        .arg(&infile)
+        .assert()
+        .failure()
+        .stdout(predicate::str::contains("Prog.asm is not formatted\n"));
+
+    Command::cargo_bin("hack").unwrap()
+        .arg("fmt")
+        .arg(dir.path())
+        .assert()
+        .success();
+
+    assert_eq!(fs::read_to_string(&infile).unwrap(), "(LOOP)\n    @LOOP\n    0;JMP //forever\n");
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["fmt", "--check"])
+        .arg(&infile)
+        .assert()
+        .success();
+}
+
+#[test]
+fn hack_fmt_machine_code() {
+    let dir = tempfile::tempdir().unwrap();
+    let infile = dir.path().join("Prog.hack");
+
+    fs::write(&infile, "0000000000000010\n1110110000010000\n").unwrap();
+
+    Command::cargo_bin("hack").unwrap()
+        .arg("fmt")
+        .arg(&infile)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("invalid input file extension, only '.asm' accepted"));
+
+    assert_eq!(fs::read_to_string(&infile).unwrap(), "0000000000000010\n1110110000010000\n");
+}
+
+#[test]
+fn hack_lint_and_info() {
+    let dir = tempfile::tempdir().unwrap();
+    let infile = dir.path().join("Prog.asm");
+
+    fs::write(&infile, "(UNUSED)\n@count\nM=1\n@count\nM=M+1\n").unwrap();
+
+    Command::cargo_bin("hack").unwrap()
+        .arg("lint")
+        .arg(&infile)
+        .assert()
+        .success()
+        .stderr(predicate::str::contains("warning[W0101]: label 'UNUSED' is never used\n"))
+        .stderr(predicate::str::contains("Linted 1 files: 0 errors, 1 warnings\n"));
+
+    Command::cargo_bin("hack").unwrap()
+        .args(["lint", "--deny", "unused-label"])
+        .arg(&infile)
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("error[W0101]"));
+
+    Command::cargo_bin("hack").unwrap()
+        .arg("info")
+        .arg(&infile)
+        .assert()
+        .success()
+        .stdout(predicate::str::contains("ROM: 4 / 32768 words"))
+        .stdout(predicate::str::contains("\nVariables:\n  count     16\n"));
+
+    // No output files are written by either command.
+    assert_eq!(fs::read_dir(dir.path()).unwrap().count(), 1);
+}
+
+#[test]
+fn hack_unknown_command() {
+    Command::cargo_bin("hack").unwrap()
+        .arg("assemble")
+        .assert()
+        .failure()
+        .stderr(predicate::str::contains("unrecognised command 'assemble'"));
+}